@@ -0,0 +1,18 @@
+#![no_main]
+
+use kfg::{FileId, FormatError, Kfg};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(ast) = Kfg::parse(data) else {
+        return;
+    };
+
+    // whatever parses is written back as something that parses
+    let written = ast.to_kfg();
+    assert!(Kfg::parse(written.as_bytes()).is_ok(), "{written:?} doesn't parse");
+
+    // formatting never changes what a file means
+    assert!(!matches!(Kfg::format(data), Err(FormatError::Changed)), "formatting changed {data:?}");
+    let _ = Kfg::parse_spanned(data, FileId(0));
+});
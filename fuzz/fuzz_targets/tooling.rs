@@ -0,0 +1,30 @@
+#![no_main]
+
+use kfg::{edit, incremental::Parse, syntax, Kfg, ParseOptions};
+use libfuzzer_sys::fuzz_target;
+
+// the editor-facing functions see every half-typed state of a file
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let _ = syntax::highlight(source);
+    let _ = syntax::outline(source);
+    let _ = edit::locate(source);
+    let _ = edit::keys(source);
+
+    for offset in (0..=source.len()).filter(|&offset| source.is_char_boundary(offset)) {
+        let _ = edit::scope_at(source, offset);
+    }
+
+    // deleting the second half again must agree with a full parse
+    let half = (0..=source.len() / 2).rev().find(|&offset| source.is_char_boundary(offset)).unwrap_or(0);
+    let mut parse = Parse::new(source.into(), &ParseOptions::default());
+    parse.edit(edit::Span { start: half, end: source.len() }, "");
+
+    match (parse.ast(), Kfg::parse(source[..half].as_bytes())) {
+        (Ok(ast), Ok(full)) => assert_eq!(**ast, *full),
+        (ast, full) => assert_eq!(ast.is_ok(), full.is_ok()),
+    }
+});
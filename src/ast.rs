@@ -1,10 +1,14 @@
-use crate::{parser::ParserError, Token, TokenKind};
+use crate::{
+    parser::{self, Context, ParseOptions, ParserError},
+    span::Sources,
+    Token, TokenKind, TokenPosition,
+};
 use std::{collections::HashMap, iter::Peekable, slice::Iter};
 
 const INDENT: fn(String) -> String = |s| s.replace('\n', "\n  ");
 
 #[derive(Debug, Default, Clone)]
-pub struct Ast(pub(crate) HashMap<String, Node>);
+pub struct Ast(pub(crate) HashMap<String, Node>, pub(crate) Sources);
 
 impl Ast {
     pub fn assignments(&self) -> usize {
@@ -21,10 +25,26 @@ impl Ast {
 
         format!("{{{string}}}")
     }
+
+    /// Finds the node at a `key::path`, numeric segments index into arrays
+    pub fn lookup(&self, path: &str) -> Option<&Node> {
+        let mut segments = path.split("::");
+        let mut node = self.0.get(segments.next()?)?;
+
+        for segment in segments {
+            node = match node {
+                Node::Dict(dict) => dict.get(segment)?,
+                Node::Array(array) => array.get(segment.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+
+        Some(node)
+    }
 }
 
 fn get_len(dict: &HashMap<String, Node>) -> usize {
-    dict.iter().map(|(_, node)| {
+    dict.values().map(|node| {
         if let Node::Dict(dict) = node {
             get_len(dict)
         } else {
@@ -79,6 +99,11 @@ pub enum Node {
 }
 
 impl Node {
+    /// Types a literal with the kfg rules, keeping it as a string when it isn't one
+    pub fn typed(value: &str) -> Self {
+        Self::try_from(value).unwrap_or_else(|_| Self::String(value.into()))
+    }
+
     pub fn inline(&self) -> String {
         match self {
             Node::Array(value) => {
@@ -164,24 +189,12 @@ impl std::fmt::Display for Node {
     }
 }
 
+/// A bare string has no position, the error points at a token at the default one
 impl TryFrom<String> for Node {
     type Error = ParserError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        match value.as_str() {
-            "true" => Ok(Self::Bool(true)),
-            "false" => Ok(Self::Bool(false)),
-            "null" => Ok(Self::Null),
-            _ => {
-                if let Ok(value) = value.parse::<i64>() {
-                    Ok(Self::Integer(value))
-                } else if let Ok(value) = value.parse::<f64>() {
-                    Ok(Self::Float(value))
-                } else {
-                    Err(ParserError::InvalidSymbol(value.clone()))
-                }
-            }
-        }
+        Node::try_from(&Token::new(TokenKind::Symbol(value), TokenPosition::default()))
     }
 }
 
@@ -197,9 +210,23 @@ impl TryFrom<&Token> for Node {
     type Error = ParserError;
 
     fn try_from(value: &Token) -> Result<Self, Self::Error> {
-        match value.kind {
-            TokenKind::Symbol(ref symbol) => symbol.clone().try_into(),
-            _ => Err(ParserError::InvalidToken(value.clone())),
+        let TokenKind::Symbol(ref symbol) = value.kind else {
+            return Err(ParserError::InvalidToken(value.clone()));
+        };
+
+        match symbol.as_str() {
+            "true" => Ok(Self::Bool(true)),
+            "false" => Ok(Self::Bool(false)),
+            "null" => Ok(Self::Null),
+            _ => {
+                if let Ok(value) = symbol.parse::<i64>() {
+                    Ok(Self::Integer(value))
+                } else if let Ok(value) = symbol.parse::<f64>() {
+                    Ok(Self::Float(value))
+                } else {
+                    Err(ParserError::InvalidSymbol(value.clone()))
+                }
+            }
         }
     }
 }
@@ -208,17 +235,7 @@ impl TryFrom<&mut Peekable<Iter<'_, Token>>> for Node {
     type Error = ParserError;
 
     fn try_from(iter: &mut Peekable<Iter<'_, Token>>) -> Result<Self, Self::Error> {
-        use TokenKind::{OpenBracket, OpenCurly, Quote, Symbol};
-
-        let token = *iter.peek().unwrap();
-
-        let node = match token.kind {
-            Symbol(_) => iter.parse_symbol(),
-            Quote => iter.parse_string(),
-            OpenBracket => iter.parse_array(),
-            OpenCurly => iter.parse_dict(),
-            _ => Err(ParserError::UnreachableToken(token.clone())),
-        };
+        let node = parser::value(iter, &mut Context::new(&ParseOptions::default()), 0);
 
         if let Ok(node) = &node {
             trace!("{}", node.inline());
@@ -258,7 +275,8 @@ impl ParseTokens for Peekable<Iter<'_, Token>> {
 
         let node = {
             if let Some(&dot) = self.peek() {
-                if let TokenKind::Dot = dot.kind {
+                // a decimal point touches the digits before it, `80 .key` is a dict entry
+                if let (TokenKind::Dot, true) = (&dot.kind, token.touches(dot)) {
                     // skip dot
                     self.next();
 
@@ -269,10 +287,12 @@ impl ParseTokens for Peekable<Iter<'_, Token>> {
                         ));
                     }
 
-                    let left = token.kind.as_str();
-                    let right = self.next().unwrap().kind.as_str();
+                    // the whole number is reported when it doesn't read
+                    let mut number = token.clone();
+                    let _ = number.join(dot);
+                    let _ = number.join(self.next().unwrap());
 
-                    Some(Node::try_from(format!("{left}.{right}"))?)
+                    Some(Node::try_from(&number)?)
                 } else {
                     None
                 }
@@ -313,92 +333,10 @@ impl ParseTokens for Peekable<Iter<'_, Token>> {
     }
 
     fn parse_array(&mut self) -> Result<Node, ParserError> {
-        let mut array = Vec::new();
-
-        // skip first open curly
-        self.next();
-
-        while let Some(&token) = self.peek() {
-            match token.kind {
-                TokenKind::CloseBracket => {
-                    self.next();
-                    break;
-                }
-                TokenKind::NewLine | TokenKind::Space | TokenKind::Tab | TokenKind::Comma => {
-                    self.next();
-                    continue;
-                }
-                TokenKind::Symbol(_)
-                | TokenKind::Quote
-                | TokenKind::OpenBracket
-                | TokenKind::OpenCurly => {
-                    array.push(Node::try_from(&mut *self)?);
-                }
-                _ => {
-                    return Err(ParserError::InvalidToken(token.clone()));
-                }
-            }
-        }
-
-        Ok(Node::Array(array))
+        Node::try_from(self)
     }
 
     fn parse_dict(&mut self) -> Result<Node, ParserError> {
-        let mut dict = HashMap::new();
-
-        // skip first open curly
-        self.next();
-
-        while let Some(&token) = self.peek() {
-            match token.kind {
-                TokenKind::CloseCurly => {
-                    self.next();
-                    break;
-                }
-                TokenKind::NewLine | TokenKind::Space | TokenKind::Tab | TokenKind::Comma => {
-                    self.next();
-                    continue;
-                }
-                TokenKind::Dot => {
-                    self.next();
-
-                    if self.peek().is_none() {
-                        return Err(ParserError::UnexpectedEOF(token.clone()));
-                    }
-
-                    let token = self.next().unwrap();
-
-                    if let TokenKind::Symbol(ref key) = token.kind {
-                        if self.peek().is_none() {
-                            return Err(ParserError::UnexpectedEOF(token.clone()));
-                        }
-
-                        match self.next() {
-                            Some(token) if matches!(token.kind, TokenKind::Colon) => {
-                                match self.peek() {
-                                    Some(&token) if matches!(token.kind, TokenKind::Colon) => {
-                                        return Err(ParserError::ScopeInsideDict(token.clone()));
-                                    }
-                                    None => return Err(ParserError::UnexpectedEOF(token.clone())),
-                                    _ => {}
-                                }
-
-                                let node = Node::try_from(&mut *self)?;
-                                dict.insert(key.clone(), node);
-                            }
-                            None => return Err(ParserError::UnexpectedEOF(token.clone())),
-                            _ => return Err(ParserError::MismatchedTokenType(TokenKind::Colon, token.clone())),
-                        }
-                    } else {
-                        return Err(ParserError::MismatchedTokenType(TokenKind::Symbol("".into()), token.clone()));
-                    }
-                }
-                _ => {
-                    return Err(ParserError::InvalidToken(token.clone()));
-                }
-            }
-        }
-
-        Ok(Node::Dict(dict))
+        Node::try_from(self)
     }
 }
\ No newline at end of file
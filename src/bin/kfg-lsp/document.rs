@@ -0,0 +1,165 @@
+use crate::rpc::{integer, object};
+use kfg::{convert, span, Ast, Format, Kfg, Node, ParserError, Token};
+use std::path::{Path, PathBuf};
+
+/// An open file, with the last version of it that parsed
+pub struct Document {
+    pub text: String,
+    pub ast: Option<Ast>,
+    pub error: Option<ParserError>,
+    lines: Vec<usize>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let mut document = Self {
+            text: String::new(),
+            ast: None,
+            error: None,
+            lines: vec![],
+        };
+
+        document.update(text);
+        document
+    }
+
+    /// Replaces the text, keeping the previous tree if the new one doesn't parse
+    pub fn update(&mut self, text: String) {
+        self.lines = span::line_starts(&text);
+
+        match Kfg::parse(text.as_bytes()) {
+            Ok(ast) => {
+                self.ast = Some(ast);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+
+        self.text = text;
+    }
+
+    fn line(&self, line: usize) -> &str {
+        let start = self.lines.get(line).copied().unwrap_or(self.text.len());
+        let end = self.lines.get(line + 1).copied().unwrap_or(self.text.len());
+
+        &self.text[start..end]
+    }
+
+    /// Converts an LSP position, which counts UTF-16 units, into a byte offset
+    pub fn offset(&self, line: usize, character: usize) -> usize {
+        let Some(&start) = self.lines.get(line) else {
+            return self.text.len();
+        };
+
+        let mut units = 0;
+
+        for (i, c) in self.line(line).char_indices() {
+            if units >= character || matches!(c, '\n' | '\r') {
+                return start + i;
+            }
+
+            units += c.len_utf16();
+        }
+
+        start + self.line(line).len()
+    }
+
+    /// Converts a byte offset into an LSP position
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.lines.partition_point(|&start| start <= offset) - 1;
+        let start = self.lines[line];
+
+        let character = self.text[start..offset].chars().map(char::len_utf16).sum();
+
+        (line, character)
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> Node {
+        let position = |offset| {
+            let (line, character) = self.position(offset);
+            object([("line", integer(line)), ("character", integer(character))])
+        };
+
+        object([("start", position(start)), ("end", position(end))])
+    }
+
+    /// The range of a token, from the bytes it was lexed from
+    pub fn token_range(&self, token: &Token) -> Node {
+        let end = token.position.end.min(self.text.len());
+
+        self.range(token.position.offset.min(end), end)
+    }
+}
+
+/// Turns a `file://` URI into a path, decoding `%XX` escapes
+pub fn path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    let mut bytes = vec![];
+    let mut iter = path.bytes();
+
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// Finds the schema of a document, a file whose keys are the ones the document may use
+///
+/// It's named by a `// schema: <path>` comment on the first line, relative to the document,
+/// or else it's the `<name>.schema.kfg` file next to it
+pub fn schema(uri: &str, text: &str) -> Option<Ast> {
+    let document = path(uri)?;
+    let dir = document.parent().unwrap_or(Path::new("."));
+
+    let named = text
+        .lines()
+        .next()
+        .and_then(|line| line.trim().strip_prefix("//"))
+        .and_then(|comment| comment.trim().strip_prefix("schema:"))
+        .map(|schema| dir.join(schema.trim()));
+
+    let schema = named.unwrap_or_else(|| document.with_extension("schema.kfg"));
+
+    let format = Format::from_extension(&schema).unwrap_or(Format::Kfg);
+    let buffer = std::fs::read(&schema).ok()?;
+
+    convert::read(format, &buffer, &mut vec![]).ok()
+}
+
+pub fn type_name(node: &Node) -> &'static str {
+    match node {
+        Node::String(_) => "string",
+        Node::Integer(_) => "integer",
+        Node::Float(_) => "float",
+        Node::Bool(_) => "bool",
+        Node::Array(_) => "array",
+        Node::Dict(_) => "dict",
+        Node::Null => "null",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions() {
+        let document = Document::new("a = 'é'\nb = '𝄞' c\n".into());
+
+        assert_eq!(document.position(0), (0, 0));
+        assert_eq!(document.position(9), (1, 0));
+        assert_eq!(document.position(document.text.find('c').unwrap()), (1, 9));
+        assert_eq!(document.offset(1, 9), document.text.find('c').unwrap());
+        assert_eq!(document.offset(0, 99), 8);
+        assert_eq!(document.offset(9, 0), document.text.len());
+
+        assert_eq!(path("file:///tmp/my%20file.kfg"), Some(PathBuf::from("/tmp/my file.kfg")));
+    }
+}
@@ -0,0 +1,34 @@
+//! Language server for kfg files, speaking LSP over stdio
+
+mod document;
+mod rpc;
+mod server;
+
+fn main() {
+    let mut input = std::io::stdin().lock();
+    let mut output = std::io::stdout().lock();
+
+    let mut server = server::Server::default();
+
+    while let Some(message) = rpc::read(&mut input) {
+        let message = match message {
+            Ok(message) => message,
+            Err(error) => {
+                eprintln!("kfg-lsp: {error}");
+                continue;
+            }
+        };
+
+        for reply in server.handle(&message) {
+            if rpc::write(&mut output, &reply).is_err() {
+                return;
+            }
+        }
+
+        if server.exit {
+            break;
+        }
+    }
+
+    std::process::exit(if server.shutdown { 0 } else { 1 });
+}
@@ -0,0 +1,99 @@
+use kfg::{convert, Ast, Format, Node};
+use std::io::{BufRead, Read, Write};
+
+/// Builds a JSON object out of key, value pairs
+pub fn object<const N: usize>(pairs: [(&str, Node); N]) -> Node {
+    Node::Dict(pairs.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+pub fn integer(value: usize) -> Node {
+    Node::Integer(value as i64)
+}
+
+pub fn string(value: impl Into<String>) -> Node {
+    Node::String(value.into())
+}
+
+/// Bodies longer than this are skipped instead of read into memory
+const MAX_LENGTH: usize = 64 << 20;
+
+/// Reads the next message, `None` once the input is closed
+///
+/// Messages that aren't JSON objects are returned as errors and can be skipped
+pub fn read(input: &mut impl BufRead) -> Option<Result<Ast, String>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Some(Err("missing Content-Length header".into()));
+    };
+
+    if length > MAX_LENGTH {
+        let skipped = std::io::copy(&mut input.by_ref().take(length as u64), &mut std::io::sink()).ok()?;
+
+        return (skipped == length as u64).then(|| Err(format!("message of {length} bytes is over the {MAX_LENGTH} byte limit")));
+    }
+
+    let mut buffer = vec![0; length];
+    input.read_exact(&mut buffer).ok()?;
+
+    Some(convert::read(Format::Json, &buffer, &mut vec![]).map_err(|error| error.to_string()))
+}
+
+pub fn write(output: &mut impl Write, message: &Node) -> std::io::Result<()> {
+    let content = message.to_json();
+
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read() {
+        let body = r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}"#;
+        let input = format!("Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{body}", body.len());
+
+        let mut input = input.as_bytes();
+        let message = read(&mut input).unwrap().unwrap();
+
+        assert_eq!(message.get("method"), Some(&string("initialize")));
+        assert_eq!(message.get("id"), Some(&Node::Integer(1)));
+        assert!(read(&mut input).is_none());
+
+        let mut output = vec![];
+        write(&mut output, &object([("id", Node::Integer(1))])).unwrap();
+
+        assert_eq!(output, b"Content-Length: 9\r\n\r\n{\"id\": 1}");
+
+        let mut input = "Content-Length: 99999999999999\r\n\r\n{}".as_bytes();
+        assert!(read(&mut input).is_none());
+
+        let input = format!("Content-Length: {}\r\n\r\n{}Content-Length: 2\r\n\r\n{{}}", MAX_LENGTH + 1, " ".repeat(MAX_LENGTH + 1));
+        let mut input = input.as_bytes();
+
+        assert!(read(&mut input).unwrap().is_err());
+        assert!(matches!(read(&mut input), Some(Ok(_))));
+    }
+}
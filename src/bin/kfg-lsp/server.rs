@@ -0,0 +1,452 @@
+use crate::{
+    document::{schema, type_name, Document},
+    rpc::{integer, object, string},
+};
+use kfg::{
+    edit,
+    syntax::{self, Category, FoldKind, ItemKind},
+    Ast, Kfg, Node, ParserError, TokenKind,
+};
+use std::collections::HashMap;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// from the LSP specification
+const SYMBOL_STRING: usize = 15;
+const SYMBOL_NUMBER: usize = 16;
+const SYMBOL_BOOLEAN: usize = 17;
+const SYMBOL_ARRAY: usize = 18;
+const SYMBOL_OBJECT: usize = 19;
+const SYMBOL_NULL: usize = 21;
+const COMPLETION_PROPERTY: usize = 10;
+
+const TOKEN_TYPES: [&str; 8] = ["property", "namespace", "string", "regexp", "number", "keyword", "comment", "operator"];
+
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    pub shutdown: bool,
+    pub exit: bool,
+}
+
+/// Where a request points, its document and byte offset
+struct At<'a> {
+    uri: &'a str,
+    document: &'a Document,
+    offset: usize,
+}
+
+impl Server {
+    /// Handles a message, returning the responses and notifications to send back
+    pub fn handle(&mut self, message: &Ast) -> Vec<Node> {
+        let method = match message.get("method") {
+            Some(Node::String(method)) => method.as_str(),
+            _ => return vec![],
+        };
+
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => Some(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Node::Null)
+            }
+            "exit" => {
+                self.exit = true;
+                None
+            }
+            "textDocument/didOpen" => return self.open(message),
+            "textDocument/didChange" => return self.change(message),
+            "textDocument/didClose" => {
+                if let Some(Node::String(uri)) = message.lookup("params::textDocument::uri") {
+                    self.documents.remove(uri);
+                }
+
+                None
+            }
+            "textDocument/formatting" => Some(self.formatting(message)),
+            "textDocument/hover" => Some(self.at(message).and_then(hover).unwrap_or(Node::Null)),
+            "textDocument/definition" => Some(self.at(message).map_or(Node::Null, definition)),
+            "textDocument/documentSymbol" => Some(self.symbols(message)),
+            "textDocument/foldingRange" => Some(self.folding(message)),
+            "textDocument/semanticTokens/full" => Some(self.tokens(message)),
+            "textDocument/completion" => Some(self.at(message).map_or(Node::Null, completion)),
+            _ if id.is_some() => {
+                let error = object([
+                    ("code", Node::Integer(METHOD_NOT_FOUND)),
+                    ("message", string(format!("unknown method '{method}'"))),
+                ]);
+
+                return vec![object([("jsonrpc", string("2.0")), ("id", id.unwrap_or(Node::Null)), ("error", error)])];
+            }
+            _ => None,
+        };
+
+        match (id, result) {
+            (Some(id), result) => vec![object([
+                ("jsonrpc", string("2.0")),
+                ("id", id),
+                ("result", result.unwrap_or(Node::Null)),
+            ])],
+            (None, _) => vec![],
+        }
+    }
+
+    fn open(&mut self, message: &Ast) -> Vec<Node> {
+        let (Some(Node::String(uri)), Some(Node::String(text))) = (
+            message.lookup("params::textDocument::uri"),
+            message.lookup("params::textDocument::text"),
+        ) else {
+            return vec![];
+        };
+
+        self.documents.insert(uri.clone(), Document::new(text.clone()));
+        self.diagnostics(uri)
+    }
+
+    fn change(&mut self, message: &Ast) -> Vec<Node> {
+        let Some(Node::String(uri)) = message.lookup("params::textDocument::uri") else {
+            return vec![];
+        };
+
+        // the full text is synced, so the last change is the whole document
+        let Some(Node::Array(changes)) = message.lookup("params::contentChanges") else {
+            return vec![];
+        };
+
+        let Some(Node::Dict(change)) = changes.last() else {
+            return vec![];
+        };
+
+        let (Some(Node::String(text)), Some(document)) = (change.get("text"), self.documents.get_mut(uri)) else {
+            return vec![];
+        };
+
+        document.update(text.clone());
+        self.diagnostics(uri)
+    }
+
+    fn diagnostics(&self, uri: &str) -> Vec<Node> {
+        let Some(document) = self.documents.get(uri) else {
+            return vec![];
+        };
+
+        let diagnostics = document
+            .error
+            .iter()
+            .map(|error| {
+                let (range, message) = match (error, error.token()) {
+                    (ParserError::Lexer(lexer), Some(token)) => {
+                        (document.token_range(token), format!("{}: {}", error.name(), lexer.message()))
+                    }
+                    (ParserError::Limit(limit, token), _) => {
+                        (document.token_range(token), format!("{}: {}", error.name(), limit.message()))
+                    }
+                    (ParserError::InvalidKey(token), _) => (
+                        document.token_range(token),
+                        match token.kind {
+                            TokenKind::Quote => format!("{}: quoted keys can't contain '::'", error.name()),
+                            _ => format!("{}: invalid key '{}', quote it", error.name(), token.kind.as_str().escape_debug()),
+                        },
+                    ),
+                    (ParserError::InvalidSymbol(token), _) => (
+                        document.token_range(token),
+                        format!("{}: invalid symbol '{}'", error.name(), token.kind.as_str().escape_debug()),
+                    ),
+                    (_, Some(token)) => (
+                        document.token_range(token),
+                        format!("{}: unexpected '{}'", error.name(), token.kind.as_str().escape_debug()),
+                    ),
+                    (_, None) => (document.range(0, 0), error.name().to_string()),
+                };
+
+                object([
+                    ("range", range),
+                    ("severity", integer(1)),
+                    ("source", string("kfg")),
+                    ("message", string(message)),
+                ])
+            })
+            .collect();
+
+        let params = object([("uri", string(uri)), ("diagnostics", Node::Array(diagnostics))]);
+
+        vec![object([
+            ("jsonrpc", string("2.0")),
+            ("method", string("textDocument/publishDiagnostics")),
+            ("params", params),
+        ])]
+    }
+
+    fn document(&self, message: &Ast) -> Option<(&str, &Document)> {
+        let Some(Node::String(uri)) = message.lookup("params::textDocument::uri") else {
+            return None;
+        };
+
+        self.documents
+            .get_key_value(uri)
+            .map(|(uri, document)| (uri.as_str(), document))
+    }
+
+    fn at(&self, message: &Ast) -> Option<At<'_>> {
+        let (uri, document) = self.document(message)?;
+
+        let (Some(Node::Integer(line)), Some(Node::Integer(character))) = (
+            message.lookup("params::position::line"),
+            message.lookup("params::position::character"),
+        ) else {
+            return None;
+        };
+
+        Some(At {
+            uri,
+            document,
+            offset: document.offset(*line as usize, *character as usize),
+        })
+    }
+
+    fn formatting(&self, message: &Ast) -> Node {
+        let Some((_, document)) = self.document(message) else {
+            return Node::Null;
+        };
+
+        match Kfg::format(document.text.as_bytes()) {
+            Ok(formatted) if formatted != document.text => Node::Array(vec![object([
+                ("range", document.range(0, document.text.len())),
+                ("newText", string(formatted)),
+            ])]),
+            _ => Node::Array(vec![]),
+        }
+    }
+
+    fn symbols(&self, message: &Ast) -> Node {
+        let Some((_, document)) = self.document(message) else {
+            return Node::Null;
+        };
+
+        let outline = syntax::outline(&document.text);
+
+        Node::Array(outline.items.iter().map(|item| symbol(document, item)).collect())
+    }
+
+    fn folding(&self, message: &Ast) -> Node {
+        let Some((_, document)) = self.document(message) else {
+            return Node::Null;
+        };
+
+        let folds = syntax::outline(&document.text)
+            .folds
+            .into_iter()
+            .map(|fold| {
+                let kind = match fold.kind {
+                    FoldKind::Comment => "comment",
+                    FoldKind::Dict | FoldKind::Array => "region",
+                };
+
+                object([
+                    ("startLine", integer(document.position(fold.span.start).0)),
+                    ("endLine", integer(document.position(fold.span.end).0)),
+                    ("kind", string(kind)),
+                ])
+            })
+            .collect();
+
+        Node::Array(folds)
+    }
+
+    fn tokens(&self, message: &Ast) -> Node {
+        let Some((_, document)) = self.document(message) else {
+            return Node::Null;
+        };
+
+        object([("data", Node::Array(semantic_tokens(document)))])
+    }
+}
+
+fn capabilities() -> Node {
+    let completion = object([("triggerCharacters", Node::Array(vec![string("."), string(":")]))]);
+
+    let legend = object([
+        ("tokenTypes", Node::Array(TOKEN_TYPES.iter().map(|&token| string(token)).collect())),
+        ("tokenModifiers", Node::Array(vec![])),
+    ]);
+
+    let tokens = object([("legend", legend), ("full", Node::Bool(true))]);
+
+    let capabilities = object([
+        ("textDocumentSync", integer(1)),
+        ("documentFormattingProvider", Node::Bool(true)),
+        ("hoverProvider", Node::Bool(true)),
+        ("definitionProvider", Node::Bool(true)),
+        ("documentSymbolProvider", Node::Bool(true)),
+        ("completionProvider", completion),
+        ("foldingRangeProvider", Node::Bool(true)),
+        ("semanticTokensProvider", tokens),
+    ]);
+
+    object([
+        ("capabilities", capabilities),
+        ("serverInfo", object([("name", string("kfg-lsp")), ("version", string(env!("CARGO_PKG_VERSION")))])),
+    ])
+}
+
+/// The key under the cursor, as the path it names
+fn key_at(at: &At) -> Option<(Vec<String>, edit::Span)> {
+    edit::keys(&at.document.text)
+        .into_iter()
+        .find(|(_, span)| (span.start..=span.end).contains(&at.offset))
+}
+
+fn hover(at: At) -> Option<Node> {
+    let (path, span) = key_at(&at)?;
+    let path = path.join("::");
+    let ast = at.document.ast.as_ref()?;
+    let node = ast.lookup(&path)?;
+
+    let mut value = format!("```kfg\n{path} = {}\n```\n{}", node.to_kfg(), type_name(node));
+
+    // the key's own doc, or else the one its schema gives it
+    let schema = schema(at.uri, &at.document.text);
+    let doc = ast.doc(&path).or_else(|| schema.as_ref()?.doc(&path));
+
+    if let Some(doc) = doc {
+        value.push_str(&format!("\n\n{doc}"));
+    }
+
+    Some(object([
+        ("contents", object([("kind", string("markdown")), ("value", string(value))])),
+        ("range", at.document.range(span.start, span.end)),
+    ]))
+}
+
+/// Every other place the key under the cursor is written, so a `server::tls` segment of
+/// a scoped assignment leads to the `server = {...}` dict and the other assignments into it
+fn definition(at: At) -> Node {
+    let Some((path, current)) = key_at(&at) else {
+        return Node::Null;
+    };
+
+    let locations = edit::keys(&at.document.text)
+        .into_iter()
+        .filter(|(other, span)| *other == path && *span != current)
+        .map(|(_, span)| object([("uri", string(at.uri)), ("range", at.document.range(span.start, span.end))]))
+        .collect();
+
+    Node::Array(locations)
+}
+
+fn completion(at: At) -> Node {
+    let Some(scope) = edit::scope_at(&at.document.text, at.offset) else {
+        return Node::Null;
+    };
+
+    let Some(schema) = schema(at.uri, &at.document.text) else {
+        return Node::Null;
+    };
+
+    let keys = match scope.is_empty() {
+        true => Some(&*schema),
+        false => match schema.lookup(&scope.join("::")) {
+            Some(Node::Dict(dict)) => Some(dict),
+            _ => None,
+        },
+    };
+
+    let mut keys = keys.into_iter().flatten().collect::<Vec<_>>();
+    keys.sort_by_key(|(key, _)| *key);
+
+    let items = keys
+        .into_iter()
+        .map(|(key, node)| {
+            let path = scope.iter().chain([key]).map(String::as_str).collect::<Vec<_>>().join("::");
+
+            let mut item = object([
+                ("label", string(key.clone())),
+                ("kind", integer(COMPLETION_PROPERTY)),
+                ("detail", string(type_name(node))),
+            ]);
+
+            if let (Some(doc), Node::Dict(item)) = (schema.doc(&path), &mut item) {
+                item.insert("documentation".into(), string(doc));
+            }
+
+            item
+        })
+        .collect();
+
+    Node::Array(items)
+}
+
+fn symbol(document: &Document, item: &syntax::Item) -> Node {
+    let kind = match item.kind {
+        ItemKind::Dict => SYMBOL_OBJECT,
+        ItemKind::Array => SYMBOL_ARRAY,
+        ItemKind::Value(Category::String) => SYMBOL_STRING,
+        ItemKind::Value(Category::Integer | Category::Float) => SYMBOL_NUMBER,
+        ItemKind::Value(Category::Boolean) => SYMBOL_BOOLEAN,
+        ItemKind::Value(Category::Null) => SYMBOL_NULL,
+        ItemKind::Value(_) => SYMBOL_OBJECT,
+    };
+
+    let children = item.children.iter().map(|child| symbol(document, child)).collect();
+
+    object([
+        ("name", string(item.name.clone())),
+        ("kind", integer(kind)),
+        ("range", document.range(item.span.start, item.span.end)),
+        ("selectionRange", document.range(item.selection.start, item.selection.end)),
+        ("children", Node::Array(children)),
+    ])
+}
+
+/// The index of a category in [`TOKEN_TYPES`], `None` for what isn't highlighted
+fn token_type(category: Category) -> Option<usize> {
+    let name = match category {
+        Category::Key | Category::DictKey => "property",
+        Category::ScopeSegment => "namespace",
+        Category::String => "string",
+        Category::Escape => "regexp",
+        Category::Integer | Category::Float => "number",
+        Category::Boolean | Category::Null => "keyword",
+        Category::Comment => "comment",
+        Category::Punctuation => "operator",
+        Category::Invalid => return None,
+    };
+
+    TOKEN_TYPES.iter().position(|&token| token == name)
+}
+
+/// Encodes highlights the way LSP wants them, as line and character deltas from the one
+/// before, with highlights spanning lines split into one per line
+fn semantic_tokens(document: &Document) -> Vec<Node> {
+    let mut data = vec![];
+    let (mut last_line, mut last_start) = (0, 0);
+
+    for highlight in syntax::highlight(&document.text) {
+        let Some(kind) = token_type(highlight.category) else {
+            continue;
+        };
+
+        let mut start = highlight.span.start;
+
+        // every byte that ends a line is a break, the piece between `\r\n` is empty
+        for piece in document.text[highlight.span.start..highlight.span.end].split(['\n', '\r']) {
+            let (line, character) = document.position(start);
+            let length = piece.chars().map(char::len_utf16).sum::<usize>();
+
+            start += piece.len() + 1;
+
+            if length == 0 {
+                continue;
+            }
+
+            let delta = if line == last_line { character - last_start } else { character };
+
+            data.extend([line - last_line, delta, length, kind, 0].map(integer));
+            (last_line, last_start) = (line, character);
+        }
+    }
+
+    data
+}
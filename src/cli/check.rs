@@ -0,0 +1,80 @@
+use super::{inputs, plural, usage, Diagnostic};
+use kfg::{json, Kfg, ParseOptions};
+
+pub fn run(args: &[String]) -> i32 {
+    let mut json_output = false;
+    let mut options = ParseOptions::default();
+    let mut paths = vec![];
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("text") => json_output = false,
+                Some("json") => json_output = true,
+                _ => return usage("--format expects 'text' or 'json'"),
+            },
+            "--strict" => options = ParseOptions::strict(),
+            "--lenient" => options = ParseOptions::lenient(),
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    let inputs = match inputs(&paths) {
+        Ok(inputs) => inputs,
+        Err(message) => return usage(&message),
+    };
+
+    let mut results = vec![];
+    let mut failed = 0;
+
+    for input in &inputs {
+        let mut warnings = vec![];
+
+        let diagnostic = match input.read() {
+            Ok(buffer) => match Kfg::parse_with(&buffer, &options) {
+                Ok((_, read)) => {
+                    warnings = read.iter().map(|warning| Diagnostic::warning(input.name(), warning)).collect();
+                    None
+                }
+                Err(error) => Some(Diagnostic::parser(input.name(), &error)),
+            },
+            Err(error) => Some(Diagnostic::io(input.name(), &error)),
+        };
+
+        match diagnostic {
+            Some(diagnostic) => {
+                failed += 1;
+
+                if json_output {
+                    results.push(diagnostic.json());
+                } else {
+                    eprintln!("{}", diagnostic.text());
+                }
+            }
+            None if json_output => {
+                let warnings = warnings.iter().map(Diagnostic::json).collect::<Vec<_>>();
+
+                results.push(format!(
+                    "{{\"path\": {}, \"ok\": true, \"warnings\": [{}]}}",
+                    json::string(&input.name()),
+                    warnings.join(", "),
+                ));
+            }
+            None => warnings.iter().for_each(|warning| eprintln!("{}", warning.text())),
+        }
+    }
+
+    if json_output {
+        println!(
+            "{{\"files\": [{}], \"checked\": {}, \"failed\": {failed}}}",
+            results.join(", "),
+            inputs.len(),
+        );
+    } else {
+        eprintln!("checked {}, {failed} failed", plural(inputs.len(), "file"));
+    }
+
+    (failed > 0) as i32
+}
@@ -0,0 +1,91 @@
+use super::{input, paint, usage, Diagnostic, Input};
+use kfg::{convert, ConvertError, Format};
+use std::path::Path;
+
+const USAGE: &str = "Usage: kfg convert [--from <format>] [--to <format>] [--strict] [in] [out]";
+
+fn format(given: Option<&String>, path: Option<&str>, name: &str) -> Result<Format, String> {
+    match (given, path) {
+        (Some(format), _) => format.parse(),
+        (None, Some(path)) if path != "-" => {
+            Format::from_extension(Path::new(path)).ok_or_else(|| format!("can't tell the format of '{path}', use --{name}"))
+        }
+        _ => Err(format!("--{name} is needed when using stdin or stdout")),
+    }
+}
+
+pub fn run(args: &[String]) -> i32 {
+    let mut from = None;
+    let mut to = None;
+    let mut strict = false;
+    let mut positional = vec![];
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = args.next(),
+            "--to" => to = args.next(),
+            "--strict" => strict = true,
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    if positional.len() > 2 {
+        return usage(USAGE);
+    }
+
+    let source = positional.first().copied();
+    let target = positional.get(1).copied();
+
+    let (from, to) = match (format(from, source, "from"), format(to, target, "to")) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(message), _) | (_, Err(message)) => return usage(&format!("{message}\n\n{USAGE}")),
+    };
+
+    let reader = input(source.unwrap_or("-"));
+
+    let buffer = match reader.read() {
+        Ok(buffer) => buffer,
+        Err(error) => {
+            eprintln!("{}", Diagnostic::io(reader.name(), &error).text());
+            return 1;
+        }
+    };
+
+    let (output, warnings) = match convert::convert(from, to, &buffer, strict) {
+        Ok(converted) => converted,
+        Err(ConvertError::Parser(error)) => {
+            eprintln!("{}", Diagnostic::parser(reader.name(), &error).text());
+            return 1;
+        }
+        Err(ConvertError::Syntax(_, error)) => {
+            eprintln!("{}: {}: {}", paint("1", &format!("{}:{}:{}", reader.name(), error.line, error.column)), paint("31", "SyntaxError"), error.message);
+            return 1;
+        }
+        Err(ConvertError::Lossy(warnings)) => {
+            for warning in warnings {
+                eprintln!("{}: {warning}", paint("31", "error"));
+            }
+
+            eprintln!("converting from {from} to {to} would lose data");
+            return 1;
+        }
+    };
+
+    for warning in warnings {
+        eprintln!("{}: {warning}", paint("33", "warning"));
+    }
+
+    match input(target.unwrap_or("-")) {
+        Input::Stdin => print!("{output}"),
+        Input::File(path) => {
+            if let Err(error) = std::fs::write(&path, output) {
+                eprintln!("{}", Diagnostic::io(path.display().to_string(), &error).text());
+                return 1;
+            }
+        }
+    }
+
+    0
+}
@@ -0,0 +1,70 @@
+use super::{load, usage};
+use kfg::{json, ChangeKind, Patch};
+use std::io::IsTerminal;
+
+const USAGE: &str = "Usage: kfg diff <from> <to> [--format text|json|patch]";
+
+pub fn run(args: &[String]) -> i32 {
+    let mut format = "text";
+    let mut positional = vec![];
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some(value @ ("text" | "json" | "patch")) => format = value,
+                _ => return usage("--format expects 'text', 'json' or 'patch'"),
+            },
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let [from, to] = positional[..] else {
+        return usage(USAGE);
+    };
+
+    let (Ok((from, _)), Ok((to, _))) = (load(from), load(to)) else {
+        return 2;
+    };
+
+    let changes = from.diff(&to);
+
+    if format == "patch" {
+        print!("{}", Patch::from_diff(&from, &to).to_ast().to_kfg());
+    } else if format == "json" {
+        let changes = changes
+            .iter()
+            .map(|change| {
+                let path = json::string(&change.path);
+
+                match &change.kind {
+                    ChangeKind::Added(node) => format!("{{\"path\": {path}, \"kind\": \"added\", \"value\": {}}}", node.to_json()),
+                    ChangeKind::Removed(node) => format!("{{\"path\": {path}, \"kind\": \"removed\", \"value\": {}}}", node.to_json()),
+                    ChangeKind::Changed(old, new) => {
+                        format!("{{\"path\": {path}, \"kind\": \"changed\", \"from\": {}, \"to\": {}}}", old.to_json(), new.to_json())
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        println!("{{\"changes\": [{}]}}", changes.join(", "));
+    } else {
+        let color = std::io::stdout().is_terminal();
+        let paint = |code: &str, text: String| if color { format!("\x1b[{code}m{text}\x1b[m") } else { text };
+
+        for change in &changes {
+            let path = &change.path;
+
+            match &change.kind {
+                ChangeKind::Added(node) => println!("{}", paint("32", format!("+ {path} = {}", node.to_kfg_inline()))),
+                ChangeKind::Removed(node) => println!("{}", paint("31", format!("- {path} = {}", node.to_kfg_inline()))),
+                ChangeKind::Changed(old, new) => {
+                    println!("{}", paint("33", format!("~ {path}: {} -> {}", old.to_kfg_inline(), new.to_kfg_inline())))
+                }
+            }
+        }
+    }
+
+    i32::from(!changes.is_empty())
+}
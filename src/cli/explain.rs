@@ -0,0 +1,101 @@
+use super::{input, load, usage, Diagnostic};
+use kfg::{FileId, Format, Kfg, LayerError, Layers, Node, Source};
+use std::path::Path;
+
+const USAGE: &str = "Usage: kfg explain <path> <files...> [--env <prefix>] [--set <key::path=value>]...";
+
+pub fn run(args: &[String]) -> i32 {
+    let mut env = None;
+    let mut overrides = vec![];
+    let mut positional = vec![];
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--env" => match args.next() {
+                Some(prefix) => env = Some(prefix.as_str()),
+                None => return usage("--env expects a prefix"),
+            },
+            "--set" => match args.next().and_then(|value| value.split_once('=')) {
+                Some(assignment) => overrides.push(assignment),
+                None => return usage("--set expects a key::path=value"),
+            },
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let [path, ref files @ ..] = positional[..] else {
+        return usage(USAGE);
+    };
+
+    if files.is_empty() {
+        return usage(USAGE);
+    }
+
+    // files in the order given, then the environment, then the command line
+    let mut layers = Layers::new();
+
+    for (i, &file) in files.iter().enumerate() {
+        let ast = match Format::from_extension(Path::new(file)).unwrap_or(Format::Kfg) {
+            Format::Kfg => {
+                let input = input(file);
+
+                let buffer = match input.read() {
+                    Ok(buffer) => buffer,
+                    Err(error) => {
+                        eprintln!("{}", Diagnostic::io(input.name(), &error).text());
+                        return 2;
+                    }
+                };
+
+                match Kfg::parse_spanned(&buffer, FileId(i)) {
+                    Ok(ast) => ast,
+                    Err(error) => {
+                        eprintln!("{}", Diagnostic::parser(input.name(), &error).text());
+                        return 2;
+                    }
+                }
+            }
+            _ => match load(file) {
+                Ok((ast, _)) => ast,
+                Err(()) => return 2,
+            },
+        };
+
+        layers.add(Source::File(file.into()), &ast);
+    }
+
+    if let Some(prefix) = env {
+        layers.env(prefix, std::env::vars());
+    }
+
+    for (key, value) in overrides {
+        if let Err(LayerError::MissingIndex(array, index)) = layers.set(Source::Override, key, Node::typed(value)) {
+            eprintln!("--set {key}: '{array}' has no element {index}");
+            return 2;
+        }
+    }
+
+    let ast = layers.into_ast();
+
+    let Some(node) = ast.lookup(path) else {
+        eprintln!("missing key '{path}'");
+        return 1;
+    };
+
+    println!("{path} = {}", node.to_kfg_inline());
+
+    for (i, origin) in ast.provenance(path).into_iter().enumerate() {
+        let source = match (&origin.source, origin.span) {
+            (Source::File(file), Some(span)) => format!("{}:{span}", file.display()),
+            (source, _) => source.to_string(),
+        };
+
+        let overridden = if i == 0 { "" } else { " (overridden)" };
+
+        println!("  {}  {source}{overridden}", origin.value.to_kfg_inline());
+    }
+
+    0
+}
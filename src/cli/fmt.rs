@@ -0,0 +1,79 @@
+use super::{inputs, paint, plural, usage, Diagnostic, Input};
+use kfg::{FormatError, Kfg};
+
+pub fn run(args: &[String]) -> i32 {
+    let mut check = false;
+    let mut paths = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    let inputs = match inputs(&paths) {
+        Ok(inputs) => inputs,
+        Err(message) => return usage(&message),
+    };
+
+    let mut changed = 0;
+    let mut failed = 0;
+
+    for input in &inputs {
+        let buffer = match input.read() {
+            Ok(buffer) => buffer,
+            Err(error) => {
+                eprintln!("{}", Diagnostic::io(input.name(), &error).text());
+                failed += 1;
+                continue;
+            }
+        };
+
+        let formatted = match Kfg::format(&buffer) {
+            Ok(formatted) => formatted,
+            Err(FormatError::Parse(error)) => {
+                eprintln!("{}", Diagnostic::parser(input.name(), &error).text());
+                failed += 1;
+                continue;
+            }
+            Err(FormatError::Changed) => {
+                eprintln!("{}: formatting would change its values, left as it is", input.name());
+                failed += 1;
+                continue;
+            }
+        };
+
+        if formatted.as_bytes() == buffer {
+            if let Input::Stdin = input {
+                print!("{formatted}");
+            }
+
+            continue;
+        }
+
+        match input {
+            _ if check => eprintln!("{} {}", paint("33", "would reformat"), input.name()),
+            Input::Stdin => print!("{formatted}"),
+            Input::File(path) => {
+                if let Err(error) = std::fs::write(path, formatted) {
+                    eprintln!("{}", Diagnostic::io(input.name(), &error).text());
+                    failed += 1;
+                    continue;
+                }
+            }
+        }
+
+        changed += 1;
+    }
+
+    let unchanged = inputs.len() - changed - failed;
+
+    if check {
+        eprintln!("{} would be reformatted, {unchanged} already formatted, {failed} failed", plural(changed, "file"));
+    } else {
+        eprintln!("reformatted {}, {unchanged} unchanged, {failed} failed", plural(changed, "file"));
+    }
+
+    (failed > 0 || (check && changed > 0)) as i32
+}
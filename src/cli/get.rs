@@ -0,0 +1,65 @@
+use super::{input, usage, Diagnostic};
+use kfg::{Kfg, Node};
+
+pub fn run(args: &[String]) -> i32 {
+    let mut output = "raw";
+    let mut default = None;
+    let mut positional = vec![];
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => match args.next().map(String::as_str) {
+                Some(value @ ("raw" | "kfg" | "json")) => output = value,
+                _ => return usage("--output expects 'raw', 'kfg' or 'json'"),
+            },
+            "--default" => match args.next() {
+                Some(value) => default = Some(value.clone()),
+                None => return usage("--default expects a value"),
+            },
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let [file, path] = positional[..] else {
+        return usage("Usage: kfg get <file> <path> [--output raw|kfg|json] [--default <value>]");
+    };
+
+    let input = input(file);
+
+    let ast = match input.read() {
+        Ok(buffer) => match Kfg::parse(&buffer) {
+            Ok(ast) => ast,
+            Err(error) => {
+                eprintln!("{}", Diagnostic::parser(input.name(), &error).text());
+                return 2;
+            }
+        },
+        Err(error) => {
+            eprintln!("{}", Diagnostic::io(input.name(), &error).text());
+            return 2;
+        }
+    };
+
+    let Some(node) = ast.lookup(path) else {
+        return match default {
+            Some(default) => {
+                println!("{default}");
+                0
+            }
+            None => {
+                eprintln!("{}: missing key '{path}'", input.name());
+                1
+            }
+        };
+    };
+
+    match (output, node) {
+        ("raw", Node::String(value)) => println!("{value}"),
+        ("json", node) => println!("{}", node.to_json()),
+        (_, node) => println!("{}", node.to_kfg()),
+    }
+
+    0
+}
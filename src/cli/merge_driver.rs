@@ -0,0 +1,84 @@
+use super::{input, plural, usage, Diagnostic};
+use kfg::{merge, MergeError};
+use std::process::Command;
+
+const USAGE: &str = "Usage: kfg merge-driver <base> <ours> <theirs>";
+
+/// Reads a file as text, printing any error
+fn read(path: &str) -> Result<String, ()> {
+    let reader = input(path);
+
+    match reader.read() {
+        Ok(buffer) => Ok(String::from_utf8_lossy(&buffer).into_owned()),
+        Err(error) => {
+            eprintln!("{}", Diagnostic::io(reader.name(), &error).text());
+            Err(())
+        }
+    }
+}
+
+/// Merges line by line with `git merge-file`, writing the result over `ours`
+fn merge_lines(base: &str, ours: &str, theirs: &str) -> i32 {
+    let status = Command::new("git")
+        .args(["merge-file", "-L", "ours", "-L", "base", "-L", "theirs", ours, base, theirs])
+        .status();
+
+    // the exit code is the number of conflicts, negative on errors
+    match status.map(|status| status.code()) {
+        Ok(Some(0)) => 0,
+        Ok(Some(1..=127)) => {
+            eprintln!("conflicts left as lines in {ours}");
+            1
+        }
+        Ok(_) => 2,
+        Err(error) => {
+            eprintln!("{}", Diagnostic::io("git merge-file".into(), &error).text());
+            2
+        }
+    }
+}
+
+/// Merges `theirs` into `ours` in place, as git expects from a merge driver
+///
+/// When a side doesn't parse the files are merged line by line instead. Exits with 1 when
+/// conflict markers were written and 2 when a file can't be read or merged, leaving `ours`
+/// untouched
+pub fn run(args: &[String]) -> i32 {
+    let [base, ours, theirs] = args else {
+        return usage(USAGE);
+    };
+
+    let (Ok(base_source), Ok(ours_source), Ok(theirs_source)) = (read(base), read(ours), read(theirs)) else {
+        return 2;
+    };
+
+    let (merged, conflicts) = match merge::merge(&base_source, &ours_source, &theirs_source) {
+        Ok(merged) => merged,
+        Err(error) => {
+            let (path, error) = match error {
+                MergeError::Base(error) => (base, error),
+                MergeError::Ours(error) => (ours, error),
+                MergeError::Theirs(error) => (theirs, error),
+            };
+
+            eprintln!("{}", Diagnostic::parser(path.clone(), &error).text());
+            return merge_lines(base, ours, theirs);
+        }
+    };
+
+    if let Err(error) = std::fs::write(ours, merged) {
+        eprintln!("{}", Diagnostic::io(ours.clone(), &error).text());
+        return 2;
+    }
+
+    if conflicts.is_empty() {
+        return 0;
+    }
+
+    for conflict in &conflicts {
+        eprintln!("conflict: {}", conflict.path);
+    }
+
+    eprintln!("{}", plural(conflicts.len(), "conflict"));
+    1
+}
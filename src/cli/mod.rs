@@ -0,0 +1,368 @@
+use kfg::{json, Ast, ConvertError, Format, ParserError, TokenKind, Warning};
+use std::{
+    io::{IsTerminal, Read},
+    path::{Path, PathBuf},
+};
+
+pub mod check;
+pub mod convert;
+pub mod diff;
+pub mod explain;
+pub mod fmt;
+pub mod get;
+pub mod merge_driver;
+pub mod patch;
+pub mod set;
+
+/// Prints a usage error and returns its exit code
+pub fn usage(message: &str) -> i32 {
+    eprintln!("{message}");
+    2
+}
+
+pub fn color() -> bool {
+    std::io::stderr().is_terminal()
+}
+
+pub fn paint(code: &str, text: &str) -> String {
+    if color() {
+        format!("\x1b[{code}m{text}\x1b[m")
+    } else {
+        text.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    pub fn name(&self) -> String {
+        match self {
+            Input::Stdin => "<stdin>".into(),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+
+    pub fn read(&self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Input::Stdin => {
+                let mut buffer = vec![];
+                std::io::stdin().read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+            Input::File(path) => std::fs::read(path),
+        }
+    }
+}
+
+pub fn input(path: &str) -> Input {
+    match path {
+        "-" => Input::Stdin,
+        path => Input::File(path.into()),
+    }
+}
+
+/// Reads a file in the format its extension names, kfg otherwise, printing any error
+pub fn load(path: &str) -> Result<(Ast, Format), ()> {
+    let reader = input(path);
+    let format = Format::from_extension(Path::new(path)).unwrap_or(Format::Kfg);
+
+    let buffer = reader.read().map_err(|error| {
+        eprintln!("{}", Diagnostic::io(reader.name(), &error).text());
+    })?;
+
+    let ast = kfg::convert::read(format, &buffer, &mut vec![]).map_err(|error| match error {
+        ConvertError::Parser(error) => eprintln!("{}", Diagnostic::parser(reader.name(), &error).text()),
+        ConvertError::Syntax(_, error) => {
+            eprintln!("{}: {}: {}", paint("1", &format!("{}:{}:{}", reader.name(), error.line, error.column)), paint("31", "SyntaxError"), error.message)
+        }
+        ConvertError::Lossy(_) => {}
+    })?;
+
+    Ok((ast, format))
+}
+
+/// Expands paths, directories and globs into the files they name, `-` is stdin
+pub fn inputs(paths: &[String]) -> Result<Vec<Input>, String> {
+    let mut inputs = vec![];
+
+    for path in paths {
+        if path == "-" {
+            inputs.push(input(path));
+        } else if Path::new(path).is_dir() {
+            let mut files = vec![];
+            walk(Path::new(path), &mut files);
+            files.sort();
+
+            inputs.extend(files.into_iter().map(Input::File));
+        } else if !Path::new(path).exists() && path.contains(['*', '?']) {
+            let files = glob(path);
+
+            if files.is_empty() {
+                return Err(format!("no files match '{path}'"));
+            }
+
+            inputs.extend(files.into_iter().map(Input::File));
+        } else {
+            inputs.push(Input::File(path.into()));
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err("no files given".into());
+    }
+
+    Ok(inputs)
+}
+
+/// Whether `path` is a directory itself, a link to one can loop back above it
+fn is_dir(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Collects every `*.kfg` file below `dir`, without following symlinked directories
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if is_dir(&path) {
+            walk(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "kfg") {
+            files.push(path);
+        }
+    }
+}
+
+/// Matches a single path component against a pattern with `*` and `?`
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..])),
+        (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Expands a glob, `**` matches any number of directories but not symlinked ones
+fn glob(pattern: &str) -> Vec<PathBuf> {
+    let absolute = pattern.starts_with('/');
+    let components = pattern.split('/').filter(|c| !c.is_empty()).collect::<Vec<_>>();
+
+    let root = if absolute { PathBuf::from("/") } else { PathBuf::new() };
+
+    let mut files = vec![];
+    glob_from(&root, &components, &mut files);
+
+    files.sort();
+    files.dedup();
+    files
+}
+
+fn glob_from(base: &Path, components: &[&str], files: &mut Vec<PathBuf>) {
+    let Some((&component, rest)) = components.split_first() else {
+        if base.is_file() {
+            files.push(base.to_path_buf());
+        }
+        return;
+    };
+
+    let dir = if base.as_os_str().is_empty() { Path::new(".") } else { base };
+
+    if component == "**" {
+        glob_from(base, rest, files);
+
+        for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            if is_dir(&entry.path()) {
+                glob_from(&base.join(entry.file_name()), components, files);
+            }
+        }
+    } else if !component.contains(['*', '?']) {
+        glob_from(&base.join(component), rest, files);
+    } else {
+        let pattern = component.chars().collect::<Vec<_>>();
+
+        for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            let name = entry.file_name().to_string_lossy().chars().collect::<Vec<_>>();
+
+            if !name.starts_with(&['.']) && matches(&pattern, &name) {
+                glob_from(&base.join(entry.file_name()), rest, files);
+            }
+        }
+    }
+}
+
+/// A parse or I/O error found in a file, or a warning of a lenient parse
+pub struct Diagnostic {
+    pub path: String,
+    pub warning: bool,
+    pub error: String,
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl Diagnostic {
+    pub fn parser(path: String, error: &ParserError) -> Self {
+        let (message, line, column) = match (error, error.token()) {
+            (ParserError::Lexer(error), Some(token)) => (
+                error.message().into(),
+                Some(token.position.line),
+                Some(token.position.character + 1),
+            ),
+            (ParserError::Limit(limit, token), _) => (
+                limit.message().into(),
+                Some(token.position.line),
+                Some(token.position.character + 1),
+            ),
+            (ParserError::InvalidKey(token), _) => (
+                match token.kind {
+                    TokenKind::Quote => "quoted keys can't contain '::'".into(),
+                    _ => format!("invalid key '{}', quote it", token.kind.as_str().escape_debug()),
+                },
+                Some(token.position.line),
+                Some(token.position.character + 1),
+            ),
+            (ParserError::InvalidSymbol(token), _) => (
+                format!("invalid symbol '{}'", token.kind.as_str().escape_debug()),
+                Some(token.position.line),
+                Some(token.position.character + 1),
+            ),
+            (_, Some(token)) => (
+                format!("unexpected '{}'", token.kind.as_str().escape_debug()),
+                Some(token.position.line),
+                Some(token.position.character + 1),
+            ),
+            (_, None) => (String::new(), None, None),
+        };
+
+        Self {
+            path,
+            warning: false,
+            error: error.name().into(),
+            message,
+            line,
+            column,
+        }
+    }
+
+    pub fn warning(path: String, warning: &Warning) -> Self {
+        let position = warning.token().position;
+
+        Self {
+            path,
+            warning: true,
+            error: warning.name().into(),
+            message: warning.message().into(),
+            line: Some(position.line),
+            column: Some(position.character + 1),
+        }
+    }
+
+    pub fn io(path: String, error: &std::io::Error) -> Self {
+        Self {
+            path,
+            warning: false,
+            error: "Io".into(),
+            message: error.to_string(),
+            line: None,
+            column: None,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let location = match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{}:{line}:{column}", self.path),
+            _ => self.path.clone(),
+        };
+
+        let color = if self.warning { "33" } else { "31" };
+
+        format!("{}: {}: {}", paint("1", &location), paint(color, &self.error), self.message)
+    }
+
+    pub fn json(&self) -> String {
+        let number = |n: Option<usize>| n.map_or("null".into(), |n| n.to_string());
+
+        // listed under the file it's in
+        if self.warning {
+            return format!(
+                "{{\"warning\": {}, \"message\": {}, \"line\": {}, \"column\": {}}}",
+                json::string(&self.error),
+                json::string(&self.message),
+                number(self.line),
+                number(self.column),
+            );
+        }
+
+        format!(
+            "{{\"path\": {}, \"ok\": false, \"error\": {}, \"message\": {}, \"line\": {}, \"column\": {}}}",
+            json::string(&self.path),
+            json::string(&self.error),
+            json::string(&self.message),
+            number(self.line),
+            number(self.column),
+        )
+    }
+}
+
+pub fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{count} {word}")
+    } else {
+        format!("{count} {word}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let matches = |pattern: &str, name: &str| {
+            matches(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+        };
+
+        assert!(matches("*.kfg", "settings.kfg"));
+        assert!(matches("s?ttings.*", "settings.kfg"));
+        assert!(matches("*", ""));
+        assert!(!matches("*.kfg", "settings.toml"));
+        assert!(!matches("?", ""));
+    }
+
+    #[test]
+    fn test_diagnostic() {
+        let error = kfg::Kfg::parse(b"a = 1\nb = \"x\" c").unwrap_err();
+        let diagnostic = Diagnostic::parser("bad.kfg".into(), &error);
+
+        assert_eq!((diagnostic.error.as_str(), diagnostic.line, diagnostic.column), ("InvalidSymbol", Some(2), Some(5)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk() {
+        let dir = std::env::temp_dir().join(format!("kfg-walk-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/a.kfg"), "a = 1").unwrap();
+
+        // a link back up would be walked forever
+        std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+
+        let mut files = vec![];
+        walk(&dir, &mut files);
+        let globbed = glob(&format!("{}/**/*.kfg", dir.display()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files, [dir.join("sub/a.kfg")]);
+        assert_eq!(globbed, [dir.join("sub/a.kfg")]);
+    }
+}
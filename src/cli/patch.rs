@@ -0,0 +1,51 @@
+use super::{input, load, usage, Diagnostic, Input};
+use kfg::{convert, Patch};
+
+const USAGE: &str = "Usage: kfg patch <file> <patch> [out]";
+
+pub fn run(args: &[String]) -> i32 {
+    let (file, patch, out) = match args {
+        [file, patch] => (file, patch, "-"),
+        [file, patch, out] => (file, patch, out.as_str()),
+        _ => return usage(USAGE),
+    };
+
+    let (Ok((mut ast, format)), Ok((document, _))) = (load(file), load(patch)) else {
+        return 2;
+    };
+
+    let patch_name = input(patch).name();
+
+    let operations = match Patch::from_ast(&document) {
+        Ok(operations) => operations,
+        Err(error) => {
+            eprintln!("{patch_name}: invalid patch: {error}");
+            return 2;
+        }
+    };
+
+    if let Err(error) = ast.apply_patch(&operations) {
+        let index = error.index();
+
+        match operations.0.get(index) {
+            Some(operation) => eprintln!("{patch_name}: operation {index} ({} '{}') failed: {error}", operation.name(), operation.path()),
+            None => eprintln!("{patch_name}: operation {index} failed: {error}"),
+        }
+
+        return 1;
+    }
+
+    let output = convert::write(format, &ast, &mut vec![]);
+
+    match input(out) {
+        Input::Stdin => print!("{output}"),
+        Input::File(path) => {
+            if let Err(error) = std::fs::write(&path, output) {
+                eprintln!("{}", Diagnostic::io(path.display().to_string(), &error).text());
+                return 2;
+            }
+        }
+    }
+
+    0
+}
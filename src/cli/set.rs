@@ -0,0 +1,64 @@
+use super::{input, usage, Diagnostic, Input};
+use kfg::{edit, EditError, Kfg, Node};
+
+/// Reads a value as a kfg literal, anything that isn't one is taken as a string
+fn value(literal: &str) -> Node {
+    Kfg::parse(format!("value = {literal}").as_bytes())
+        .ok()
+        .and_then(|mut ast| ast.remove("value"))
+        .unwrap_or_else(|| Node::String(literal.into()))
+}
+
+pub fn run(args: &[String]) -> i32 {
+    let mut create = false;
+    let mut positional = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "--create" => create = true,
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let [file, path, literal] = positional[..] else {
+        return usage("Usage: kfg set <file> <path> <value> [--create]");
+    };
+
+    let input = input(file);
+
+    let source = match input.read() {
+        Ok(buffer) => String::from_utf8_lossy(&buffer).into_owned(),
+        Err(error) => {
+            eprintln!("{}", Diagnostic::io(input.name(), &error).text());
+            return 2;
+        }
+    };
+
+    let source = match edit::set(&source, path, &value(literal), create) {
+        Ok(source) => source,
+        Err(EditError::Parse(error)) => {
+            eprintln!("{}", Diagnostic::parser(input.name(), &error).text());
+            return 2;
+        }
+        Err(EditError::MissingKey(_)) => {
+            eprintln!("{}: missing key '{path}', use --create to add it", input.name());
+            return 1;
+        }
+        Err(EditError::NotADict(parent)) => {
+            eprintln!("{}: '{parent}' isn't a dict", input.name());
+            return 2;
+        }
+    };
+
+    match input {
+        Input::Stdin => print!("{source}"),
+        Input::File(ref path) => {
+            if let Err(error) = std::fs::write(path, source) {
+                eprintln!("{}", Diagnostic::io(input.name(), &error).text());
+                return 2;
+            }
+        }
+    }
+
+    0
+}
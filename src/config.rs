@@ -0,0 +1,219 @@
+use crate::Ast;
+use arc_swap::ArcSwap;
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Missing(String),
+    Invalid(String, String),
+}
+
+impl std::error::Error for ConfigError {}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A value that can be read out of an [`Ast`]
+pub trait FromAst: Sized {
+    fn from_ast(ast: &Ast) -> Result<Self, ConfigError>;
+
+    /// Checks the value before it replaces the current one, anything goes by default
+    fn validate(&self) -> Result<(), ConfigError> {
+        Ok(())
+    }
+}
+
+impl FromAst for Ast {
+    fn from_ast(ast: &Ast) -> Result<Self, ConfigError> {
+        Ok(ast.clone())
+    }
+}
+
+/// Holds the current value of a config, shared between threads
+///
+/// Readers get a snapshot with [`Config::load`] without taking a lock, writers swap the
+/// value in and the old one is freed with its last snapshot
+pub struct Config<T> {
+    current: ArcSwap<T>,
+}
+
+impl<T> Config<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(value),
+        }
+    }
+
+    /// Takes a snapshot of the current value, later replacements don't affect it
+    pub fn load(&self) -> Arc<T> {
+        self.current.load_full()
+    }
+
+    /// Replaces the value, readers holding a snapshot keep the old one
+    pub fn store(&self, value: T) {
+        self.current.store(Arc::new(value));
+    }
+}
+
+impl<T: FromAst> Config<T> {
+    /// Reads and validates the first value
+    pub fn from_ast(ast: &Ast) -> Result<Self, ConfigError> {
+        let value = T::from_ast(ast)?;
+        value.validate()?;
+
+        Ok(Self::new(value))
+    }
+
+    /// Replaces the value with the one read from `ast`, only if it reads and validates
+    pub fn update(&self, ast: &Ast) -> Result<(), ConfigError> {
+        let value = T::from_ast(ast)?;
+        value.validate()?;
+
+        self.store(value);
+        Ok(())
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Config<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("Config").field(&self.load()).finish()
+    }
+}
+
+type Registry = Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Makes a config available to the whole process under `name`, replacing any other one
+pub fn register<T: Send + Sync + 'static>(name: &str, config: Arc<Config<T>>) {
+    registry()
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .insert(name.into(), config);
+}
+
+/// Finds the config registered under `name`, if it holds a `T`
+pub fn lookup<T: Send + Sync + 'static>(name: &str) -> Option<Arc<Config<T>>> {
+    let config = registry()
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .get(name)?
+        .clone();
+
+    config.downcast().ok()
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::{Kfg, Node};
+
+    #[derive(Debug, PartialEq)]
+    struct Server {
+        port: i64,
+    }
+
+    impl FromAst for Server {
+        fn from_ast(ast: &Ast) -> Result<Self, ConfigError> {
+            match ast.lookup("server::port") {
+                Some(Node::Integer(port)) => Ok(Self { port: *port }),
+                Some(_) => Err(ConfigError::Invalid("server::port".into(), "expected an integer".into())),
+                None => Err(ConfigError::Missing("server::port".into())),
+            }
+        }
+
+        fn validate(&self) -> Result<(), ConfigError> {
+            match self.port {
+                1..=65535 => Ok(()),
+                _ => Err(ConfigError::Invalid("server::port".into(), "out of range".into())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let config = Arc::new(Config::<Server>::from_ast(&Kfg::parse(b"server::port = 80").unwrap()).unwrap());
+        let snapshot = config.load();
+
+        config.update(&Kfg::parse(b"server::port = 8080").unwrap()).unwrap();
+
+        assert_eq!(snapshot.port, 80);
+        assert_eq!(config.load().port, 8080);
+
+        assert_eq!(config.update(&Kfg::parse(b"server::port = 0").unwrap()), Err(ConfigError::Invalid("server::port".into(), "out of range".into())));
+        assert_eq!(config.update(&Kfg::parse(b"other = 1").unwrap()), Err(ConfigError::Missing("server::port".into())));
+        assert_eq!(config.load().port, 8080);
+
+        let threads = (0..4).map(|i| {
+            let config = config.clone();
+
+            std::thread::spawn(move || {
+                for port in 1..200 {
+                    if i == 0 {
+                        config.store(Server { port });
+                    } else {
+                        assert!((1..=8080).contains(&config.load().port));
+                    }
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(config.load().port, 199);
+
+        register("test_config", config.clone());
+
+        assert_eq!(lookup::<Server>("test_config").unwrap().load().port, 199);
+        assert!(lookup::<Ast>("test_config").is_none());
+        assert!(lookup::<Server>("missing").is_none());
+    }
+
+    #[test]
+    fn test_busy_readers() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let config = Arc::new(Config::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+
+        // readers that never stop loading can't keep the writer out
+        let readers = (0..8).map(|_| {
+            let (config, done) = (config.clone(), done.clone());
+
+            std::thread::spawn(move || {
+                let mut last = 0;
+
+                while !done.load(Ordering::Relaxed) {
+                    let value = *config.load();
+                    assert!(value >= last);
+                    last = value;
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        for value in 1..=1000 {
+            config.store(value);
+        }
+
+        done.store(true, Ordering::Relaxed);
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(*config.load(), 1000);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{json, toml, yaml, Ast, Kfg, Node, ParseOptions, ParserError};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Kfg,
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Guesses the format from a file extension
+    pub fn from_extension(path: &std::path::Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "kfg" => Ok(Self::Kfg),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            _ => Err(format!("unknown format '{value}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Format::Kfg => "kfg",
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// A syntax error in a JSON, TOML or YAML document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl SyntaxError {
+    /// Builds an error pointing at the byte `index` of `source`, or the char it falls in
+    pub(crate) fn at(source: &str, index: usize, message: impl Into<String>) -> Self {
+        let index = (0..=index.min(source.len())).rev().find(|&i| source.is_char_boundary(i)).unwrap_or(0);
+        let before = &source[..index];
+
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before.rsplit('\n').next().unwrap_or("").chars().count() + 1,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Whether dicts and arrays are nested deeper than a kfg parse allows by default,
+/// readers stop there instead of running out of stack
+pub(crate) fn too_deep(depth: usize) -> bool {
+    depth > ParseOptions::default().max_depth
+}
+
+/// Something that couldn't be carried over exactly, at a `key::path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub path: String,
+    pub message: String,
+}
+
+impl Warning {
+    pub(crate) fn new(path: &[String], message: impl Into<String>) -> Self {
+        Self {
+            path: path.join("::"),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ConvertError {
+    Parser(ParserError),
+    Syntax(Format, SyntaxError),
+    Lossy(Vec<Warning>),
+}
+
+impl std::error::Error for ConvertError {}
+
+impl std::fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Reads a document of any format into an [`Ast`], pushing anything lossy into `warnings`
+pub fn read(format: Format, buffer: &[u8], warnings: &mut Vec<Warning>) -> Result<Ast, ConvertError> {
+    match format {
+        Format::Kfg => Kfg::parse(buffer).map_err(ConvertError::Parser),
+        Format::Json => json::read(buffer, warnings).map_err(|error| ConvertError::Syntax(format, error)),
+        Format::Toml => toml::read(buffer, warnings).map_err(|error| ConvertError::Syntax(format, error)),
+        Format::Yaml => yaml::read(buffer, warnings).map_err(|error| ConvertError::Syntax(format, error)),
+    }
+}
+
+/// Writes an [`Ast`] in any format, pushing anything lossy into `warnings`
+pub fn write(format: Format, ast: &Ast, warnings: &mut Vec<Warning>) -> String {
+    match format {
+        Format::Kfg => {
+            paths(&ast.0, &mut vec![], warnings);
+            crate::writer::write_ast(ast)
+        }
+        Format::Json => json::pretty(ast, warnings),
+        Format::Toml => toml::write(ast, warnings),
+        Format::Yaml => yaml::write(ast, warnings),
+    }
+}
+
+/// Warns about keys with a `::` in them, kfg reads those as a `key::path`
+fn paths(dict: &HashMap<String, Node>, path: &mut Vec<String>, warnings: &mut Vec<Warning>) {
+    for (key, node) in dict {
+        path.push(key.clone());
+
+        if key.contains("::") {
+            warnings.push(Warning::new(path, "kfg keys can't contain '::', it won't read back"));
+        }
+
+        nested(node, path, warnings);
+        path.pop();
+    }
+}
+
+fn nested(node: &Node, path: &mut Vec<String>, warnings: &mut Vec<Warning>) {
+    match node {
+        Node::Dict(dict) => paths(dict, path, warnings),
+        Node::Array(array) => {
+            for (i, node) in array.iter().enumerate() {
+                path.push(i.to_string());
+                nested(node, path, warnings);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Converts a document between formats, failing on anything lossy when `strict` is set
+///
+/// Returns the converted document and the warnings for everything that didn't survive exactly
+pub fn convert(from: Format, to: Format, buffer: &[u8], strict: bool) -> Result<(String, Vec<Warning>), ConvertError> {
+    let mut warnings = vec![];
+
+    let ast = read(from, buffer, &mut warnings)?;
+    let output = write(to, &ast, &mut warnings);
+
+    if strict && !warnings.is_empty() {
+        return Err(ConvertError::Lossy(warnings));
+    }
+
+    Ok((output, warnings))
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    const SOURCE: &[u8] = b"
+        name = 'kfg'
+        ratio = 0.5
+        count = 3
+        enabled = true
+        tags = ['a', 'b']
+        server = { .host: 'localhost' .ports: [80, 443] .tls: { .enabled: false } }
+        servers = [{ .name: 'a' .tls: { .on: true } }, { .name: 'b' }]
+        empty = {}
+    ";
+
+    #[test]
+    fn test_round_trip() {
+        let ast = Kfg::parse(SOURCE).unwrap();
+
+        for format in [Format::Kfg, Format::Json, Format::Toml, Format::Yaml] {
+            let (output, warnings) = convert(Format::Kfg, format, SOURCE, true).unwrap();
+
+            assert!(warnings.is_empty());
+            assert_eq!(read(format, output.as_bytes(), &mut vec![]).unwrap().0, ast.0, "{format}:\n{output}");
+        }
+    }
+
+    #[test]
+    fn test_lossy() {
+        let source = b"missing = null\nratio = 0.5\n";
+
+        let (output, warnings) = convert(Format::Kfg, Format::Toml, source, false).unwrap();
+
+        assert_eq!(output, "ratio = 0.5\n");
+        assert_eq!(warnings, [Warning::new(&["missing".into()], "toml has no null, the key was left out")]);
+
+        assert!(matches!(convert(Format::Kfg, Format::Toml, source, true), Err(ConvertError::Lossy(_))));
+
+        let (output, warnings) = convert(Format::Kfg, Format::Toml, b"ports = [80, null, 443]", false).unwrap();
+
+        assert_eq!(output, "ports = [80, 443]\n");
+        assert_eq!(warnings, [Warning::new(&["ports".into()], "toml has no null, element 1 was left out and the ones after it moved up")]);
+
+        let (_, warnings) = convert(Format::Json, Format::Kfg, br#"{"big": 123456789012345678901234567890}"#, false).unwrap();
+
+        assert_eq!(warnings, [Warning::new(&["big".into()], "integer doesn't fit in 64 bits, it was read as a float")]);
+
+        let (_, warnings) = convert(Format::Json, Format::Kfg, br#"{"a": [{"b::c": 1}]}"#, false).unwrap();
+
+        assert_eq!(warnings, [Warning::new(&["a".into(), "0".into(), "b::c".into()], "kfg keys can't contain '::', it won't read back")]);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{Ast, Node};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+    Added(Node),
+    Removed(Node),
+    Changed(Node, Node),
+}
+
+/// A difference between two trees at a `key::path`
+///
+/// Removed array elements are addressed by their index in the old array, added and changed
+/// ones by their index in the new array
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+struct Differ {
+    path: Vec<String>,
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn record(&mut self, kind: ChangeKind) {
+        self.changes.push(Change {
+            path: self.path.join("::"),
+            kind,
+        });
+    }
+
+    fn push(&mut self, segment: impl ToString, kind: ChangeKind) {
+        self.path.push(segment.to_string());
+        self.record(kind);
+        self.path.pop();
+    }
+
+    fn child(&mut self, segment: impl ToString, from: &Node, to: &Node) {
+        self.path.push(segment.to_string());
+        self.node(from, to);
+        self.path.pop();
+    }
+
+    fn node(&mut self, from: &Node, to: &Node) {
+        match (from, to) {
+            (Node::Dict(from), Node::Dict(to)) => self.dict(from, to),
+            (Node::Array(from), Node::Array(to)) => self.array(from, to),
+            (from, to) if from != to => self.record(ChangeKind::Changed(from.clone(), to.clone())),
+            _ => {}
+        }
+    }
+
+    fn dict(&mut self, from: &HashMap<String, Node>, to: &HashMap<String, Node>) {
+        let keys = from.keys().chain(to.keys()).collect::<BTreeSet<_>>();
+
+        for key in keys {
+            match (from.get(key), to.get(key)) {
+                (Some(from), Some(to)) => self.child(key, from, to),
+                (Some(from), None) => self.push(key, ChangeKind::Removed(from.clone())),
+                (None, Some(to)) => self.push(key, ChangeKind::Added(to.clone())),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
+    fn array(&mut self, from: &[Node], to: &[Node]) {
+        for step in align(from, to) {
+            match step {
+                Step::Same(..) => {}
+                Step::Paired(i, j) => self.child(j, &from[i], &to[j]),
+                Step::Removed(i) => self.push(i, ChangeKind::Removed(from[i].clone())),
+                Step::Added(j) => self.push(j, ChangeKind::Added(to[j].clone())),
+            }
+        }
+    }
+}
+
+/// How an element of the old array lines up with the new one, by index in each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Step {
+    Same(usize, usize),
+    Paired(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Cells of the table [`lcs`] fills past which arrays are lined up by index instead
+const MAX_CELLS: usize = 1 << 20;
+
+/// Aligns both arrays on their longest common subsequence, so inserting an element
+/// shows up as one addition instead of a change to every element after it
+///
+/// Elements replaced in the same gap are paired with each other, the rest are added or removed.
+/// Steps are in array order, so walking them turns `from` into `to` left to right
+///
+/// The common start and end line up as they are. Between them, arrays too long for the
+/// table are compared index by index
+pub(crate) fn align(from: &[Node], to: &[Node]) -> Vec<Step> {
+    let prefix = from.iter().zip(to).take_while(|(from, to)| from == to).count();
+    let suffix = from[prefix..].iter().rev().zip(to[prefix..].iter().rev()).take_while(|(from, to)| from == to).count();
+
+    let (inner_from, inner_to) = (&from[prefix..from.len() - suffix], &to[prefix..to.len() - suffix]);
+
+    let inner = match (inner_from.len() + 1).saturating_mul(inner_to.len() + 1) > MAX_CELLS {
+        true => by_index(inner_from, inner_to),
+        false => lcs(inner_from, inner_to),
+    };
+
+    let shifted = inner.into_iter().map(|step| match step {
+        Step::Same(i, j) => Step::Same(i + prefix, j + prefix),
+        Step::Paired(i, j) => Step::Paired(i + prefix, j + prefix),
+        Step::Removed(i) => Step::Removed(i + prefix),
+        Step::Added(j) => Step::Added(j + prefix),
+    });
+
+    (0..prefix)
+        .map(|i| Step::Same(i, i))
+        .chain(shifted)
+        .chain((1..=suffix).rev().map(|k| Step::Same(from.len() - k, to.len() - k)))
+        .collect()
+}
+
+/// Pairs elements at the same index, the longer array's tail is added or removed
+fn by_index(from: &[Node], to: &[Node]) -> Vec<Step> {
+    let paired = (0..from.len().min(to.len())).map(|i| match from[i] == to[i] {
+        true => Step::Same(i, i),
+        false => Step::Paired(i, i),
+    });
+
+    paired
+        .chain((to.len()..from.len()).map(Step::Removed))
+        .chain((from.len()..to.len()).map(Step::Added))
+        .collect()
+}
+
+/// [`align`] on the longest common subsequence, from a table of `(from.len() + 1) * (to.len() + 1)` cells
+fn lcs(from: &[Node], to: &[Node]) -> Vec<Step> {
+    let mut lengths = vec![vec![0usize; to.len() + 1]; from.len() + 1];
+
+    for i in (0..from.len()).rev() {
+        for j in (0..to.len()).rev() {
+            lengths[i][j] = if from[i] == to[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut removed = vec![];
+    let mut added = vec![];
+    let mut steps = vec![];
+
+    loop {
+        let matched = i < from.len() && j < to.len() && from[i] == to[j];
+
+        if matched || (i == from.len() && j == to.len()) {
+            let paired = removed.len().min(added.len());
+
+            steps.extend(removed.iter().zip(&added).map(|(&i, &j)| Step::Paired(i, j)));
+            steps.extend(removed[paired..].iter().map(|&i| Step::Removed(i)));
+            steps.extend(added[paired..].iter().map(|&j| Step::Added(j)));
+
+            removed.clear();
+            added.clear();
+
+            if !matched {
+                return steps;
+            }
+
+            steps.push(Step::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if j < to.len() && (i == from.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            added.push(j);
+            j += 1;
+        } else {
+            removed.push(i);
+            i += 1;
+        }
+    }
+}
+
+impl Ast {
+    /// Lists what changed from `self` to `other`, sorted by key
+    ///
+    /// Only values are compared, so reordered keys or a different way of writing the same
+    /// nested dict aren't changes
+    pub fn diff(&self, other: &Ast) -> Vec<Change> {
+        let mut differ = Differ {
+            path: vec![],
+            changes: vec![],
+        };
+
+        differ.dict(&self.0, &other.0);
+        differ.changes
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Kfg;
+
+    #[test]
+    fn test_diff() {
+        let from = Kfg::parse(b"
+            server = { .port: 80 .host: 'a' }
+            list = [1, 2, 3, 4]
+            removed = true
+        ").unwrap();
+
+        let to = Kfg::parse(b"
+            server::host = 'a'
+            server::port = 8080
+            list = [0, 1, 5, 3, 4]
+            added = null
+        ").unwrap();
+
+        assert_eq!(from.diff(&to), [
+            Change { path: "added".into(), kind: ChangeKind::Added(Node::Null) },
+            Change { path: "list::0".into(), kind: ChangeKind::Added(Node::Integer(0)) },
+            Change { path: "list::2".into(), kind: ChangeKind::Changed(Node::Integer(2), Node::Integer(5)) },
+            Change { path: "removed".into(), kind: ChangeKind::Removed(Node::Bool(true)) },
+            Change { path: "server::port".into(), kind: ChangeKind::Changed(Node::Integer(80), Node::Integer(8080)) },
+        ]);
+
+        let same = Kfg::parse(b"list = [1, 2, 3, 4]\nremoved = true\nserver::port = 80\nserver::host = 'a'").unwrap();
+
+        assert!(from.diff(&same).is_empty());
+
+        let long = (0..100_000).map(Node::Integer).collect::<Vec<_>>();
+        let mut inserted = long.clone();
+        inserted.insert(50_000, Node::Null);
+
+        assert_eq!(align(&long, &inserted).into_iter().filter(|step| !matches!(step, Step::Same(..))).collect::<Vec<_>>(), [Step::Added(50_000)]);
+
+        // too long for the table between the common ends, compared by index
+        let shifted = (0..2_000).map(|i| Node::Integer(if i % 1_999 == 0 { i } else { i + 1 })).collect::<Vec<_>>();
+        let steps = align(&long[..2_000], &shifted);
+
+        assert_eq!((steps.len(), steps[1], steps[1_999]), (2_000, Step::Paired(1, 1), Step::Same(1_999, 1_999)));
+    }
+}
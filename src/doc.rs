@@ -0,0 +1,202 @@
+use crate::{edit, syntax, Ast};
+use std::collections::HashMap;
+
+/// Whether the source has anything that looks like a doc comment, to skip looking for them
+pub(crate) fn has_docs(buffer: &[u8]) -> bool {
+    buffer.windows(3).any(|window| window == b"///" || window == b"/**")
+}
+
+/// The text of a `/** */` block, without the `*` that usually starts its lines
+fn block(comment: &str) -> String {
+    let comment = comment.replace("\r\n", "\n");
+
+    let lines = comment
+        .split(['\n', '\r'])
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect::<Vec<_>>();
+
+    let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |end| end + 1);
+
+    lines[start..end].join("\n")
+}
+
+/// Doc comments by the byte they end at, `///` lines and `/** */` blocks
+///
+/// Comments are found the way highlighting finds them, so `/**` in a string or after `//`
+/// doesn't open a block, and `////` is a plain comment
+fn comments(source: &str) -> HashMap<usize, &str> {
+    syntax::comments(source)
+        .map(|span| {
+            let text = source[span.start..span.end].trim_end();
+            (span.start + text.len(), text)
+        })
+        .filter(|(_, text)| match text.strip_prefix("//") {
+            Some(line) => line.starts_with('/') && !line.starts_with("//"),
+            // `/**/` is an empty plain comment
+            None => text.starts_with("/**") && text.len() > 4 && text.ends_with("*/"),
+        })
+        .collect()
+}
+
+/// `head` without its last line break, `None` if it doesn't end in one
+fn above(head: &str) -> Option<&str> {
+    match head.strip_suffix('\n') {
+        Some(head) => Some(head.strip_suffix('\r').unwrap_or(head)),
+        None => head.strip_suffix('\r'),
+    }
+}
+
+/// The doc comment written right before byte `start`, either `///` lines or a `/** */`
+/// block from `comments`, on the line above or on the same line
+fn before(source: &str, comments: &HashMap<usize, &str>, start: usize) -> Option<String> {
+    let head = source[..start].trim_end_matches([' ', '\t']);
+
+    let opened = |head: &str| {
+        let comment = comments.get(&head.len())?;
+        comment.starts_with("/**").then(|| block(&comment[3..comment.len() - 2]))
+    };
+
+    if head.ends_with("*/") {
+        return opened(head);
+    }
+
+    let mut head = above(head)?.trim_end_matches([' ', '\t']);
+
+    if head.ends_with("*/") {
+        return opened(head);
+    }
+
+    let mut lines = vec![];
+
+    // `///` lines from the last one up, each alone on its line
+    while let Some(line) = comments.get(&head.len()).and_then(|comment| comment.strip_prefix("///")) {
+        let start = head.len() - line.len() - 3;
+        let indent = edit::line_start(source, start);
+
+        if !source[indent..start].trim().is_empty() {
+            break;
+        }
+
+        lines.push(line.strip_prefix(' ').unwrap_or(line));
+
+        match above(&source[..indent]) {
+            Some(rest) => head = rest.trim_end_matches([' ', '\t']),
+            None => break,
+        }
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// The doc comment of every documented assignment and dict entry starting from byte `from`,
+/// in the order they're written
+pub(crate) fn written(source: &str, from: usize) -> Vec<(Vec<String>, String)> {
+    let comments = comments(source);
+
+    edit::entries(source)
+        .into_iter()
+        .filter(|(_, span)| span.start >= from)
+        .filter_map(|(path, span)| Some((path, before(source, &comments, span.start)?)))
+        .collect()
+}
+
+/// Attaches the doc comment of every assignment and dict entry to its `key::path`,
+/// a later documented write of the same path replaces the doc
+pub(crate) fn docs(source: &str, ast: &Ast) -> HashMap<String, String> {
+    written(source, 0)
+        .into_iter()
+        .map(|(path, doc)| (path.join("::"), doc))
+        .filter(|(path, _)| ast.lookup(path).is_some())
+        .collect()
+}
+
+impl Ast {
+    /// The doc comment of a `key::path`, written as `///` lines or a `/** */` block right before it
+    pub fn doc(&self, path: &str) -> Option<&str> {
+        self.1.docs.get(path).map(String::as_str)
+    }
+
+    /// Documents a `key::path`, the kfg writer puts it before the key
+    pub fn set_doc(&mut self, path: &str, doc: impl Into<String>) {
+        self.1.docs.insert(path.into(), doc.into());
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use crate::Kfg;
+
+    #[test]
+    fn test_docs() {
+        let source = "\
+/// The port to listen on
+///
+/// Below 1024 needs root
+port = 80
+
+// not a doc
+host = 'a'
+
+/**
+ * The server
+ */
+server = {
+    /// Whether to use tls
+    .tls: true
+    .name: /** not here */ 'x'
+}
+/** Scoped */ server::workers = 4
+";
+
+        let mut ast = Kfg::parse(source.as_bytes()).unwrap();
+
+        assert_eq!(ast.doc("port"), Some("The port to listen on\n\nBelow 1024 needs root"));
+        assert_eq!(ast.doc("host"), None);
+        assert_eq!(ast.doc("server"), Some("The server"));
+        assert_eq!(ast.doc("server::tls"), Some("Whether to use tls"));
+        assert_eq!(ast.doc("server::name"), None);
+        assert_eq!(ast.doc("server::workers"), Some("Scoped"));
+
+        ast.set_doc("host", "Where to listen");
+
+        let written = ast.to_kfg();
+        assert!(written.contains("/// The port to listen on\n///\n/// Below 1024 needs root\nport = 80\n"));
+        assert!(written.contains("    /// Whether to use tls\n    .tls: true\n"));
+
+        let reread = Kfg::parse(written.as_bytes()).unwrap();
+
+        for path in ["port", "host", "server", "server::tls", "server::workers"] {
+            assert_eq!(reread.doc(path), ast.doc(path));
+        }
+
+        assert_eq!(Kfg::parse_spanned(source.as_bytes(), Default::default()).unwrap().doc("port"), ast.doc("port"));
+
+        let ast = Kfg::parse(b"/** a */ a = 1 /* b */ b = 2\n/**/ c = 3\n/** d */ /* e */ e = 5").unwrap();
+
+        assert_eq!(ast.doc("a"), Some("a"));
+        assert_eq!((ast.doc("b"), ast.doc("c"), ast.doc("e")), (None, None, None));
+
+        let ast = Kfg::parse(b"/// one\r\n/// two\r///three\r\na = 1\r\n\r\n/// b\r\n\r\nb = 2").unwrap();
+
+        assert_eq!((ast.doc("a"), ast.doc("b")), (Some("one\ntwo\nthree"), None));
+
+        let ast = Kfg::parse(b"s = '/**'\na = 1 // see /**\n// */\nb = 2\n//// not a doc\nc = 3\n/// d   \nd = 4\n").unwrap();
+
+        assert_eq!((ast.doc("a"), ast.doc("b"), ast.doc("c"), ast.doc("d")), (None, None, None, Some("d")));
+
+        let ast = Kfg::parse(b"x = 1 /// not alone\ny = 2\n/// z\nz = '''\n/** */'''\nw = 1").unwrap();
+
+        assert_eq!((ast.doc("y"), ast.doc("z"), ast.doc("w")), (None, Some("z"), None));
+    }
+}
@@ -0,0 +1,626 @@
+use crate::{lexer, span, writer, Kfg, Node, ParseOptions, ParserError, Token, TokenKind};
+
+#[derive(Debug, Clone)]
+pub enum EditError {
+    Parse(ParserError),
+    MissingKey(String),
+    NotADict(String),
+}
+
+impl std::error::Error for EditError {}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl From<ParserError> for EditError {
+    fn from(value: ParserError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+/// Where a value was written in the source, in bytes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+struct Locator<'a> {
+    tokens: &'a [Token],
+    index: usize,
+    values: Vec<(Vec<String>, Span)>,
+    entries: Vec<(Vec<String>, Span)>,
+    keys: Vec<(Vec<String>, Span)>,
+}
+
+impl<'a> Locator<'a> {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.index).map(|token| &token.kind)
+    }
+
+    fn skip(&mut self, skip: impl Fn(&TokenKind) -> bool) {
+        while self.peek().is_some_and(&skip) {
+            self.index += 1;
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Option<&'a Token> {
+        let token = self.tokens.get(self.index).filter(|token| token.kind == kind)?;
+        self.index += 1;
+        Some(token)
+    }
+
+    fn symbol(&mut self) -> Option<String> {
+        match self.peek()? {
+            TokenKind::Symbol(symbol) => {
+                let symbol = symbol.clone();
+                self.index += 1;
+                Some(symbol)
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads a bare or quoted key into `path`, recording where it was written, quotes included
+    fn key(&mut self, path: &mut Vec<String>) -> Option<()> {
+        let first = self.tokens.get(self.index)?;
+
+        let last = match self.expect(TokenKind::Quote) {
+            Some(_) => {
+                path.push(self.symbol()?);
+                self.expect(TokenKind::Quote)?
+            }
+            None => {
+                path.push(self.symbol()?);
+                first
+            }
+        };
+
+        let span = Span {
+            start: first.position.offset,
+            end: last.position.end,
+        };
+
+        self.keys.push((path.clone(), span));
+
+        Some(())
+    }
+
+    /// Records a whole assignment or dict entry, from its key to the end of the value just read
+    fn entry(&mut self, path: Vec<String>, start: usize) {
+        if let Some((_, value)) = self.values.last() {
+            let end = value.end;
+            self.entries.push((path, Span { start, end }));
+        }
+    }
+
+    fn assignments(&mut self) -> Option<()> {
+        loop {
+            self.skip(|kind| matches!(kind, TokenKind::NewLine));
+
+            if self.peek().is_none() {
+                return Some(());
+            }
+
+            let start = self.tokens.get(self.index)?.position.offset;
+            let mut path = vec![];
+            self.key(&mut path)?;
+
+            while self.expect(TokenKind::Colon).is_some() {
+                self.expect(TokenKind::Colon)?;
+                // a scope can end its line, like the parser allows
+                self.skip(|kind| matches!(kind, TokenKind::NewLine));
+                self.key(&mut path)?;
+            }
+
+            self.expect(TokenKind::Equals)?;
+            self.value(&mut path)?;
+            self.entry(path, start);
+        }
+    }
+
+    fn value(&mut self, path: &mut Vec<String>) -> Option<()> {
+        use TokenKind::*;
+
+        // deeper than any parse allows, and deep enough to run out of stack
+        if path.len() > ParseOptions::default().max_depth + 1 {
+            return None;
+        }
+
+        let tokens = self.tokens;
+        let first = tokens.get(self.index)?;
+        let start = first.position.offset;
+
+        self.index += 1;
+
+        let last = match first.kind {
+            Symbol(_) => {
+                // the parser reads whatever follows the point into the number, lenient parses keep it
+                if tokens.get(self.index).is_some_and(|dot| dot.kind == Dot && first.touches(dot)) {
+                    tokens.get(self.index + 1)?;
+                    self.index += 2;
+                }
+
+                &tokens[self.index - 1]
+            }
+            Quote => {
+                self.symbol()?;
+                self.expect(Quote)?
+            }
+            OpenBracket => {
+                let mut i = 0;
+
+                loop {
+                    self.skip(|kind| matches!(kind, NewLine | Comma));
+
+                    if let Some(close) = self.expect(CloseBracket) {
+                        break close;
+                    }
+
+                    path.push(i.to_string());
+                    self.value(path)?;
+                    path.pop();
+
+                    i += 1;
+                }
+            }
+            OpenCurly => loop {
+                self.skip(|kind| matches!(kind, NewLine | Comma));
+
+                if let Some(close) = self.expect(CloseCurly) {
+                    break close;
+                }
+
+                let dot = self.expect(Dot)?;
+                let len = path.len();
+                self.key(path)?;
+
+                self.expect(Colon)?;
+
+                // `.a::b:` is a scoped key
+                while self.expect(Colon).is_some() {
+                    self.key(path)?;
+                    self.expect(Colon)?;
+                }
+
+                self.value(path)?;
+                self.entry(path.clone(), dot.position.offset);
+                path.truncate(len);
+            },
+            _ => return None,
+        };
+
+        let end = last.position.end;
+
+        self.values.push((path.clone(), Span { start, end }));
+
+        Some(())
+    }
+}
+
+/// Finds the span of every value in a source file, keyed by its path, in the order they're written
+///
+/// Stops at the first thing it can't understand, so only the values before it are returned
+pub fn locate(source: &str) -> Vec<(Vec<String>, Span)> {
+    locator(source, |locator| locator.values)
+}
+
+/// Like [`locate`], but spans cover whole assignments and dict entries, key included
+pub fn entries(source: &str) -> Vec<(Vec<String>, Span)> {
+    locator(source, |locator| locator.entries)
+}
+
+/// Finds every key as written, each segment of a scoped assignment on its own,
+/// paired with the path it names
+pub fn keys(source: &str) -> Vec<(Vec<String>, Span)> {
+    locator(source, |locator| locator.keys)
+}
+
+enum Open {
+    Dict(Vec<String>),
+    Array(Vec<String>, usize),
+}
+
+/// Finds the path whose keys can be written at byte `offset`, `None` where a value goes
+///
+/// Only the tokens before `offset` are looked at, so it works while the file is being typed
+pub fn scope_at(source: &str, offset: usize) -> Option<Vec<String>> {
+    use TokenKind::*;
+
+    let tokens = lexer::lex_partial(source.as_bytes());
+
+    let mut open: Vec<Open> = vec![];
+    let mut keys = vec![];
+    // the path of the dict entry being read
+    let mut entry: Option<Vec<String>> = None;
+    // a segment of its key comes next
+    let mut segment = false;
+    // the last token was the colon after its key
+    let mut colon = false;
+    let mut value = false;
+    let mut done = false;
+    let mut string = false;
+    // the string being read is a key
+    let mut quoted = false;
+
+    for token in &tokens {
+        let end = token.position.end;
+
+        // a key being typed isn't part of the scope yet
+        if end > offset || (end == offset && matches!(token.kind, Symbol(_)) && (!string || quoted)) {
+            break;
+        }
+
+        if string {
+            string = token.kind != Quote;
+
+            if let (Symbol(symbol), true) = (&token.kind, quoted) {
+                match open.last() {
+                    Some(_) => {
+                        entry.get_or_insert_with(Vec::new).push(symbol.clone());
+                        segment = false;
+                    }
+                    None => keys.push(symbol.clone()),
+                }
+            }
+
+            continue;
+        }
+
+        let after_colon = std::mem::take(&mut colon);
+        let expecting = value || matches!(open.last(), Some(Open::Array(..)));
+
+        match (&token.kind, open.last_mut()) {
+            (OpenCurly, _) if expecting => {
+                open.push(Open::Dict(scope(&open, &entry, &keys)));
+                value = false;
+                entry = None;
+                segment = false;
+            }
+            (OpenBracket, _) if expecting => {
+                open.push(Open::Array(scope(&open, &entry, &keys), 0));
+                value = false;
+            }
+            (CloseCurly | CloseBracket, _) => {
+                open.pop();
+                entry = None;
+                segment = false;
+                done = open.is_empty();
+            }
+            (Comma, Some(Open::Array(_, i))) => *i += 1,
+            (NewLine, None) => {
+                keys.clear();
+                done = false;
+            }
+            (Quote, None) if !value && !done => {
+                string = true;
+                quoted = true;
+            }
+            (Quote, Some(Open::Dict(_))) if segment => {
+                string = true;
+                quoted = true;
+            }
+            (Quote, _) => {
+                string = true;
+                quoted = false;
+                value = false;
+                done = open.is_empty();
+            }
+            (Symbol(symbol), None) if !value && !done => keys.push(symbol.clone()),
+            (Equals, None) if !done => value = true,
+            (Dot, Some(Open::Dict(_))) if !value => {
+                entry = Some(vec![]);
+                segment = true;
+            }
+            (Symbol(symbol), Some(Open::Dict(_))) if segment => {
+                entry.get_or_insert_with(Vec::new).push(symbol.clone());
+                segment = false;
+            }
+            // `.a::` goes on with another segment
+            (Colon, Some(Open::Dict(_))) if after_colon => {
+                value = false;
+                segment = true;
+            }
+            (Colon, Some(Open::Dict(_))) if entry.is_some() && !segment && !value => {
+                value = true;
+                colon = true;
+            }
+            _ if value => {
+                value = false;
+                done = open.is_empty();
+            }
+            _ => {}
+        }
+    }
+
+    match open.last() {
+        _ if (string && !quoted) || value => None,
+        Some(Open::Dict(path)) if segment => Some(path.iter().cloned().chain(entry.into_iter().flatten()).collect()),
+        Some(Open::Dict(path)) => Some(path.clone()),
+        Some(Open::Array(..)) => None,
+        None if done => None,
+        None => Some(keys),
+    }
+}
+
+/// The path of the value about to be opened, inside whatever is open now
+fn scope(open: &[Open], entry: &Option<Vec<String>>, keys: &[String]) -> Vec<String> {
+    match open.last() {
+        Some(Open::Dict(path)) => path.iter().chain(entry.iter().flatten()).cloned().collect(),
+        Some(Open::Array(path, i)) => path.iter().cloned().chain([i.to_string()]).collect(),
+        None => keys.to_vec(),
+    }
+}
+
+fn locator(source: &str, result: impl FnOnce(Locator) -> Vec<(Vec<String>, Span)>) -> Vec<(Vec<String>, Span)> {
+    let tokens = lexer::lex_partial(source.as_bytes());
+
+    let mut locator = Locator {
+        tokens: &tokens,
+        index: 0,
+        values: vec![],
+        entries: vec![],
+        keys: vec![],
+    };
+
+    locator.assignments();
+    result(locator)
+}
+
+/// Where the line with byte `offset` starts
+pub(crate) fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind(['\n', '\r']).map_or(0, |i| i + 1)
+}
+
+/// Where the line after byte `offset` starts, past its line break
+pub(crate) fn line_end(source: &str, offset: usize) -> usize {
+    let rest = &source[offset..];
+
+    match rest.find(['\n', '\r']) {
+        Some(i) if rest[i..].starts_with("\r\n") => offset + i + 2,
+        Some(i) => offset + i + 1,
+        None => source.len(),
+    }
+}
+
+/// Replaces the value at a `key::path` in `source`, keeping everything else as written
+///
+/// Lines written keep the line ending of the source
+///
+/// Missing keys are appended as a scoped assignment when `create` is set. So is a key
+/// with no value of its own to replace, like the dict `.a::b: 1` makes, after what
+/// set it is removed
+pub fn set(source: &str, path: &str, node: &Node, create: bool) -> Result<String, EditError> {
+    let ast = Kfg::parse(source.as_bytes())?;
+    let segments = path.split("::").collect::<Vec<_>>();
+    let ending = span::line_ending(source);
+
+    if ast.lookup(path).is_some() {
+        let span = locate(source).into_iter().rev().find(|(path, _)| *path == segments);
+
+        if let Some((_, span)) = span {
+            let line = line_start(source, span.start);
+            let indent = &source[line..span.start];
+            let indent = &indent[..indent.len() - indent.trim_start().len()];
+
+            let value = node.to_kfg().replace('\n', &format!("{ending}{indent}"));
+            let edited = format!("{}{value}{}", &source[..span.start], &source[span.end..]);
+
+            // a later scoped assignment can still change what was replaced
+            let written = Kfg::parse(edited.as_bytes()).ok().and_then(|ast| ast.lookup(path).map(Node::to_kfg));
+
+            if written == Some(node.to_kfg()) {
+                return Ok(edited);
+            }
+        }
+
+        return Ok(append(remove(source, path)?, path, node, ending));
+    }
+
+    if !create {
+        return Err(EditError::MissingKey(path.into()));
+    }
+
+    for i in 1..segments.len() {
+        let parent = segments[..i].join("::");
+
+        match ast.lookup(&parent) {
+            Some(Node::Dict(_)) | None => {}
+            Some(_) => return Err(EditError::NotADict(parent)),
+        }
+    }
+
+    Ok(append(source.to_string(), path, node, ending))
+}
+
+/// Writes `node` as a scoped assignment at the end of `source`
+fn append(mut source: String, path: &str, node: &Node, ending: &str) -> String {
+    if !source.is_empty() && !source.ends_with(['\n', '\r']) {
+        source.push_str(ending);
+    }
+
+    source.push_str(&format!("{} = {}{ending}", writer::path(path), node.to_kfg().replace('\n', ending)));
+    source
+}
+
+/// Deletes every assignment and dict entry that sets a `key::path` or anything below it
+///
+/// Lines left empty are dropped along with their trailing comment
+pub fn remove(source: &str, path: &str) -> Result<String, EditError> {
+    let ast = Kfg::parse(source.as_bytes())?;
+    let segments = path.split("::").collect::<Vec<_>>();
+
+    if ast.lookup(path).is_none() {
+        return Err(EditError::MissingKey(path.into()));
+    }
+
+    let mut spans = entries(source)
+        .into_iter()
+        .filter(|(path, _)| path.len() >= segments.len() && path[..segments.len()] == segments)
+        .map(|(_, span)| span)
+        .collect::<Vec<_>>();
+
+    // entries inside a removed one go with it
+    spans.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+    spans.dedup_by(|inner, outer| inner.end <= outer.end);
+
+    let mut source = source.to_string();
+
+    for span in spans.into_iter().rev() {
+        let line = line_start(&source, span.start);
+        let rest = source[span.end..].trim_start_matches([' ', '\t']);
+        let rest = rest.strip_prefix(',').unwrap_or(rest).trim_start_matches([' ', '\t']);
+        let after = source.len() - rest.len();
+
+        let line_end = line_end(&source, after);
+        let trailing = source[after..line_end].trim();
+
+        if source[line..span.start].trim().is_empty() && (trailing.is_empty() || trailing.starts_with("//")) {
+            source.replace_range(line..line_end, "");
+        } else {
+            source.replace_range(span.start..after, "");
+        }
+    }
+
+    match Kfg::parse(source.as_bytes())?.lookup(path) {
+        None => Ok(source),
+        Some(_) => Err(EditError::MissingKey(path.into())),
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+// port of the server
+port = 8080 // keep in sync
+server = {
+    .hosts: ['a', 'b'] /* hosts */
+    .ratio: 0.5
+}
+";
+
+    #[test]
+    fn test_locate() {
+        let values = locate(SOURCE)
+            .into_iter()
+            .map(|(path, span)| (path.join("::"), &SOURCE[span.start..span.end]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(values, [
+            ("port".into(), "8080"),
+            ("server::hosts::0".into(), "'a'"),
+            ("server::hosts::1".into(), "'b'"),
+            ("server::hosts".into(), "['a', 'b']"),
+            ("server::ratio".into(), "0.5"),
+            ("server".into(), "{\n    .hosts: ['a', 'b'] /* hosts */\n    .ratio: 0.5\n}"),
+        ]);
+
+        let paths = entries("a::\n  b = 1\nc = 2").into_iter().map(|(path, _)| path.join("::")).collect::<Vec<_>>();
+        assert_eq!(paths, ["a::b", "c"]);
+
+        let paths = entries("a = 8.\nb = 1").into_iter().map(|(path, _)| path.join("::")).collect::<Vec<_>>();
+        assert_eq!(paths, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_set() {
+        let source = set(SOURCE, "port", &Node::Integer(9090), false).unwrap();
+        let source = set(&source, "server::hosts::1", &Node::String("c".into()), false).unwrap();
+        let source = set(&source, "server::ratio", &Node::Dict([("x".into(), Node::Integer(1))].into()), false).unwrap();
+        let source = set(&source, "server::tls::enabled", &Node::Bool(true), true).unwrap();
+
+        assert_eq!(source, "\
+// port of the server
+port = 9090 // keep in sync
+server = {
+    .hosts: ['a', 'c'] /* hosts */
+    .ratio: {
+        .x: 1
+    }
+}
+server::tls::enabled = true
+");
+
+        let crlf = SOURCE.replace('\n', "\r\n");
+        let edited = set(&crlf, "server::ratio", &Node::Dict([("x".into(), Node::Integer(1))].into()), false).unwrap();
+        let edited = set(&edited, "server::tls::enabled", &Node::Bool(true), true).unwrap();
+        let edited = remove(&edited, "port").unwrap();
+        assert_eq!(edited.matches('\n').count(), edited.matches("\r\n").count());
+        assert!(edited.ends_with("    .ratio: {\r\n        .x: 1\r\n    }\r\n}\r\nserver::tls::enabled = true\r\n"));
+
+        assert_eq!(set("x = { .a::b: 1 .c: 2 }", "x::a", &Node::Integer(3), false).unwrap(), "x = { .c: 2 }\nx::a = 3\n");
+        assert_eq!(set("a::b = 1\n", "a", &Node::Integer(3), false).unwrap(), "a = 3\n");
+        assert_eq!(set("a = { .b: 1 }\na::b::c = 2\n", "a::b", &Node::Integer(3), false).unwrap(), "a = { }\na::b = 3\n");
+
+        assert!(matches!(set(SOURCE, "missing", &Node::Null, false), Err(EditError::MissingKey(_))));
+        assert!(matches!(set(SOURCE, "port::inner", &Node::Null, true), Err(EditError::NotADict(key)) if key == "port"));
+    }
+
+    #[test]
+    fn test_remove() {
+        let source = remove(SOURCE, "server::ratio").unwrap();
+        let source = remove(&source, "port").unwrap();
+
+        assert_eq!(source, "\
+// port of the server
+server = {
+    .hosts: ['a', 'b'] /* hosts */
+}
+");
+
+        assert_eq!(remove("a::b = 1\na::c = 2 b = { .x: 1, .y: 2 }\n", "a").unwrap(), "b = { .x: 1, .y: 2 }\n");
+        assert_eq!(remove("b = { .x: 1, .y: 2 }\n", "b::x").unwrap(), "b = { .y: 2 }\n");
+        assert!(matches!(remove(SOURCE, "missing"), Err(EditError::MissingKey(_))));
+    }
+
+    #[test]
+    fn test_keys() {
+        let keys = keys("a::b = { .c: 1 }\n")
+            .into_iter()
+            .map(|(path, span)| (path.join("::"), span.start))
+            .collect::<Vec<_>>();
+
+        assert_eq!(keys, [("a".into(), 0), ("a::b".into(), 3), ("a::b::c".into(), 10)]);
+
+        let source = "'a b'::c = { .'d.e': 1 }\n";
+        let quoted = super::keys(source).into_iter().map(|(path, span)| (path, &source[span.start..span.end])).collect::<Vec<_>>();
+
+        assert_eq!(quoted[2], (vec!["a b".to_string(), "c".into(), "d.e".into()], "'d.e'"));
+
+        let source = "a = { .b::c: 1, .b::d: 2 }\n";
+        let scoped = entries(source).into_iter().map(|(path, span)| (path.join("::"), &source[span.start..span.end])).collect::<Vec<_>>();
+
+        assert_eq!(scoped, [("a::b::c".into(), ".b::c: 1"), ("a::b::d".into(), ".b::d: 2"), ("a".into(), "a = { .b::c: 1, .b::d: 2 }")]);
+        assert_eq!(set(source, "a::b::d", &Node::Integer(3), false).unwrap(), "a = { .b::c: 1, .b::d: 3 }\n");
+        assert_eq!(set(source, "x y::z", &Node::Null, true).unwrap(), format!("{source}'x y'::z = null\n"));
+    }
+
+    #[test]
+    fn test_scope_at() {
+        let scope = |source: &str| scope_at(source, source.len()).map(|path| path.join("::"));
+
+        assert_eq!(scope(""), Some("".into()));
+        assert_eq!(scope("server::tl"), Some("server".into()));
+        assert_eq!(scope("a = 1\nserver::"), Some("server".into()));
+        assert_eq!(scope("a = "), None);
+        assert_eq!(scope("a = 1.5"), None);
+        assert_eq!(scope("a = { .b: 1.5 .c: { ."), Some("a::c".into()));
+        assert_eq!(scope("a = { .b: 'x, y' .c: [{ .d: 1 }, { .e"), Some("a::c::1".into()));
+        assert_eq!(scope("a = { .b: "), None);
+        assert_eq!(scope("a = [1, "), None);
+        assert_eq!(scope("a = { .b: {}\n"), Some("a".into()));
+        assert_eq!(scope("'a b'::'c"), Some("a b".into()));
+        assert_eq!(scope("a = { .'b c': { .'d"), Some("a::b c".into()));
+        assert_eq!(scope("a = { .'b c': 'd"), None);
+        assert_eq!(scope("a = { .b::c::"), Some("a::b::c".into()));
+        assert_eq!(scope("a = { .b::c: { .d"), Some("a::b::c".into()));
+        assert_eq!(scope("a = { .b::c: 1, ."), Some("a".into()));
+        assert_eq!(scope("a = { .b::c: "), None);
+    }
+}
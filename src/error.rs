@@ -0,0 +1,86 @@
+use crate::{LexerError, ParserError};
+use std::sync::Arc;
+
+/// Anything that can go wrong reading a kfg file, from opening it to parsing it
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Shared so the error stays `Clone`
+    Io(Arc<std::io::Error>),
+    Lexer(LexerError),
+    Parser(ParserError),
+}
+
+impl Error {
+    /// The variant name of the underlying error, e.g. `UnterminatedString`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "Io",
+            Error::Lexer(error) => error.name(),
+            Error::Parser(error) => error.name(),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(Arc::new(value))
+    }
+}
+
+impl From<LexerError> for Error {
+    fn from(value: LexerError) -> Self {
+        Self::Lexer(value)
+    }
+}
+
+/// Lexer errors the parser passed on become [`Error::Lexer`]
+impl From<ParserError> for Error {
+    fn from(value: ParserError) -> Self {
+        match value {
+            ParserError::Lexer(error) => Self::Lexer(error),
+            error => Self::Parser(error),
+        }
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Kfg;
+
+    #[test]
+    fn test_read() {
+        let error = Kfg::read("/nonexistent/settings.kfg").unwrap_err();
+        assert!(matches!(&error, Error::Io(io) if io.kind() == std::io::ErrorKind::NotFound));
+        assert!(std::error::Error::source(&error).is_some());
+
+        let path = std::env::temp_dir().join(format!("kfg-read-{}.kfg", std::process::id()));
+
+        std::fs::write(&path, "a = 'open").unwrap();
+        assert!(matches!(Kfg::read(&path), Err(Error::Lexer(LexerError::UnterminatedString(_)))));
+
+        std::fs::write(&path, "a = = 1").unwrap();
+        assert!(matches!(Kfg::read(&path), Err(Error::Parser(_))));
+
+        std::fs::write(&path, "a = 1").unwrap();
+        assert!(Kfg::read(&path).is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
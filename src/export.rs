@@ -0,0 +1,260 @@
+use crate::{Ast, Node};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCase {
+    #[default]
+    Upper,
+    Lower,
+    Preserve,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlatOptions {
+    /// Prepended to every key, joined with the separator
+    pub prefix: String,
+    /// Placed between the segments of a path, `::` is never used
+    pub separator: String,
+    pub casing: KeyCase,
+}
+
+impl Default for FlatOptions {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            separator: "_".into(),
+            casing: KeyCase::Upper,
+        }
+    }
+}
+
+impl FlatOptions {
+    /// Dotted keys with their original casing, as used by `.properties` files
+    pub fn properties() -> Self {
+        Self {
+            prefix: String::new(),
+            separator: ".".into(),
+            casing: KeyCase::Preserve,
+        }
+    }
+
+    fn key(&self, path: &[String]) -> String {
+        let key = std::iter::once(self.prefix.as_str())
+            .filter(|prefix| !prefix.is_empty())
+            .chain(path.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(&self.separator);
+
+        match self.casing {
+            KeyCase::Upper => key.to_uppercase(),
+            KeyCase::Lower => key.to_lowercase(),
+            KeyCase::Preserve => key,
+        }
+    }
+}
+
+/// Renders a leaf without any decoration, `null` and empty containers become empty strings
+fn scalar(node: &Node) -> String {
+    match node {
+        Node::String(value) => value.clone(),
+        Node::Integer(value) => value.to_string(),
+        Node::Float(value) => value.to_string(),
+        Node::Bool(value) => value.to_string(),
+        Node::Array(_) | Node::Dict(_) | Node::Null => String::new(),
+    }
+}
+
+/// Replaces every character that can't be part of an environment variable name
+fn env_key(key: String) -> String {
+    let key = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect::<String>();
+
+    if key.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{key}")
+    } else {
+        key
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-.,/:@%+=".contains(c);
+
+    if !value.is_empty() && value.chars().all(safe) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+fn dotenv_quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-.,/:@%+=".contains(c);
+
+    if value.chars().all(safe) {
+        return value.to_string();
+    }
+
+    let mut string = String::from('"');
+
+    for c in value.chars() {
+        match c {
+            '\\' => string.push_str("\\\\"),
+            '"' => string.push_str("\\\""),
+            '\n' => string.push_str("\\n"),
+            '\r' => string.push_str("\\r"),
+            '\t' => string.push_str("\\t"),
+            c => string.push(c),
+        }
+    }
+
+    string.push('"');
+    string
+}
+
+fn properties_escape(value: &str, key: bool) -> String {
+    let mut string = String::new();
+
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            '\r' => string.push_str("\\r"),
+            '\t' => string.push_str("\\t"),
+            ' ' if key || i == 0 => string.push_str("\\ "),
+            '=' | ':' | '#' | '!' if key => {
+                string.push('\\');
+                string.push(c);
+            }
+            c if !c.is_ascii() || c.is_ascii_control() => {
+                let mut units = [0; 2];
+
+                for unit in c.encode_utf16(&mut units) {
+                    string.push_str(&format!("\\u{unit:04X}"));
+                }
+            }
+            c => string.push(c),
+        }
+    }
+
+    string
+}
+
+/// Flattens the tree into keys built with `options` and their unquoted values
+pub fn pairs(ast: &Ast, options: &FlatOptions) -> Vec<(String, String)> {
+    ast.leaves()
+        .into_iter()
+        .map(|(path, node)| (options.key(&path), scalar(node)))
+        .collect()
+}
+
+/// Writes a shell script with one `export KEY=value` per leaf
+pub fn shell(ast: &Ast, options: &FlatOptions) -> String {
+    pairs(ast, options)
+        .into_iter()
+        .map(|(key, value)| format!("export {}={}\n", env_key(key), shell_quote(&value)))
+        .collect()
+}
+
+/// Writes a `.env` file with one `KEY=value` per leaf
+pub fn dotenv(ast: &Ast, options: &FlatOptions) -> String {
+    pairs(ast, options)
+        .into_iter()
+        .map(|(key, value)| format!("{}={}\n", env_key(key), dotenv_quote(&value)))
+        .collect()
+}
+
+/// Writes a Java-style `.properties` file with one `key=value` per leaf
+pub fn properties(ast: &Ast, options: &FlatOptions) -> String {
+    pairs(ast, options)
+        .into_iter()
+        .map(|(key, value)| format!("{}={}\n", properties_escape(&key, true), properties_escape(&value, false)))
+        .collect()
+}
+
+/// The environment variable a leaf is set as, like `PREFIX_KEY_PATH`
+pub(crate) fn env_name(path: &[String], prefix: &str) -> String {
+    let options = FlatOptions {
+        prefix: prefix.into(),
+        ..Default::default()
+    };
+
+    env_key(options.key(path))
+}
+
+impl Ast {
+    /// Sets every leaf as an environment variable of `command`, named like `PREFIX_KEY_PATH`
+    pub fn apply_to_command(&self, command: &mut std::process::Command, prefix: &str) {
+        for (path, node) in self.leaves() {
+            command.env(env_name(&path, prefix), scalar(node));
+        }
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Kfg;
+
+    const BUFFER: &[u8] = b"
+        debug = true
+        name = 'it\\'s here'
+        server::port = 8080
+        server::hosts = ['a.example.com', 'b example']
+        server::tls = { .ratio: 0.5 .cert: null }
+    ";
+
+    #[test]
+    fn test_shell() {
+        let ast = Kfg::parse(BUFFER).unwrap();
+
+        let options = FlatOptions { prefix: "app".into(), ..Default::default() };
+
+        assert_eq!(shell(&ast, &options), concat!(
+            "export APP_DEBUG=true\n",
+            "export APP_NAME='it'\\''s here'\n",
+            "export APP_SERVER_HOSTS_0=a.example.com\n",
+            "export APP_SERVER_HOSTS_1='b example'\n",
+            "export APP_SERVER_PORT=8080\n",
+            "export APP_SERVER_TLS_CERT=''\n",
+            "export APP_SERVER_TLS_RATIO=0.5\n",
+        ));
+    }
+
+    #[test]
+    fn test_dotenv() {
+        let ast = Kfg::parse(BUFFER).unwrap();
+
+        let written = dotenv(&ast, &FlatOptions::default());
+
+        assert_eq!(written, concat!(
+            "DEBUG=true\n",
+            "NAME=\"it's here\"\n",
+            "SERVER_HOSTS_0=a.example.com\n",
+            "SERVER_HOSTS_1=\"b example\"\n",
+            "SERVER_PORT=8080\n",
+            "SERVER_TLS_CERT=\n",
+            "SERVER_TLS_RATIO=0.5\n",
+        ));
+
+        let imported = crate::import::dotenv(written.as_bytes()).unwrap();
+
+        assert_eq!(imported["NAME"], Node::String("it's here".into()));
+        assert_eq!(imported["SERVER_PORT"], Node::Integer(8080));
+    }
+
+    #[test]
+    fn test_properties() {
+        let ast = Kfg::parse(BUFFER).unwrap();
+
+        assert_eq!(properties(&ast, &FlatOptions::properties()), concat!(
+            "debug=true\n",
+            "name=it's here\n",
+            "server.hosts.0=a.example.com\n",
+            "server.hosts.1=b example\n",
+            "server.port=8080\n",
+            "server.tls.cert=\n",
+            "server.tls.ratio=0.5\n",
+        ));
+    }
+}
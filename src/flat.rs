@@ -0,0 +1,247 @@
+use crate::{Ast, Node};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlatError {
+    EmptySegment(String),
+    LeafAndParent(String),
+    MissingIndex(String, usize),
+}
+
+impl std::error::Error for FlatError {}
+
+impl std::fmt::Display for FlatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A value accepted by [`Ast::from_flat`], strings are typed with [`Node::typed`]
+pub trait FlatValue {
+    fn into_node(self) -> Node;
+}
+
+impl FlatValue for Node {
+    fn into_node(self) -> Node {
+        self
+    }
+}
+
+impl FlatValue for String {
+    fn into_node(self) -> Node {
+        Node::typed(&self)
+    }
+}
+
+impl FlatValue for &str {
+    fn into_node(self) -> Node {
+        Node::typed(self)
+    }
+}
+
+enum Tree {
+    Leaf(Node),
+    Branch(BTreeMap<String, Tree>),
+}
+
+impl Tree {
+    fn insert(&mut self, path: &[&str], node: Node, full: &str) -> Result<(), FlatError> {
+        let Tree::Branch(branch) = self else {
+            return Err(FlatError::LeafAndParent(full.into()));
+        };
+
+        let (key, rest) = path.split_first().unwrap();
+
+        if rest.is_empty() {
+            if let Some(Tree::Branch(_)) = branch.get(*key) {
+                return Err(FlatError::LeafAndParent(full.into()));
+            }
+
+            branch.insert(key.to_string(), Tree::Leaf(node));
+
+            return Ok(());
+        }
+
+        branch
+            .entry(key.to_string())
+            .or_insert_with(|| Tree::Branch(BTreeMap::new()))
+            .insert(rest, node, full)
+    }
+
+    /// Builds the node, branches whose keys are all indices become arrays
+    fn into_node(self, path: &mut Vec<String>) -> Result<Node, FlatError> {
+        let branch = match self {
+            Tree::Leaf(node) => return Ok(node),
+            Tree::Branch(branch) => branch,
+        };
+
+        if branch.keys().all(|key| key.parse::<usize>().is_ok()) {
+            let mut array = branch
+                .into_iter()
+                .map(|(key, tree)| (key.parse::<usize>().unwrap(), tree))
+                .collect::<Vec<_>>();
+
+            array.sort_by_key(|(index, _)| *index);
+
+            let mut nodes = vec![];
+
+            for (i, (index, tree)) in array.into_iter().enumerate() {
+                if index != i {
+                    return Err(FlatError::MissingIndex(path.join("::"), i));
+                }
+
+                path.push(index.to_string());
+                nodes.push(tree.into_node(path)?);
+                path.pop();
+            }
+
+            return Ok(Node::Array(nodes));
+        }
+
+        let mut dict = HashMap::new();
+
+        for (key, tree) in branch {
+            path.push(key.clone());
+            dict.insert(key, tree.into_node(path)?);
+            path.pop();
+        }
+
+        Ok(Node::Dict(dict))
+    }
+}
+
+impl Ast {
+    /// Flattens the tree into `key::path` and scalar pairs, sorted by path
+    ///
+    /// Array elements are addressed by their index, empty arrays and dicts are kept as leaves
+    pub fn flatten(&self) -> Vec<(String, Node)> {
+        self.leaves()
+            .into_iter()
+            .map(|(path, node)| (path.join("::"), node.clone()))
+            .collect()
+    }
+
+    /// Builds a tree from `key::path` and value pairs, the inverse of [`Ast::flatten`]
+    ///
+    /// Numeric segments address array positions, a later pair replaces an earlier one with the same path
+    pub fn from_flat<K, V>(pairs: impl IntoIterator<Item = (K, V)>) -> Result<Ast, FlatError>
+    where
+        K: AsRef<str>,
+        V: FlatValue,
+    {
+        let mut root = Tree::Branch(BTreeMap::new());
+
+        for (key, value) in pairs {
+            let key = key.as_ref();
+            let path = key.split("::").collect::<Vec<_>>();
+
+            if path.iter().any(|segment| segment.is_empty()) {
+                return Err(FlatError::EmptySegment(key.into()));
+            }
+
+            root.insert(&path, value.into_node(), key)?;
+        }
+
+        let Tree::Branch(branch) = root else { unreachable!() };
+
+        let mut ast = Ast::default();
+        let mut path = vec![];
+
+        for (key, tree) in branch {
+            path.push(key.clone());
+            ast.0.insert(key, tree.into_node(&mut path)?);
+            path.pop();
+        }
+
+        Ok(ast)
+    }
+
+    pub(crate) fn leaves(&self) -> Vec<(Vec<String>, &Node)> {
+        let mut leaves = vec![];
+
+        get_leaves(&self.0, &mut vec![], &mut leaves);
+
+        leaves
+    }
+}
+
+fn get_leaves<'a>(dict: &'a HashMap<String, Node>, path: &mut Vec<String>, leaves: &mut Vec<(Vec<String>, &'a Node)>) {
+    let mut keys = dict.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    for key in keys {
+        path.push(key.clone());
+        get_node_leaves(&dict[key], path, leaves);
+        path.pop();
+    }
+}
+
+fn get_node_leaves<'a>(node: &'a Node, path: &mut Vec<String>, leaves: &mut Vec<(Vec<String>, &'a Node)>) {
+    match node {
+        Node::Dict(dict) if !dict.is_empty() => get_leaves(dict, path, leaves),
+        Node::Array(array) if !array.is_empty() => {
+            for (i, node) in array.iter().enumerate() {
+                path.push(i.to_string());
+                get_node_leaves(node, path, leaves);
+                path.pop();
+            }
+        }
+        node => leaves.push((path.clone(), node)),
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Kfg;
+
+    #[test]
+    fn test_round_trip() {
+        let ast = Kfg::parse(b"
+            a = 1
+            b::c = [true, ['x', null], {.d: 0.5}]
+            e = []
+            f = {}
+        ").unwrap();
+
+        let flat = ast.flatten();
+
+        assert_eq!(flat, vec![
+            ("a".to_string(), Node::Integer(1)),
+            ("b::c::0".into(), Node::Bool(true)),
+            ("b::c::1::0".into(), Node::String("x".into())),
+            ("b::c::1::1".into(), Node::Null),
+            ("b::c::2::d".into(), Node::Float(0.5)),
+            ("e".into(), Node::Array(vec![])),
+            ("f".into(), Node::Dict(HashMap::new())),
+        ]);
+
+        assert_eq!(Ast::from_flat(flat).unwrap().0, ast.0);
+    }
+
+    #[test]
+    fn test_from_flat() {
+        let ast = Ast::from_flat([
+            ("list::1", "two"),
+            ("list::0", "1"),
+            ("db::port", "5432"),
+            ("db::debug", "false"),
+        ]).unwrap();
+
+        let expected = HashMap::<String, _>::from([
+            ("list".into(), Node::Array(vec![Node::Integer(1), Node::String("two".into())])),
+            ("db".into(), Node::Dict(HashMap::from([
+                ("port".into(), Node::Integer(5432)),
+                ("debug".into(), Node::Bool(false)),
+            ]))),
+        ]);
+
+        assert_eq!(ast.0, expected);
+
+        assert_eq!(Ast::from_flat([("a", "1"), ("a::b", "2")]).unwrap_err(), FlatError::LeafAndParent("a::b".into()));
+        assert_eq!(Ast::from_flat([("a::b", "2"), ("a", "1")]).unwrap_err(), FlatError::LeafAndParent("a".into()));
+        assert_eq!(Ast::from_flat([("a::0", "1"), ("a::2", "1")]).unwrap_err(), FlatError::MissingIndex("a".into(), 1));
+        assert_eq!(Ast::from_flat([("a::::b", "1")]).unwrap_err(), FlatError::EmptySegment("a::::b".into()));
+    }
+}
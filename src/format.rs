@@ -0,0 +1,263 @@
+use crate::{lexer, span, ParserError, Token, TokenKind};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, Clone)]
+pub enum FormatError {
+    Parse(ParserError),
+    /// The formatted source reads back as a different tree, the source is left as it was
+    Changed,
+}
+
+impl std::error::Error for FormatError {}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl From<ParserError> for FormatError {
+    fn from(value: ParserError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    Text(String),
+    Comment(String),
+    Punct(TokenKind),
+    NewLine,
+}
+
+/// Groups raw tokens into strings, comments, symbols and punctuation, remembering
+/// whether each one was preceded by whitespace
+fn atoms(tokens: &[Token]) -> Vec<(Atom, bool)> {
+    use TokenKind::*;
+
+    let mut atoms = vec![];
+    let mut spaced = false;
+
+    let mut iter = tokens.iter().peekable();
+
+    while let Some(token) = iter.next() {
+        let next = iter.peek().map(|&token| &token.kind);
+
+        let atom = match (&token.kind, next) {
+            (Space | Tab, _) => {
+                spaced = true;
+                continue;
+            }
+            (NewLine, _) => Atom::NewLine,
+            (Slash, Some(Slash)) => {
+                let mut comment = String::new();
+
+                while let Some(token) = iter.next_if(|token| !matches!(token.kind, NewLine)) {
+                    comment.push_str(token.kind.as_str());
+                }
+
+                Atom::Comment(format!("/{}", comment.trim_end()))
+            }
+            (Slash, Some(Asterisk)) => {
+                let mut comment = String::from("/*");
+                let mut last = None;
+
+                // skip asterisk
+                iter.next();
+
+                for token in iter.by_ref() {
+                    comment.push_str(token.kind.as_str());
+
+                    if matches!((last, &token.kind), (Some(Asterisk), Slash)) {
+                        break;
+                    }
+
+                    last = Some(token.kind.clone());
+                }
+
+                Atom::Comment(comment)
+            }
+            // kept as written up to the closing quote, line breaks in them too
+            (Quote, _) | (Symbol(_), Some(Quote)) if token.kind == Quote || token.kind.as_str() == "r" => {
+                let raw = token.kind != Quote;
+                let mut string = token.kind.as_str().to_string();
+
+                if raw {
+                    string.push_str(iter.next().unwrap().kind.as_str());
+                }
+
+                let multiline = lexer::triple(&iter);
+
+                if multiline {
+                    string.push_str("''");
+                    iter.nth(1);
+                }
+
+                while let Some(token) = iter.next() {
+                    string.push_str(token.kind.as_str());
+
+                    match token.kind {
+                        Quote if !multiline => break,
+                        Quote if lexer::triple(&iter) => {
+                            string.push_str("''");
+                            iter.nth(1);
+                            break;
+                        }
+                        BackSlash if !raw => {
+                            if let Some(token) = iter.next() {
+                                string.push_str(token.kind.as_str());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                Atom::Text(string)
+            }
+            (Symbol(symbol), _) => Atom::Text(symbol.clone()),
+            (kind, _) => Atom::Punct(kind.clone()),
+        };
+
+        atoms.push((atom, spaced));
+        spaced = false;
+    }
+
+    atoms
+}
+
+/// Reprints a source file in canonical form, keeping its comments and line structure
+///
+/// Values are indented by nesting depth, `=`, `,` and single `:` are spaced,
+/// blank lines are collapsed to one and trailing whitespace is removed, lines end the
+/// way the first line of the source does
+pub fn format(buffer: &[u8]) -> String {
+    use TokenKind::*;
+
+    let atoms = atoms(&lexer::tokenize(buffer));
+
+    let is = |i: usize, kind: TokenKind| matches!(atoms.get(i), Some((Atom::Punct(k), _)) if *k == kind);
+
+    let mut out = String::new();
+    let mut depth = 0usize;
+    let mut newlines = 0;
+    let mut previous: Option<usize> = None;
+
+    for (i, (atom, spaced)) in atoms.iter().enumerate() {
+        if let Atom::NewLine = atom {
+            newlines += 1;
+            continue;
+        }
+
+        let closing = matches!(atom, Atom::Punct(CloseBracket | CloseCurly));
+
+        match previous {
+            Some(p) if newlines > 0 => {
+                let opening = matches!(atoms[p].0, Atom::Punct(OpenBracket | OpenCurly));
+                let max = if opening || closing { 1 } else { 2 };
+
+                out.push_str(&"\n".repeat(newlines.min(max)));
+                out.push_str(&INDENT.repeat(depth.saturating_sub(closing as usize)));
+            }
+            Some(p) => {
+                let double = |i: usize| is(i, Colon) && (is(i + 1, Colon) || (i > 0 && is(i - 1, Colon)));
+
+                let gap = match (&atoms[p].0, atom) {
+                    (_, Atom::Punct(Comma | CloseBracket | CloseCurly)) => false,
+                    (Atom::Punct(OpenBracket | OpenCurly | Dot), _) => false,
+                    (Atom::Punct(Comma | Equals), _) | (_, Atom::Punct(Equals)) => true,
+                    (Atom::Punct(Colon), _) if !double(p) => true,
+                    (Atom::Punct(Colon), _) | (_, Atom::Punct(Colon)) => false,
+                    (_, Atom::Comment(_)) => true,
+                    _ => *spaced,
+                };
+
+                if gap {
+                    out.push(' ');
+                }
+            }
+            None => out.push_str(&INDENT.repeat(depth.saturating_sub(closing as usize))),
+        }
+
+        match atom {
+            Atom::Text(text) | Atom::Comment(text) => out.push_str(text),
+            Atom::Punct(kind) => out.push_str(kind.as_str()),
+            Atom::NewLine => {}
+        }
+
+        match atom {
+            Atom::Punct(OpenBracket | OpenCurly) => depth += 1,
+            Atom::Punct(CloseBracket | CloseCurly) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+
+        newlines = 0;
+        previous = Some(i);
+    }
+
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    match span::line_ending(&String::from_utf8_lossy(buffer)) {
+        "\n" => out,
+        ending => out.replace('\n', ending),
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let buffer: &[u8] = b"
+
+
+// comment
+a=1  // trailing
+b  =  [1 ,2,   'x  y' ]
+
+
+
+c={
+  .one:true
+        .two  :  {
+ .three: [
+'a',
+   'b' ,
+]
+}
+}
+d :: e::f='\\'' /* block */
+";
+
+        let expected = "\
+// comment
+a = 1 // trailing
+b = [1, 2, 'x  y']
+
+c = {
+    .one: true
+    .two: {
+        .three: [
+            'a',
+            'b',
+        ]
+    }
+}
+d::e::f = '\\'' /* block */
+";
+
+        assert_eq!(format(buffer), expected);
+        assert_eq!(format(expected.as_bytes()), expected);
+
+        assert_eq!(format(b"a = '\nx \t'"), "a = '\nx \t'\n");
+        assert_eq!(format(b"a = r'\n  x \t'  ,"), "a = r'\n  x \t',\n");
+
+        for source in [buffer, expected.as_bytes(), b"a = nan\nb = [-0.0, 1e3, { .c: '\n' }]", b"'k l'::m = { .'n o': r'p' }"] {
+            assert!(crate::Kfg::format(source).is_ok());
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{Ast, Node};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    InvalidLine(usize, String),
+    UnclosedSection(usize),
+    UnclosedQuote(usize),
+    EmptyKey(usize),
+    KeyConflict(usize, String),
+}
+
+impl std::error::Error for ImportError {}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Returns the dict at `path`, creating every missing level on the way
+fn dict_at<'a>(
+    root: &'a mut HashMap<String, Node>,
+    path: &[String],
+    line: usize,
+) -> Result<&'a mut HashMap<String, Node>, ImportError> {
+    let mut dict = root;
+
+    for key in path {
+        let node = dict
+            .entry(key.clone())
+            .or_insert_with(|| Node::Dict(HashMap::new()));
+
+        match node {
+            Node::Dict(inner) => dict = inner,
+            _ => return Err(ImportError::KeyConflict(line, key.clone())),
+        }
+    }
+
+    Ok(dict)
+}
+
+/// Removes a trailing `;` or `#` comment that is preceded by whitespace
+fn strip_inline_comment(value: &str) -> &str {
+    let bytes = value.as_bytes();
+
+    for i in 1..bytes.len() {
+        if matches!(bytes[i], b';' | b'#') && bytes[i - 1].is_ascii_whitespace() {
+            return value[..i].trim_end();
+        }
+    }
+
+    value
+}
+
+/// Splits a quoted value into its content, if `value` starts with a quote
+fn unquote(value: &str, line: usize) -> Result<Option<String>, ImportError> {
+    let Some(quote) = value.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+        return Ok(None);
+    };
+
+    match value[1..].find(quote) {
+        Some(end) => Ok(Some(value[1..end + 1].to_string())),
+        None => Err(ImportError::UnclosedQuote(line)),
+    }
+}
+
+/// Imports an INI file, turning `[section.sub]` headers into nested dicts
+///
+/// Keys written before any section go to the top level, `;` and `#` start comments
+pub fn ini(buffer: &[u8]) -> Result<Ast, ImportError> {
+    let content = String::from_utf8_lossy(buffer);
+
+    let mut ast = Ast::default();
+    let mut section: Vec<String> = vec![];
+
+    for (i, line) in content.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let Some(header) = strip_inline_comment(header).strip_suffix(']') else {
+                return Err(ImportError::UnclosedSection(number));
+            };
+
+            section = header.split('.').map(|s| s.trim().to_string()).collect();
+
+            if section.iter().any(String::is_empty) {
+                return Err(ImportError::EmptyKey(number));
+            }
+
+            dict_at(&mut ast.0, &section, number)?;
+
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(ImportError::InvalidLine(number, line.into()));
+        };
+
+        let key = key.trim();
+
+        if key.is_empty() {
+            return Err(ImportError::EmptyKey(number));
+        }
+
+        let value = value.trim();
+
+        let node = match unquote(value, number)? {
+            Some(string) => Node::String(string),
+            None => Node::typed(strip_inline_comment(value)),
+        };
+
+        trace!("\x1b[32m+\x1b[m {}::{key} = {}", section.join("::"), node.inline());
+
+        dict_at(&mut ast.0, &section, number)?.insert(key.into(), node);
+    }
+
+    debug!("\x1b[1;33m*\x1b[39m Assignments: \x1b[36m{}\x1b[m", ast.assignments());
+
+    Ok(ast)
+}
+
+/// Imports a `.env` file into top-level keys
+///
+/// Supports the `export` prefix, `#` comments, single quotes (taken literally)
+/// and double quotes (with `\n`, `\r`, `\t`, `\"` and `\\` escapes, spanning lines)
+pub fn dotenv(buffer: &[u8]) -> Result<Ast, ImportError> {
+    let content = String::from_utf8_lossy(buffer);
+
+    let mut ast = Ast::default();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((i, line)) = lines.next() {
+        let number = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(ImportError::InvalidLine(number, line.into()));
+        };
+
+        let key = key.trim();
+
+        if key.is_empty() {
+            return Err(ImportError::EmptyKey(number));
+        }
+
+        let value = value.trim_start();
+
+        let node = if let Some(value) = value.strip_prefix('"') {
+            let mut string = String::new();
+            let mut rest = value.to_string();
+
+            'outer: loop {
+                let mut chars = rest.chars();
+
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break 'outer,
+                        '\\' => match chars.next() {
+                            Some('n') => string.push('\n'),
+                            Some('r') => string.push('\r'),
+                            Some('t') => string.push('\t'),
+                            Some(c) => string.push(c),
+                            None => string.push('\\'),
+                        },
+                        c => string.push(c),
+                    }
+                }
+
+                match lines.next() {
+                    Some((_, line)) => {
+                        string.push('\n');
+                        rest = line.to_string();
+                    }
+                    None => return Err(ImportError::UnclosedQuote(number)),
+                }
+            }
+
+            Node::String(string)
+        } else if value.starts_with('\'') {
+            Node::String(unquote(value, number)?.unwrap_or_default())
+        } else {
+            Node::typed(strip_inline_comment(value))
+        };
+
+        trace!("\x1b[32m+\x1b[m {key} = {}", node.inline());
+
+        ast.0.insert(key.into(), node);
+    }
+
+    debug!("\x1b[1;33m*\x1b[39m Assignments: \x1b[36m{}\x1b[m", ast.assignments());
+
+    Ok(ast)
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ini() {
+        let buffer: &[u8] = b"
+            ; comment
+            debug = true
+
+            [server]
+            host = localhost ; inline comment
+            port = 8080
+
+            [server.tls]
+            enabled = false
+            cert = '/etc/cert.pem'
+            version = \"1.3\"
+        ";
+
+        let expected = HashMap::<String, _>::from([
+            ("debug".into(), Node::Bool(true)),
+            ("server".into(), Node::Dict(HashMap::from([
+                ("host".into(), Node::String("localhost".into())),
+                ("port".into(), Node::Integer(8080)),
+                ("tls".into(), Node::Dict(HashMap::from([
+                    ("enabled".into(), Node::Bool(false)),
+                    ("cert".into(), Node::String("/etc/cert.pem".into())),
+                    ("version".into(), Node::String("1.3".into())),
+                ]))),
+            ]))),
+        ]);
+
+        assert_eq!(ini(buffer).unwrap().0, expected);
+
+        assert_eq!(ini(b"[server").unwrap_err(), ImportError::UnclosedSection(1));
+        assert_eq!(ini(b"a = 1\n[a.b]").unwrap_err(), ImportError::KeyConflict(2, "a".into()));
+        assert_eq!(ini(b"nothing").unwrap_err(), ImportError::InvalidLine(1, "nothing".into()));
+    }
+
+    #[test]
+    fn test_dotenv() {
+        let buffer: &[u8] = b"
+            # comment
+            export DEBUG=true
+            PORT=8080
+            RATIO=0.5
+            HOST=localhost # inline comment
+            EMPTY=
+            LITERAL='a\\nb'
+            ESCAPED=\"a\\nb \\\"c\\\"\"
+            MULTI=\"first
+            second\"
+            NULL=null
+        ";
+
+        let expected = HashMap::<String, _>::from([
+            ("DEBUG".into(), Node::Bool(true)),
+            ("PORT".into(), Node::Integer(8080)),
+            ("RATIO".into(), Node::Float(0.5)),
+            ("HOST".into(), Node::String("localhost".into())),
+            ("EMPTY".into(), Node::String("".into())),
+            ("LITERAL".into(), Node::String("a\\nb".into())),
+            ("ESCAPED".into(), Node::String("a\nb \"c\"".into())),
+            ("MULTI".into(), Node::String("first\n            second".into())),
+            ("NULL".into(), Node::Null),
+        ]);
+
+        assert_eq!(dotenv(buffer).unwrap().0, expected);
+
+        assert_eq!(dotenv(b"KEY=\"open").unwrap_err(), ImportError::UnclosedQuote(1));
+        assert_eq!(dotenv(b"=value").unwrap_err(), ImportError::EmptyKey(1));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{
+    doc,
+    edit::Span,
+    lexer,
+    parser::{self, Assignment},
+    span,
+    Ast, Mode, ParseOptions, ParserError, Warning,
+};
+use std::collections::HashSet;
+
+/// A run of whole lines that parses on its own, the parser has nothing pending at its start
+///
+/// Only the last chunk can be without assignments, so doc comments stay with the key below them
+#[derive(Debug, Clone)]
+struct Chunk {
+    len: usize,
+    lines: usize,
+    assignments: Vec<Assignment>,
+    /// The documented entries starting in the chunk, by path
+    docs: Vec<(Vec<String>, String)>,
+    warnings: Vec<Warning>,
+    /// Only the last chunk can have one, it runs to the end of the source
+    error: Option<ParserError>,
+}
+
+/// A parse that can be updated by edits to its source
+///
+/// The source is kept in chunks of lines, an edit re-lexes and re-parses only the chunks
+/// it touches, growing the region until it ends where a full parse would start over.
+/// Only the top-level keys those chunks write are built again, so the result is always the
+/// one [`Kfg::parse_with`](crate::Kfg::parse_with) gives for the whole source
+///
+/// Strict mode and the limits on keys and nodes count over the whole source, with them
+/// every edit parses all of it again
+#[derive(Debug, Clone)]
+pub struct Parse {
+    source: String,
+    options: ParseOptions,
+    chunks: Vec<Chunk>,
+    ast: Ast,
+    error: Option<ParserError>,
+}
+
+impl Parse {
+    pub fn new(source: String, options: &ParseOptions) -> Self {
+        let (chunks, _) = chunks(&source, 0, 1, options);
+
+        let mut parse = Self {
+            source,
+            options: options.clone(),
+            chunks,
+            ast: Ast::default(),
+            error: None,
+        };
+
+        let mut keys = parse.chunks.iter().flat_map(|chunk| &chunk.assignments).map(|assignment| top(assignment).to_owned()).collect();
+
+        parse.document(0..parse.chunks.len(), 0, &mut keys);
+        parse.build(&keys);
+        parse
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The same as a full parse of the current source
+    pub fn ast(&self) -> Result<&Ast, &ParserError> {
+        match &self.error {
+            Some(error) => Err(error),
+            None => Ok(&self.ast),
+        }
+    }
+
+    /// The warnings of a lenient parse, in the order they're written
+    pub fn warnings(&self) -> impl Iterator<Item = &Warning> {
+        self.chunks.iter().flat_map(|chunk| &chunk.warnings)
+    }
+
+    /// Whether the options need the whole source to parse any of it
+    fn whole(&self) -> bool {
+        let options = &self.options;
+        options.mode == Mode::Strict || options.max_keys.is_some() || options.max_nodes.is_some()
+    }
+
+    /// Replaces the bytes in `span` with `text`, returning the part of the new source that was parsed again
+    ///
+    /// Panics if `span` isn't on char boundaries of the source, like [`String::replace_range`]
+    pub fn edit(&mut self, span: Span, text: &str) -> Span {
+        self.source.replace_range(span.start..span.end, text);
+
+        let delta = text.len() as isize - (span.end - span.start) as isize;
+
+        // the chunks touching the edit, the one ending where it starts is taken too
+        let mut first = None;
+        let mut last = 0;
+        let mut start = 0;
+        let mut old_end = 0;
+        let mut line = 1;
+
+        let mut offset = 0;
+        let mut lines = 1;
+
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            let end = offset + chunk.len;
+
+            if first.is_none() && end >= span.start {
+                first = Some(i);
+                start = offset;
+                line = lines;
+            }
+
+            if offset <= span.end || chunk.error.is_some() {
+                last = i;
+                old_end = end;
+            }
+
+            offset = end;
+            lines += chunk.lines;
+        }
+
+        let Some(first) = first.filter(|_| !self.whole()) else {
+            *self = Self::new(std::mem::take(&mut self.source), &self.options);
+            return Span { start: 0, end: self.source.len() };
+        };
+
+        let mut end = (old_end as isize + delta) as usize;
+
+        let (count, removed) = loop {
+            let (chunks, clean) = chunks(&self.source[start..end], start, line, &self.options);
+
+            // a `\r` the edit left before the next chunk's `\n` makes them one line break
+            let split = self.source[..end].ends_with('\r') && self.source[end..].starts_with('\n');
+            let documents = chunks.last().is_none_or(|chunk| !chunk.assignments.is_empty());
+
+            if (clean && !split && documents) || end == self.source.len() {
+                let count = chunks.len();
+                break (count, self.chunks.splice(first..=last, chunks).collect::<Vec<_>>());
+            }
+
+            last += 1;
+            end += self.chunks[last].len;
+        };
+
+        // the warnings after the region moved with the edit
+        let lines = self.chunks[first..first + count].iter().map(|chunk| chunk.lines as isize).sum::<isize>()
+            - removed.iter().map(|chunk| chunk.lines as isize).sum::<isize>();
+
+        for warning in self.chunks[first + count..].iter_mut().flat_map(|chunk| &mut chunk.warnings) {
+            let position = &mut warning.token_mut().position;
+
+            position.line = (position.line as isize + lines) as usize;
+            position.offset = (position.offset as isize + delta) as usize;
+            position.end = (position.end as isize + delta) as usize;
+        }
+
+        let mut keys = removed
+            .iter()
+            .chain(&self.chunks[first..first + count])
+            .flat_map(|chunk| &chunk.assignments)
+            .map(|assignment| top(assignment).to_owned())
+            .collect();
+
+        // the chunk after can take its first doc from the last line of the region
+        let after = (first + count + 1).min(self.chunks.len());
+
+        self.document(first..after, start, &mut keys);
+        self.build(&keys);
+
+        Span { start, end }
+    }
+
+    /// Finds the docs of `chunks`, the first of them starting at byte `start`, adding the keys
+    /// whose docs changed to `keys`
+    ///
+    /// The chunk before each is looked at too, a `/** */` can end on the line above a key
+    fn document(&mut self, chunks: std::ops::Range<usize>, mut start: usize, keys: &mut HashSet<String>) {
+        for i in chunks {
+            let from = start - i.checked_sub(1).map_or(0, |before| self.chunks[before].len);
+            let end = start + self.chunks[i].len;
+
+            let docs = match doc::has_docs(&self.source.as_bytes()[from..end]) {
+                true => doc::written(&self.source[from..end], start - from),
+                false => vec![],
+            };
+
+            if docs != self.chunks[i].docs {
+                let old = std::mem::replace(&mut self.chunks[i].docs, docs);
+                keys.extend(old.iter().chain(&self.chunks[i].docs).filter_map(|(path, _)| path.first().cloned()));
+            }
+
+            start = end;
+        }
+    }
+
+    /// Builds the values and docs of top-level `keys` again from every chunk writing them,
+    /// the rest of the tree is kept
+    fn build(&mut self, keys: &HashSet<String>) {
+        let ast = &mut self.ast;
+
+        ast.0.retain(|key, _| !keys.contains(key));
+        ast.1.docs.retain(|path, _| !path.split("::").next().is_some_and(|key| keys.contains(key)));
+
+        for assignment in self.chunks.iter().flat_map(|chunk| &chunk.assignments) {
+            if keys.contains(top(assignment)) {
+                assignment.clone().apply(ast);
+            }
+        }
+
+        for (path, doc) in self.chunks.iter().flat_map(|chunk| &chunk.docs) {
+            if !path.first().is_some_and(|key| keys.contains(key)) {
+                continue;
+            }
+
+            let path = path.join("::");
+
+            if ast.lookup(&path).is_some() {
+                ast.1.docs.insert(path, doc.clone());
+            }
+        }
+
+        self.error = parser::check_input(self.source.as_bytes(), &self.options)
+            .err()
+            .or_else(|| self.chunks.last().and_then(|chunk| chunk.error.clone()));
+    }
+}
+
+/// The top-level key an assignment writes into
+fn top(assignment: &Assignment) -> &str {
+    assignment.scopes.first().unwrap_or(&assignment.key)
+}
+
+/// Moves a token of a chunk to where the chunk is in the whole source
+fn shift(token: &mut crate::Token, offset: usize, line: usize) {
+    token.position.line += line - 1;
+    token.position.offset += offset;
+    token.position.end += offset;
+}
+
+/// How many line breaks a source has
+fn lines(source: &str) -> usize {
+    span::line_starts(source).len() - 1
+}
+
+/// Parses whole lines starting at byte `offset` and `line` of the whole source, also telling
+/// whether the parser has nothing pending at the end of them
+fn chunks(source: &str, offset: usize, line: usize, options: &ParseOptions) -> (Vec<Chunk>, bool) {
+    let mut tokens = match lexer::lex_with(source.as_bytes(), options.mode) {
+        Ok(tokens) => tokens,
+        // either the region runs on into the rest of the source or the source has the error
+        Err(mut error) => {
+            shift(error.token_mut(), offset, line);
+
+            let chunk = Chunk {
+                len: source.len(),
+                lines: lines(source),
+                assignments: vec![],
+                docs: vec![],
+                warnings: vec![],
+                error: Some(ParserError::Lexer(error)),
+            };
+
+            return (vec![chunk], false);
+        }
+    };
+
+    for token in &mut tokens {
+        shift(token, offset, line);
+    }
+
+    let starts = span::line_starts(source);
+
+    let mut assignments = vec![];
+    let mut clean = vec![];
+    let mut context = parser::Context::new(options);
+
+    let result = parser::assignments(&tokens, &mut context, &mut assignments, |count, at| {
+        clean.push((count, starts[at - line]));
+    });
+
+    // each chunk takes the warnings of the tokens in it
+    let mut warnings = context.warnings.into_iter().peekable();
+    let mut warned = |end: usize| std::iter::from_fn(|| warnings.next_if(|warning| warning.token().position.offset < offset + end)).collect();
+
+    let ends_clean = source.is_empty() || clean.last().is_some_and(|&(_, at)| at == source.len());
+
+    let mut chunks = vec![];
+    let mut assignments = assignments.into_iter();
+
+    let mut from = 0;
+    let mut taken = 0;
+
+    for (count, at) in clean {
+        if count > taken && at < source.len() {
+            chunks.push(Chunk {
+                len: at - from,
+                lines: lines(&source[from..at]),
+                assignments: assignments.by_ref().take(count - taken).collect(),
+                docs: vec![],
+                warnings: warned(at),
+                error: None,
+            });
+
+            from = at;
+            taken = count;
+        }
+    }
+
+    if from < source.len() {
+        chunks.push(Chunk {
+            len: source.len() - from,
+            lines: lines(&source[from..]),
+            assignments: assignments.collect(),
+            docs: vec![],
+            warnings: warned(source.len()),
+            error: result.as_ref().err().cloned(),
+        });
+    } else if let Err(error) = &result {
+        chunks.push(Chunk {
+            len: 0,
+            lines: 0,
+            assignments: vec![],
+            docs: vec![],
+            warnings: vec![],
+            error: Some(error.clone()),
+        });
+    }
+
+    (chunks, result.is_ok() && ends_clean)
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Kfg;
+
+    fn check(parse: &Parse) {
+        match (parse.ast(), Kfg::parse_with(parse.source().as_bytes(), &parse.options)) {
+            (Ok(ast), Ok((full, warnings))) => {
+                assert_eq!((&ast.0, &ast.1.docs), (&full.0, &full.1.docs), "{:?}", parse.source());
+                assert_eq!(format!("{:?}", parse.warnings().collect::<Vec<_>>()), format!("{:?}", warnings.iter().collect::<Vec<_>>()));
+            }
+            (Err(error), Err(full)) => assert_eq!(format!("{error:?}"), format!("{full:?}"), "{:?}", parse.source()),
+            (ast, full) => panic!("{:?}: {ast:?} != {full:?}", parse.source()),
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let source = "port = 80\nserver::host = 'a'\nserver::tls::\n  enabled = true\nlist = [\n  1, 2\n]\n// comment\ndict = { .k: 'v' }\n\nlast = 2.5";
+
+        let mut parse = Parse::new(source.into(), &Default::default());
+        check(&parse);
+
+        let at = parse.source().find("80").unwrap();
+        let reparsed = parse.edit(Span { start: at, end: at + 2 }, "8080");
+
+        assert_eq!(reparsed, Span { start: 0, end: 12 });
+        assert_eq!(parse.ast().unwrap().lookup("port"), Some(&crate::Node::Integer(8080)));
+
+        // opening a bracket takes the rest of the source with it
+        let at = parse.source().find("'a'").unwrap();
+        let reparsed = parse.edit(Span { start: at, end: at + 3 }, "[");
+
+        assert_eq!(reparsed.end, parse.source().len());
+        check(&parse);
+
+        // a block ending on the line above documents the key in the next chunk
+        let documented = "/// p\nport = 80 /** s */\nserver::host = 1\n/** t */ x = {\n  /// k\n  .k: 1 }\n\n/// l\nlast = 2";
+
+        let mut parse = Parse::new(documented.into(), &Default::default());
+        assert_eq!(parse.ast().unwrap().doc("server::host"), Some("s"));
+
+        let at = parse.source().find("80").unwrap();
+        let reparsed = parse.edit(Span { start: at, end: at + 11 }, "1 /** u */");
+
+        assert_eq!(reparsed, Span { start: 0, end: 24 });
+        assert_eq!(parse.ast().unwrap().doc("server::host"), Some("u"));
+        check(&parse);
+
+        let fragments = [
+            "a", "b", "key", " = ", "=", "1", "2.5", "true", "null", "'x'", "'", "\n", " ", ",",
+            "[", "]", "{", "}", ".k: ", ".", ":", "::", "scope::", "// c\n", "x = 1\n", "/*", "*/", "\\", "/// d\n",
+            "\r", "\r\n", "y = 2\r", "'''", "r'", "'k b' = ", ".'k': ", ".k::j: ", "/** e */ ",
+        ];
+
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut random = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+
+        let limited = ParseOptions {
+            max_input: Some(150),
+            max_depth: 2,
+            max_string: Some(4),
+            max_array: Some(2),
+            max_keys: Some(8),
+            max_nodes: Some(12),
+            ..ParseOptions::lenient()
+        };
+
+        let options = [ParseOptions::default(), ParseOptions::strict(), ParseOptions::lenient(), limited];
+
+        for round in 0..400 {
+            let source = if round % 2 == 0 { source } else { documented };
+            let mut parse = Parse::new(source.into(), &options[round / 2 % options.len()]);
+
+            for _ in 0..20 {
+                let len = parse.source().len();
+                let start = random(len + 1);
+                let end = (start + random(4)).min(len);
+                let text = if random(3) == 0 { "" } else { fragments[random(fragments.len())] };
+
+                parse.edit(Span { start, end }, text);
+                check(&parse);
+            }
+
+            // undoing back to a valid source keeps agreeing too
+            if round % 40 < 8 {
+                let len = parse.source().len();
+                parse.edit(Span { start: 0, end: len }, source);
+                check(&parse);
+            }
+        }
+    }
+}
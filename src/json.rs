@@ -0,0 +1,414 @@
+use crate::{
+    convert::{self, SyntaxError, Warning},
+    Ast, Node,
+};
+use std::collections::HashMap;
+
+const INDENT: &str = "  ";
+
+/// Quotes and escapes `value` as a JSON string
+pub fn string(value: &str) -> String {
+    let mut string = String::from('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => string.push_str("\\\""),
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            '\r' => string.push_str("\\r"),
+            '\t' => string.push_str("\\t"),
+            c if c.is_control() => string.push_str(&format!("\\u{:04x}", c as u32)),
+            c => string.push(c),
+        }
+    }
+
+    string.push('"');
+    string
+}
+
+fn write(node: &Node, depth: Option<usize>, path: &mut Vec<String>, warnings: &mut Vec<Warning>) -> String {
+    let (open, separator, close) = match depth {
+        Some(depth) => (
+            format!("\n{}", INDENT.repeat(depth + 1)),
+            format!(",\n{}", INDENT.repeat(depth + 1)),
+            format!("\n{}", INDENT.repeat(depth)),
+        ),
+        None => (String::new(), ", ".into(), String::new()),
+    };
+
+    let depth = depth.map(|depth| depth + 1);
+
+    match node {
+        Node::String(value) => string(value),
+        Node::Integer(value) => value.to_string(),
+        Node::Float(value) if value.is_finite() => format!("{value:?}"),
+        Node::Float(value) => {
+            warnings.push(Warning::new(path, format!("json has no {value}, it was written as null")));
+            "null".into()
+        }
+        Node::Null => "null".into(),
+        Node::Bool(value) => value.to_string(),
+        Node::Array(array) if array.is_empty() => "[]".into(),
+        Node::Array(array) => {
+            let array = array
+                .iter()
+                .enumerate()
+                .map(|(i, node)| {
+                    path.push(i.to_string());
+                    let value = write(node, depth, path, warnings);
+                    path.pop();
+                    value
+                })
+                .collect::<Vec<_>>();
+
+            format!("[{open}{}{close}]", array.join(&separator))
+        }
+        Node::Dict(dict) if dict.is_empty() => "{}".into(),
+        Node::Dict(dict) => {
+            let mut entries = dict.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+
+            let entries = entries
+                .into_iter()
+                .map(|(key, node)| {
+                    path.push(key.clone());
+                    let value = write(node, depth, path, warnings);
+                    path.pop();
+                    format!("{}: {value}", string(key))
+                })
+                .collect::<Vec<_>>();
+
+            format!("{{{open}{}{close}}}", entries.join(&separator))
+        }
+    }
+}
+
+/// Writes the tree as an indented JSON object, pushing every float JSON can't hold into `warnings`
+pub(crate) fn pretty(ast: &Ast, warnings: &mut Vec<Warning>) -> String {
+    format!("{}\n", write(&Node::Dict(ast.0.clone()), Some(0), &mut vec![], warnings))
+}
+
+impl Node {
+    /// Writes the node as JSON, floats that aren't finite become `null`
+    pub fn to_json(&self) -> String {
+        write(self, None, &mut vec![], &mut vec![])
+    }
+}
+
+impl Ast {
+    pub fn to_json(&self) -> String {
+        write(&Node::Dict(self.0.clone()), None, &mut vec![], &mut vec![])
+    }
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    index: usize,
+    path: Vec<String>,
+    warnings: &'a mut Vec<Warning>,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError::at(self.source, self.index, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.index).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.index += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), SyntaxError> {
+        self.whitespace();
+
+        if self.peek() != Some(byte) {
+            return Err(self.error(format!("expected '{}'", byte as char)));
+        }
+
+        self.index += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Node, SyntaxError> {
+        self.whitespace();
+
+        match self.peek() {
+            Some(b'{' | b'[') if convert::too_deep(self.path.len()) => Err(self.error("too deeply nested")),
+            Some(b'{') => self.object().map(Node::Dict),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Node::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => {
+                for (word, node) in [("true", Node::Bool(true)), ("false", Node::Bool(false)), ("null", Node::Null)] {
+                    if self.source[self.index..].starts_with(word) {
+                        self.index += word.len();
+                        return Ok(node);
+                    }
+                }
+
+                Err(self.error("expected a value"))
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<HashMap<String, Node>, SyntaxError> {
+        let mut dict = HashMap::new();
+
+        self.expect(b'{')?;
+        self.whitespace();
+
+        if self.peek() == Some(b'}') {
+            self.index += 1;
+            return Ok(dict);
+        }
+
+        loop {
+            self.whitespace();
+
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a key"));
+            }
+
+            let key = self.string()?;
+
+            self.expect(b':')?;
+
+            self.path.push(key.clone());
+            let node = self.value()?;
+            self.path.pop();
+
+            dict.insert(key, node);
+
+            self.whitespace();
+
+            match self.peek() {
+                Some(b',') => self.index += 1,
+                Some(b'}') => {
+                    self.index += 1;
+                    return Ok(dict);
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Node, SyntaxError> {
+        let mut array = vec![];
+
+        self.expect(b'[')?;
+        self.whitespace();
+
+        if self.peek() == Some(b']') {
+            self.index += 1;
+            return Ok(Node::Array(array));
+        }
+
+        loop {
+            self.path.push(array.len().to_string());
+            array.push(self.value()?);
+            self.path.pop();
+
+            self.whitespace();
+
+            match self.peek() {
+                Some(b',') => self.index += 1,
+                Some(b']') => {
+                    self.index += 1;
+                    return Ok(Node::Array(array));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, SyntaxError> {
+        let digits = self.source.get(self.index..self.index + 4).ok_or_else(|| self.error("expected 4 hex digits"))?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| self.error("expected 4 hex digits"))?;
+
+        self.index += 4;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        let mut string = String::new();
+
+        // skip opening quote
+        self.index += 1;
+
+        loop {
+            let Some(c) = self.source[self.index..].chars().next() else {
+                return Err(self.error("unclosed string"));
+            };
+
+            self.index += c.len_utf8();
+
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escape = self.source[self.index..].chars().next().ok_or_else(|| self.error("unclosed string"))?;
+                    self.index += escape.len_utf8();
+
+                    match escape {
+                        '"' => string.push('"'),
+                        '\\' => string.push('\\'),
+                        '/' => string.push('/'),
+                        'b' => string.push('\u{8}'),
+                        'f' => string.push('\u{c}'),
+                        'n' => string.push('\n'),
+                        'r' => string.push('\r'),
+                        't' => string.push('\t'),
+                        'u' => {
+                            let mut code = self.hex()?;
+
+                            if (0xD800..0xDC00).contains(&code) && self.source[self.index..].starts_with("\\u") {
+                                self.index += 2;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (self.hex()?.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+
+                            string.push(char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))?);
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                c if c.is_control() => return Err(self.error("control character in string")),
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Node, SyntaxError> {
+        let start = self.index;
+
+        while matches!(self.peek(), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.index += 1;
+        }
+
+        let number = &self.source[start..self.index];
+
+        if !number.contains(['.', 'e', 'E']) {
+            if let Ok(value) = number.parse::<i64>() {
+                return Ok(Node::Integer(value));
+            }
+        }
+
+        let value = number.parse::<f64>().map_err(|_| SyntaxError::at(self.source, start, "invalid number"))?;
+
+        if !number.contains(['.', 'e', 'E']) {
+            self.warnings.push(Warning::new(&self.path, "integer doesn't fit in 64 bits, it was read as a float"));
+        } else if value.is_infinite() {
+            self.warnings.push(Warning::new(&self.path, "number is too large for a float, it was read as infinity"));
+        } else if digits(number) != digits(&format!("{value:e}")) {
+            self.warnings.push(Warning::new(&self.path, "float can't hold the number exactly, it was rounded"));
+        }
+
+        Ok(Node::Float(value))
+    }
+}
+
+/// The sign, significant digits and exponent of a decimal number, so numbers
+/// written differently compare equal, `None` if the exponent is out of range
+fn digits(number: &str) -> Option<(bool, String, i64)> {
+    let (negative, number) = match number.strip_prefix('-') {
+        Some(number) => (true, number),
+        None => (false, number),
+    };
+
+    let (mantissa, exponent) = number.split_once(['e', 'E']).unwrap_or((number, "0"));
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let mut exponent = exponent.parse::<i64>().ok()?.checked_sub(fraction.len() as i64)?;
+    let digits = format!("{whole}{fraction}");
+    let digits = digits.trim_start_matches('0');
+
+    exponent += (digits.len() - digits.trim_end_matches('0').len()) as i64;
+
+    match digits.trim_end_matches('0') {
+        "" => Some((negative, String::new(), 0)),
+        digits => Some((negative, digits.into(), exponent)),
+    }
+}
+
+/// Reads a JSON document whose top level is an object
+pub(crate) fn read(buffer: &[u8], warnings: &mut Vec<Warning>) -> Result<Ast, SyntaxError> {
+    let source = String::from_utf8_lossy(buffer);
+
+    let mut reader = Reader {
+        source: &source,
+        index: 0,
+        path: vec![],
+        warnings,
+    };
+
+    reader.whitespace();
+
+    if reader.peek() != Some(b'{') {
+        return Err(reader.error("the top level must be an object"));
+    }
+
+    let dict = reader.object()?;
+
+    reader.whitespace();
+
+    if reader.peek().is_some() {
+        return Err(reader.error("unexpected trailing characters"));
+    }
+
+    Ok(Ast(dict, Default::default()))
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Kfg;
+
+    #[test]
+    fn test_write() {
+        let ast = Kfg::parse(b"
+            b = { .list: [1, 2.0, 'say \\'hi\\'\\n'] .none: null }
+            a = []
+        ").unwrap();
+
+        assert_eq!(ast.to_json(), r#"{"a": [], "b": {"list": [1, 2.0, "say 'hi'\n"], "none": null}}"#);
+    }
+
+    #[test]
+    fn test_read() {
+        let buffer = br#" {"a": [1, -2.5e1, "\u00e9\ud83d\ude00\"", true, null, {}], "b": {"c": false}} "#;
+
+        let expected = HashMap::<String, _>::from([
+            ("a".into(), Node::Array(vec![
+                Node::Integer(1),
+                Node::Float(-25.0),
+                Node::String("\u{e9}\u{1f600}\"".into()),
+                Node::Bool(true),
+                Node::Null,
+                Node::Dict(HashMap::new()),
+            ])),
+            ("b".into(), Node::Dict(HashMap::from([("c".into(), Node::Bool(false))]))),
+        ]);
+
+        assert_eq!(read(buffer, &mut vec![]).unwrap().0, expected);
+
+        assert_eq!(read(b"[]", &mut vec![]).unwrap_err().message, "the top level must be an object");
+        assert_eq!(read(b"{\n  \"a\": }", &mut vec![]).unwrap_err(), SyntaxError { line: 2, column: 8, message: "expected a value".into() });
+        assert_eq!(read("{\"s\": \"\\\u{e9}\"}".as_bytes(), &mut vec![]).unwrap_err(), SyntaxError { line: 1, column: 10, message: "invalid escape".into() });
+
+        let mut warnings = vec![];
+        read(br#"{"a": [0.1, 1e2, -0.0, 2.50, 0.10000000000000000001], "b": 1e-400}"#, &mut warnings).unwrap();
+
+        assert_eq!(warnings, [
+            Warning::new(&["a".into(), "4".into()], "float can't hold the number exactly, it was rounded"),
+            Warning::new(&["b".into()], "float can't hold the number exactly, it was rounded"),
+        ]);
+
+        let deep = format!("{{\"a\": {}", "[".repeat(20_000));
+        assert_eq!(read(deep.as_bytes(), &mut vec![]).unwrap_err().message, "too deeply nested");
+    }
+}
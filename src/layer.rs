@@ -0,0 +1,263 @@
+use crate::{export, Ast, Node, SourceSpan};
+use std::{collections::HashMap, path::PathBuf};
+
+/// What put a value into a layered [`Ast`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Built into the program
+    Default,
+    File(PathBuf),
+    /// An environment variable, by name
+    Env(String),
+    /// A `key::path=value` given on the command line
+    Override,
+    /// Written by the program itself
+    Set,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(name) => write!(f, "env {name}"),
+            Source::Override => write!(f, "command line"),
+            Source::Set => write!(f, "set by the program"),
+        }
+    }
+}
+
+/// Why [`Layers::set`] couldn't write a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayerError {
+    /// An array, by path, has no element at the index
+    MissingIndex(String, usize),
+}
+
+impl std::error::Error for LayerError {}
+
+impl std::fmt::Display for LayerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// One value written at a path, whether or not a later layer replaced it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub source: Source,
+    /// Where a file wrote it, if the file was parsed with [`Kfg::parse_spanned`](crate::Kfg::parse_spanned)
+    pub span: Option<SourceSpan>,
+    pub value: Node,
+}
+
+/// Builds an [`Ast`] out of layers, each one taking precedence over the ones added before it
+///
+/// Dicts merge key by key and anything else is replaced whole. Every value written is
+/// remembered, see [`Ast::provenance`]
+#[derive(Debug, Default, Clone)]
+pub struct Layers {
+    ast: Ast,
+}
+
+impl Layers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ast(&self) -> &Ast {
+        &self.ast
+    }
+
+    pub fn into_ast(self) -> Ast {
+        self.ast
+    }
+
+    /// Puts a whole tree over the current one, spans are taken from it when it has them
+    pub fn add(&mut self, source: Source, layer: &Ast) {
+        let Ast(dict, sources) = &mut self.ast;
+
+        overlay(dict, &layer.0, &mut vec![], &mut |path, node| {
+            record(&mut sources.origins, &source, Some(layer), path, node);
+        });
+    }
+
+    /// Overrides leaves with the environment variables [`Ast::apply_to_command`] would set for them
+    ///
+    /// Values are typed like kfg literals, `vars` is usually [`std::env::vars`]
+    pub fn env(&mut self, prefix: &str, vars: impl IntoIterator<Item = (String, String)>) {
+        let vars = vars.into_iter().collect::<HashMap<_, _>>();
+
+        let paths = self.ast.leaves().into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+
+        for path in paths {
+            let name = export::env_name(&path, prefix);
+
+            if let Some(value) = vars.get(&name) {
+                // leaves are there to write over, so no index can be missing
+                let _ = self.write(Source::Env(name), &path, Node::typed(value));
+            }
+        }
+    }
+
+    /// Writes a single value, making dicts on the way like a scoped assignment does
+    ///
+    /// Numeric segments index into arrays, it's an error when the element doesn't exist
+    pub fn set(&mut self, source: Source, path: &str, value: Node) -> Result<(), LayerError> {
+        let path = path.split("::").map(String::from).collect::<Vec<_>>();
+        self.write(source, &path, value)
+    }
+
+    fn write(&mut self, source: Source, path: &[String], value: Node) -> Result<(), LayerError> {
+        let Ast(dict, sources) = &mut self.ast;
+        let mut root = Node::Dict(std::mem::take(dict));
+
+        let result = insert(&mut root, path, 0, value, &mut |path, node| {
+            record(&mut sources.origins, &source, None, path, node);
+        });
+
+        let Node::Dict(root) = root else { unreachable!() };
+        *dict = root;
+
+        result
+    }
+}
+
+/// Writes `value` at `path` below `node`, which is at the first `depth` segments of it
+///
+/// Nothing is written when an index is missing, only existing nodes lead to an array
+fn insert(
+    node: &mut Node,
+    path: &[String],
+    depth: usize,
+    value: Node,
+    written: &mut impl FnMut(&[String], &Node),
+) -> Result<(), LayerError> {
+    let Some(key) = path.get(depth) else {
+        written(path, &value);
+        *node = value;
+        return Ok(());
+    };
+
+    if let (Node::Array(array), Ok(index)) = (&mut *node, key.parse::<usize>()) {
+        return match array.get_mut(index) {
+            Some(element) => insert(element, path, depth + 1, value, written),
+            None => Err(LayerError::MissingIndex(path[..depth].join("::"), index)),
+        };
+    }
+
+    if !matches!(node, Node::Dict(_)) {
+        *node = Node::Dict(HashMap::new());
+        written(&path[..depth], node);
+    }
+
+    let Node::Dict(dict) = node else { unreachable!() };
+
+    // a missing entry is either replaced by the value or turned into a dict on the way
+    insert(dict.entry(key.clone()).or_insert(Node::Null), path, depth + 1, value, written)
+}
+
+fn overlay(
+    into: &mut HashMap<String, Node>,
+    from: &HashMap<String, Node>,
+    path: &mut Vec<String>,
+    written: &mut impl FnMut(&[String], &Node),
+) {
+    for (key, node) in from {
+        path.push(key.clone());
+
+        match (into.get_mut(key), node) {
+            (Some(Node::Dict(into)), Node::Dict(from)) => overlay(into, from, path, written),
+            _ => {
+                written(path, node);
+                into.insert(key.clone(), node.clone());
+            }
+        }
+
+        path.pop();
+    }
+}
+
+/// Remembers `node` and everything in it as written by `source`
+fn record(
+    origins: &mut HashMap<String, Vec<Origin>>,
+    source: &Source,
+    layer: Option<&Ast>,
+    path: &[String],
+    node: &Node,
+) {
+    let key = path.join("::");
+
+    origins.entry(key.clone()).or_default().push(Origin {
+        source: source.clone(),
+        span: layer.and_then(|layer| layer.span_of(&key)),
+        value: node.clone(),
+    });
+
+    let mut child = |name: String, node: &Node| {
+        let path = path.iter().cloned().chain([name]).collect::<Vec<_>>();
+        record(origins, source, layer, &path, node);
+    };
+
+    match node {
+        Node::Dict(dict) => dict.iter().for_each(|(key, node)| child(key.clone(), node)),
+        Node::Array(array) => array.iter().enumerate().for_each(|(i, node)| child(i.to_string(), node)),
+        _ => {}
+    }
+}
+
+impl Ast {
+    /// Every value written at a `key::path` while layering, the one in effect first
+    ///
+    /// Empty for paths the tree doesn't have and for trees that weren't built with [`Layers`]
+    pub fn provenance(&self, path: &str) -> Vec<&Origin> {
+        if self.lookup(path).is_none() {
+            return vec![];
+        }
+
+        self.1.origins.get(path).map_or(vec![], |origins| origins.iter().rev().collect())
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::{FileId, Kfg};
+
+    #[test]
+    fn test_layers() {
+        let mut layers = Layers::new();
+
+        layers.add(Source::Default, &Kfg::parse(b"db::port = 5432\ndb::host = 'localhost'\nports = [1, 2]").unwrap());
+        layers.add(Source::File("prod.kfg".into()), &Kfg::parse_spanned(b"db = {\n  .port: 5433\n}", FileId(0)).unwrap());
+        layers.env("app", [("APP_DB_HOST".to_string(), "db.internal".to_string()), ("APP_PORTS_1".into(), "3".into())]);
+        layers.set(Source::Override, "db::pool::size", Node::Integer(4)).unwrap();
+        layers.set(Source::Set, "db::port::x", Node::Null).unwrap();
+
+        assert_eq!(layers.set(Source::Set, "ports::5", Node::Integer(5)), Err(LayerError::MissingIndex("ports".into(), 5)));
+        assert_eq!(layers.set(Source::Set, "ports::2::a", Node::Integer(5)), Err(LayerError::MissingIndex("ports".into(), 2)));
+
+        let ast = layers.into_ast();
+
+        assert_eq!(ast.lookup("db::host"), Some(&Node::String("db.internal".into())));
+        assert_eq!(ast.lookup("ports"), Some(&Node::Array(vec![Node::Integer(1), Node::Integer(3)])));
+
+        let host = ast.provenance("db::host");
+        assert_eq!(host.iter().map(|origin| origin.source.clone()).collect::<Vec<_>>(), [Source::Env("APP_DB_HOST".into()), Source::Default]);
+
+        let port = ast.provenance("db::port::x");
+        assert_eq!((port.len(), &port[0].source), (1, &Source::Set));
+
+        let port = ast.provenance("db::port");
+        let sources = port.iter().map(|origin| origin.source.to_string()).collect::<Vec<_>>();
+        assert_eq!(sources, ["set by the program", "prod.kfg", "default"]);
+        assert_eq!(port[1].value, Node::Integer(5433));
+        assert_eq!(port[1].span.map(|span| (span.start.line, span.start.column)), Some((2, 10)));
+
+        assert_eq!(ast.provenance("db::pool::size")[0].source, Source::Override);
+        assert_eq!(ast.provenance("db::pool")[0].value, Node::Dict(HashMap::new()));
+        assert!(ast.provenance("missing").is_empty());
+        assert!(Kfg::parse(b"a = 1").unwrap().provenance("a").is_empty());
+    }
+}
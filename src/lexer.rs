@@ -1,17 +1,25 @@
-use crate::{Token, TokenKind};
+use crate::{parser::Mode, Token, TokenKind};
+use std::{iter::Peekable, slice::Iter};
 
 pub fn tokenize(buffer: &[u8]) -> Vec<Token> {
     let mut tokens: Vec<Token> = vec![];
 
-    let mut iter = buffer.iter().map(|&byte| byte as char).peekable();
+    let content = String::from_utf8_lossy(buffer);
+    let mut iter = content.char_indices().peekable();
 
-    while let Some(char) = iter.next() {
+    while let Some((offset, char)) = iter.next() {
         let mut token = Token {
             kind: char.into(),
             ..Default::default()
         };
 
         token.position.length = 1;
+        token.position.offset = offset;
+
+        // `\r\n` is a single line break
+        if char == '\r' && iter.next_if(|&(_, char)| char == '\n').is_some() {
+            token.position.length = 2;
+        }
 
         match tokens.last() {
             Some(last) if matches!(last.kind, TokenKind::NewLine) => {
@@ -26,17 +34,15 @@ pub fn tokenize(buffer: &[u8]) -> Vec<Token> {
 
         if let TokenKind::Symbol(ref mut symbol) = token.kind {
             if !tokens.last().is_some_and(|token| matches!(token.kind, TokenKind::BackSlash)) {
-                while let Some(char) = iter.next() {
-                    symbol.push(char);
+                while matches!(iter.peek().map(|&(_, char)| TokenKind::from(char)), Some(TokenKind::Symbol(_))) {
+                    symbol.push(iter.next().unwrap().1);
                     token.position.length += 1;
-
-                    if !matches!(iter.peek().map(|&char| TokenKind::from(char)), Some(TokenKind::Symbol(_))) {
-                        break;
-                    }
                 }
             }
         }
 
+        token.position.end = iter.peek().map_or(content.len(), |&(end, _)| end);
+
         trace!("{token}");
 
         tokens.push(token);
@@ -47,16 +53,309 @@ pub fn tokenize(buffer: &[u8]) -> Vec<Token> {
     tokens
 }
 
-pub fn filter(tokens: &[Token]) -> Vec<Token> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexerError {
+    /// A `/*` without its `*/`, at the `/`
+    UnterminatedComment(Token),
+    /// A string without its closing quote, at the opening one
+    UnterminatedString(Token),
+    /// A `\\` with nothing after it to escape
+    InvalidEscape(Token),
+    /// A `\\` before something that isn't escaped, at the `\\`
+    UnknownEscape(Token),
+    /// A `\\x` or `\\u{}` that isn't a character, at the `\\`
+    MalformedEscape(Token),
+    /// A tab before the first thing on a line, only when strict
+    TabIndentation(Token),
+}
+
+impl LexerError {
+    /// The variant name, e.g. `UnterminatedComment`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LexerError::UnterminatedComment(_) => "UnterminatedComment",
+            LexerError::UnterminatedString(_) => "UnterminatedString",
+            LexerError::InvalidEscape(_) => "InvalidEscape",
+            LexerError::UnknownEscape(_) => "UnknownEscape",
+            LexerError::MalformedEscape(_) => "MalformedEscape",
+            LexerError::TabIndentation(_) => "TabIndentation",
+        }
+    }
+
+    /// What went wrong, in words
+    pub fn message(&self) -> &'static str {
+        match self {
+            LexerError::UnterminatedComment(_) => "comment is never closed",
+            LexerError::UnterminatedString(_) => "string is never closed",
+            LexerError::InvalidEscape(_) => "nothing to escape",
+            LexerError::UnknownEscape(_) => "unknown escape",
+            LexerError::MalformedEscape(_) => "expected \\xNN up to 7F or \\u{XXXX} naming a character",
+            LexerError::TabIndentation(_) => "tabs used for indentation",
+        }
+    }
+
+    pub fn token(&self) -> &Token {
+        match self {
+            LexerError::UnterminatedComment(token)
+            | LexerError::UnterminatedString(token)
+            | LexerError::InvalidEscape(token)
+            | LexerError::UnknownEscape(token)
+            | LexerError::MalformedEscape(token)
+            | LexerError::TabIndentation(token) => token,
+        }
+    }
+
+    pub(crate) fn token_mut(&mut self) -> &mut Token {
+        match self {
+            LexerError::UnterminatedComment(token)
+            | LexerError::UnterminatedString(token)
+            | LexerError::InvalidEscape(token)
+            | LexerError::UnknownEscape(token)
+            | LexerError::MalformedEscape(token)
+            | LexerError::TabIndentation(token) => token,
+        }
+    }
+}
+
+impl std::error::Error for LexerError {}
+
+impl std::fmt::Display for LexerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Reads the escape after `backslash` into `symbol`
+///
+/// `\\ \' \" \n \r \t \0`, `\xNN` up to `7F` and `\u{XXXX}` with one to six hex digits
+fn escape(backslash: &Token, iter: &mut Peekable<Iter<Token>>, symbol: &mut String) -> Result<(), LexerError> {
+    use TokenKind::*;
+
+    let Some(escaped) = iter.next() else {
+        return Err(LexerError::InvalidEscape(backslash.clone()));
+    };
+
+    let malformed = || LexerError::MalformedEscape(backslash.clone());
+    let hex = |digits: &str| digits.chars().all(|c| c.is_ascii_hexdigit()).then(|| u32::from_str_radix(digits, 16).ok()).flatten();
+
+    let c = match escaped.kind.as_str() {
+        "\\" => '\\',
+        "'" => '\'',
+        "\"" => '"',
+        "n" => '\n',
+        "r" => '\r',
+        "t" => '\t',
+        "0" => '\0',
+        "x" => {
+            let digits = match iter.peek().map(|&token| &token.kind) {
+                Some(Symbol(digits)) => digits,
+                _ => return Err(malformed()),
+            };
+
+            let code = digits.get(..2).and_then(hex).filter(|&code| code <= 0x7f).ok_or_else(malformed)?;
+
+            symbol.push(char::from(code as u8));
+            symbol.push_str(&digits[2..]);
+            iter.next();
+
+            return Ok(());
+        }
+        "u" => {
+            iter.next_if(|token| matches!(token.kind, OpenCurly)).ok_or_else(malformed)?;
+
+            let code = match iter.next().map(|token| &token.kind) {
+                Some(Symbol(digits)) if (1..=6).contains(&digits.len()) => hex(digits),
+                _ => None,
+            };
+
+            iter.next_if(|token| matches!(token.kind, CloseCurly)).ok_or_else(malformed)?;
+
+            code.and_then(char::from_u32).ok_or_else(malformed)?
+        }
+        _ => return Err(LexerError::UnknownEscape(backslash.clone())),
+    };
+
+    symbol.push(c);
+
+    Ok(())
+}
+
+/// Whether the next two tokens are quotes, which makes a `'''`
+pub(crate) fn triple(iter: &Peekable<Iter<Token>>) -> bool {
+    let mut ahead = iter.clone();
+    let mut quote = || ahead.next_if(|token| token.kind == TokenKind::Quote).is_some();
+
+    quote() && quote()
+}
+
+/// Drops the line break right after the opening `'''`, the line of the closing one when
+/// it's only indentation, and the indentation every other line has in common
+fn dedent(content: Vec<&Token>) -> Vec<&Token> {
+    use TokenKind::*;
+
+    let mut lines = content.split(|token| token.kind == NewLine).collect::<Vec<_>>();
+    let mut breaks = content.iter().filter(|token| token.kind == NewLine).copied().collect::<Vec<_>>();
+
+    let blank = |line: &&[&Token]| line.iter().all(|token| matches!(token.kind, Space | Tab));
+
+    if lines.len() > 1 && blank(&lines[0]) {
+        lines.remove(0);
+        breaks.remove(0);
+    }
+
+    if lines.len() > 1 && lines.last().is_some_and(blank) {
+        lines.pop();
+        breaks.pop();
+    }
+
+    let indent = lines
+        .iter()
+        .filter(|line| !blank(line))
+        .map(|line| line.iter().take_while(|token| matches!(token.kind, Space | Tab)).count())
+        .min()
+        .unwrap_or(0);
+
+    let mut dedented = vec![];
+
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            dedented.push(breaks[i - 1]);
+        }
+
+        dedented.extend(line.iter().skip(indent));
+    }
+
+    dedented
+}
+
+/// Reads a `'`, `'''`, `r'` or `r'''` string starting at `first` into its opening quote,
+/// its contents as a single symbol spanning the whole string, and its closing quote
+///
+/// Raw strings have no escapes, so they can't have the quotes they end with
+fn string(first: &Token, iter: &mut Peekable<Iter<Token>>, array: &mut Vec<Token>) -> Result<(), LexerError> {
+    use TokenKind::*;
+
+    let raw = matches!(first.kind, Symbol(_));
+    let mut open = Token::new(Quote, first.position);
+
+    if raw {
+        open.position += iter.next().unwrap().position;
+    }
+
+    let multiline = triple(iter);
+
+    if multiline {
+        open.position += iter.next().unwrap().position;
+        open.position += iter.next().unwrap().position;
+    }
+
+    trace!("\x1b[32m+\x1b[m {open}");
+    array.push(open.clone());
+
+    let mut content = vec![];
+
+    let close = loop {
+        let Some(next) = iter.next() else {
+            let symbol = content.iter().map(|token: &&Token| token.kind.as_str()).collect();
+            let mut string = Token::new(Symbol(symbol), open.position);
+
+            for token in &content {
+                string.position += token.position;
+            }
+
+            array.push(string);
+
+            return Err(LexerError::UnterminatedString(first.clone()));
+        };
+
+        match next.kind {
+            Quote if !multiline || triple(iter) => {
+                let mut close = next.clone();
+
+                if multiline {
+                    close.position += iter.next().unwrap().position;
+                    close.position += iter.next().unwrap().position;
+                }
+
+                break close;
+            }
+            // an escaped quote doesn't close the string
+            BackSlash if !raw => {
+                let Some(escaped) = iter.next() else {
+                    return Err(LexerError::InvalidEscape(next.clone()));
+                };
+
+                content.extend([next, escaped]);
+            }
+            _ => content.push(next),
+        }
+    };
+
+    let mut string = Token::new(Symbol("".into()), open.position);
+
+    for token in &content {
+        string.position += token.position;
+    }
+
+    string.position += close.position;
+
+    let content = match multiline {
+        true => dedent(content),
+        false => content,
+    };
+
+    let content = content.into_iter().cloned().collect::<Vec<_>>();
+    let mut content = content.iter().peekable();
+    let mut symbol = String::new();
+
+    while let Some(token) = content.next() {
+        match token.kind {
+            BackSlash if !raw => escape(token, &mut content, &mut symbol)?,
+            _ => symbol.push_str(token.kind.as_str()),
+        }
+    }
+
+    string.kind = Symbol(symbol);
+
+    trace!("\x1b[32m+\x1b[m {string}");
+    array.push(string);
+
+    trace!("\x1b[32m+\x1b[m {close}");
+    array.push(close);
+
+    Ok(())
+}
+
+pub fn filter(tokens: &[Token], mode: Mode) -> Result<Vec<Token>, LexerError> {
+    match filter_partial(tokens, mode) {
+        (tokens, None) => Ok(tokens),
+        (_, Some(error)) => Err(error),
+    }
+}
+
+/// Filters as much as it can, an unterminated string keeps what was read of it
+fn filter_partial(tokens: &[Token], mode: Mode) -> (Vec<Token>, Option<LexerError>) {
     use TokenKind::*;
 
     let mut array: Vec<Token> = vec![];
+    // nothing but whitespace since the line started
+    let mut indent = true;
 
     let mut iter = tokens.iter().peekable();
 
     while let Some(token) = iter.next() {
         let next = iter.peek().map(|&token| &token.kind);
 
+        if matches!(token.kind, Tab) && indent && mode == Mode::Strict {
+            return (array, Some(LexerError::TabIndentation(token.clone())));
+        }
+
+        indent = match token.kind {
+            NewLine => true,
+            Space | Tab => indent,
+            _ => false,
+        };
+
         match (&token.kind, &next) {
             // Comment
             (Slash, Some(Slash)) => {
@@ -69,15 +368,22 @@ pub fn filter(tokens: &[Token]) -> Vec<Token> {
             }
             // Comment block
             (Slash, Some(Asterisk)) => {
-                while !matches!(
-                    (iter.next(), iter.peek()),
-                    (Some(left), Some(&right))
-                        if matches!(left.kind, Asterisk)
-                        && matches!(right.kind, Slash)
-                ) {}
+                // skip the `*` so `/*/` doesn't close itself
+                iter.next();
+
+                let to = loop {
+                    match (iter.next(), iter.peek()) {
+                        (Some(left), Some(&right)) if matches!(left.kind, Asterisk) && matches!(right.kind, Slash) => {
+                            break right.position;
+                        }
+                        (Some(_), _) => {}
+                        (None, _) => return (array, Some(LexerError::UnterminatedComment(token.clone()))),
+                    }
+                };
+
+                iter.next();
 
                 let from = token.position;
-                let to = iter.next().unwrap().position;
                 trace!("\x1b[31m-\x1b[m \x1b[35mComment Block\x1b[m from \x1b[36m{}:{}\x1b[m to \x1b[36m{}:{}\x1b[m", from.line, from.character, to.line, to.character);
 
                 // Remove \n after block end
@@ -87,44 +393,10 @@ pub fn filter(tokens: &[Token]) -> Vec<Token> {
 
                 continue;
             }
-            // String
-            (Quote, _) => {
-                let mut string = Token::new(Symbol("".into()), token.position);
-
-                trace!("\x1b[32m+\x1b[m {token}");
-                array.push(token.clone());
-
-                while let Some(token) = iter.next() {
-                    match token.kind {
-                        Quote => {
-                            string.position += token.position;
-
-                            trace!("\x1b[32m+\x1b[m {string}");
-                            array.push(string);
-
-                            trace!("\x1b[32m+\x1b[m {token}");
-                            array.push(token.clone());
-
-                            break;
-                        }
-                        BackSlash => {
-                            let next = iter.next().unwrap();
-
-                            if let TokenKind::Symbol(ref mut symbol) = string.kind {
-                                if let Some(c) = match next.kind.as_str() {
-                                    "\\" => Some('\\'),
-                                    "r" => Some('\r'),
-                                    "n" => Some('\n'),
-                                    "t" => Some('\t'),
-                                    "'" => Some('\''),
-                                    _ => None,
-                                } {
-                                    symbol.push(c);
-                                }
-                            }
-                        }
-                        _ => string.join(token).unwrap(),
-                    }
+            // String, `r'` starts a raw one
+            (Quote, _) | (Symbol(_), Some(Quote)) if token.kind == Quote || token.kind.as_str() == "r" => {
+                if let Err(error) = string(token, &mut iter, &mut array) {
+                    return (array, Some(error));
                 }
 
                 continue;
@@ -144,11 +416,21 @@ pub fn filter(tokens: &[Token]) -> Vec<Token> {
     debug!("\x1b[1;33m*\x1b[39m Filtered: \x1b[31m{}\x1b[m", tokens.len() - array.len());
     debug!("\x1b[1;33m*\x1b[39m Remaining: \x1b[32m{}\x1b[m", array.len());
 
-    array
+    (array, None)
+}
+
+pub fn lex(buffer: &[u8]) -> Result<Vec<Token>, LexerError> {
+    lex_with(buffer, Mode::Standard)
+}
+
+/// Lexes the way a [`Mode`] wants
+pub fn lex_with(buffer: &[u8], mode: Mode) -> Result<Vec<Token>, LexerError> {
+    filter(&tokenize(buffer), mode)
 }
 
-pub fn lex(buffer: &[u8]) -> Vec<Token> {
-    filter(&tokenize(buffer))
+/// Lexes up to the first error, for tools that work on files being typed
+pub fn lex_partial(buffer: &[u8]) -> Vec<Token> {
+    filter_partial(&tokenize(buffer), Mode::Standard).0
 }
 
 #[cfg(test)]
@@ -163,27 +445,42 @@ mod tests {
         let buffer: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789,.\n:/' *=[]{}\\a";
 
         let expect: &[Token] = &[
-            Token::new(Symbol("abcdefghijklmnopqrstuvwxyz0123456789".into()), (1, 0, 36)),
-            Token::new(Comma, (1, 36, 1)),
-            Token::new(Dot, (1, 37, 1)),
-            Token::new(NewLine, (1, 38, 1)),
-            Token::new(Colon, (2, 0, 1)),
-            Token::new(Slash, (2, 1, 1)),
-            Token::new(Quote, (2, 2, 1)),
-            Token::new(Space, (2, 3, 1)),
-            Token::new(Asterisk, (2, 4, 1)),
-            Token::new(Equals, (2, 5, 1)),
-            Token::new(OpenBracket, (2, 6, 1)),
-            Token::new(CloseBracket, (2, 7, 1)),
-            Token::new(OpenCurly, (2, 8, 1)),
-            Token::new(CloseCurly, (2, 9, 1)),
-            Token::new(BackSlash, (2, 10, 1)),
-            Token::new(Symbol("a".into()), (2, 11, 1)),
+            Token::new(Symbol("abcdefghijklmnopqrstuvwxyz0123456789".into()), (1, 0, 36, 0, 36)),
+            Token::new(Comma, (1, 36, 1, 36, 37)),
+            Token::new(Dot, (1, 37, 1, 37, 38)),
+            Token::new(NewLine, (1, 38, 1, 38, 39)),
+            Token::new(Colon, (2, 0, 1, 39, 40)),
+            Token::new(Slash, (2, 1, 1, 40, 41)),
+            Token::new(Quote, (2, 2, 1, 41, 42)),
+            Token::new(Space, (2, 3, 1, 42, 43)),
+            Token::new(Asterisk, (2, 4, 1, 43, 44)),
+            Token::new(Equals, (2, 5, 1, 44, 45)),
+            Token::new(OpenBracket, (2, 6, 1, 45, 46)),
+            Token::new(CloseBracket, (2, 7, 1, 46, 47)),
+            Token::new(OpenCurly, (2, 8, 1, 47, 48)),
+            Token::new(CloseCurly, (2, 9, 1, 48, 49)),
+            Token::new(BackSlash, (2, 10, 1, 49, 50)),
+            Token::new(Symbol("a".into()), (2, 11, 1, 50, 51)),
         ];
 
         assert_eq!(tokenize(buffer), expect);
     }
 
+    #[test]
+    fn test_single_chars() {
+        use TokenKind::*;
+
+        // a one char symbol ends at the next char that isn't part of one
+        let expect: &[Token] = &[
+            Token::new(Symbol("a".into()), (1, 0, 1, 0, 1)),
+            Token::new(Equals, (1, 1, 1, 1, 2)),
+            Token::new(Symbol("1".into()), (1, 2, 1, 2, 3)),
+            Token::new(Comma, (1, 3, 1, 3, 4)),
+        ];
+
+        assert_eq!(tokenize(b"a=1,"), expect);
+    }
+
     #[test]
     fn test_filter() {
         use TokenKind::*;
@@ -196,7 +493,7 @@ mod tests {
 
         let expect: &[Token] = &tokens[4..8];
 
-        assert_eq!(filter(tokens), expect);
+        assert_eq!(filter(tokens, Mode::Standard).unwrap(), expect);
     }
 
     #[test]
@@ -225,6 +522,128 @@ mod tests {
         let expect: &[Token] = &[Symbol("var".into()), Equals, Quote, Symbol("abcd\nefg\thijk\r\nlmn\'opq\'rstuv\nwxyz".into()), Quote]
             .map(|kind| Token::new(kind, (0, 0, 0)));
 
-        assert_eq!(filter(tokens), expect);
+        assert_eq!(filter(tokens, Mode::Standard).unwrap(), expect);
+    }
+
+    #[test]
+    fn test_errors() {
+        use TokenKind::*;
+
+        let quote = Token::new(Quote, (1, 4, 1, 4, 5));
+
+        assert_eq!(lex(b"a = /* never closed\n"), Err(LexerError::UnterminatedComment(Token::new(Slash, (1, 4, 1, 4, 5)))));
+        assert_eq!(lex(b"a = 'ends in \\"), Err(LexerError::InvalidEscape(Token::new(BackSlash, (1, 13, 1, 13, 14)))));
+        assert_eq!(lex(b"a = 'never closed\n"), Err(LexerError::UnterminatedString(quote)));
+        assert_eq!(lex(b"/*/ a = 1 */b = 2").unwrap().len(), 3);
+
+        let partial = lex_partial(b"a = 'typing");
+        assert_eq!(partial.last().map(|token| &token.kind), Some(&Symbol("typing".into())));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_line_endings() {
+        use crate::{Kfg, Node};
+        use TokenKind::*;
+
+        let tokens = tokenize(b"a\r\nb\rc\n");
+
+        let expect: &[Token] = &[
+            Token::new(Symbol("a".into()), (1, 0, 1, 0, 1)),
+            Token::new(NewLine, (1, 1, 2, 1, 3)),
+            Token::new(Symbol("b".into()), (2, 0, 1, 3, 4)),
+            Token::new(NewLine, (2, 1, 1, 4, 5)),
+            Token::new(Symbol("c".into()), (3, 0, 1, 5, 6)),
+            Token::new(NewLine, (3, 1, 1, 6, 7)),
+        ];
+
+        assert_eq!(tokens, expect);
+
+        let ast = Kfg::parse(b"a = true\r\n// c\r\nb = [1,\r 2]\rc = 'x'").unwrap();
+        assert_eq!(ast.lookup("a"), Some(&Node::Bool(true)));
+        assert_eq!(ast.lookup("b::1"), Some(&Node::Integer(2)));
+
+        let ast = Kfg::parse(b"a = 'x\r\ny\rz'\r\nb = r'\\d\n'\r\n'k\r\nl' = { .'m\rn': 1 }").unwrap();
+        assert_eq!(ast.lookup("a"), Some(&Node::String("x\ny\nz".into())));
+        assert_eq!(ast.lookup("b"), Some(&Node::String("\\d\n".into())));
+        assert_eq!(ast.lookup("k\nl::m\nn"), Some(&Node::Integer(1)));
+
+        let error = Kfg::parse(b"a = 1\r\nb = 2\rc = [.\r\n").unwrap_err();
+        assert_eq!(error.token().map(|token| (token.position.line, token.position.character)), Some((3, 5)));
+
+        assert_eq!(crate::span::line_starts("a\r\nb\rc\n"), [0, 3, 5, 7]);
+        assert_eq!(crate::format::format(b"a=1\r\nb=2\r\n"), "a = 1\r\nb = 2\r\n");
+    }
+
+    #[test]
+    fn test_escapes() {
+        use crate::{writer, Kfg, Node, ParserError};
+
+        let ast = Kfg::parse(br#"a = '\\ \' \" \n \r \t \0 \x41\x7e2 \u{e9} \u{1F600}'"#).unwrap();
+        assert_eq!(ast.lookup("a"), Some(&Node::String("\\ ' \" \n \r \t \0 A~2 é 😀".into())));
+
+        let error = |source: &str| match Kfg::parse(source.as_bytes()) {
+            Err(ParserError::Lexer(error)) => (error.name(), error.token().position.line, error.token().position.character),
+            result => panic!("{result:?}"),
+        };
+
+        assert_eq!(error("a = 1\nb = 'x\\q'"), ("UnknownEscape", 2, 6));
+        assert_eq!(error("a = '\\x4'"), ("MalformedEscape", 1, 5));
+        assert_eq!(error("a = '\\x80'"), ("MalformedEscape", 1, 5));
+        assert_eq!(error("a = '\\xé1'"), ("MalformedEscape", 1, 5));
+        assert_eq!(error("a = '\\u{d800}'"), ("MalformedEscape", 1, 5));
+        assert_eq!(error("a = '\\u{110000}'"), ("MalformedEscape", 1, 5));
+        assert_eq!(error("a = '\\u{}'"), ("MalformedEscape", 1, 5));
+        assert_eq!(error("a = '\\u41'"), ("MalformedEscape", 1, 5));
+
+        let value = "tab\t nul\0 bell\u{7} del\u{7f} c1\u{85} quote' \\ é\r\n";
+        let ast = Kfg::parse(format!("a = {}", writer::string(value)).as_bytes()).unwrap();
+        assert_eq!(ast.lookup("a"), Some(&Node::String(value.into())));
+    }
+
+    #[test]
+    fn test_multiline() {
+        use crate::{format, syntax, FileId, Kfg, Node};
+
+        let source = r#"query = '''
+    SELECT *
+      FROM t // not a comment
+    /* nor this */ WHERE a = '1'
+    '''
+pattern = r'\d+\.\d*'
+pem = r'''
+  -----BEGIN-----
+  a\b
+
+  -----END-----
+  '''
+inline = '''a\tb''' after = 'x'
+broken = [.
+"#;
+
+        let error = Kfg::parse(source.as_bytes()).unwrap_err();
+        assert_eq!(error.token().map(|token| (token.position.line, token.position.character)), Some((14, 10)));
+
+        let source = source.replace("broken = [.\n", "");
+        let ast = Kfg::parse_spanned(source.as_bytes(), FileId(0)).unwrap();
+
+        let string = |path: &str| match ast.lookup(path) {
+            Some(Node::String(string)) => string.as_str(),
+            node => panic!("{node:?}"),
+        };
+
+        assert_eq!(string("query"), "SELECT *\n  FROM t // not a comment\n/* nor this */ WHERE a = '1'");
+        assert_eq!(string("pattern"), "\\d+\\.\\d*");
+        assert_eq!(string("pem"), "-----BEGIN-----\na\\b\n\n-----END-----");
+        assert_eq!(string("inline"), "a\tb");
+        assert_eq!(string("after"), "x");
+
+        let span = ast.span_of("query").unwrap();
+        assert_eq!((span.start.line, span.start.column, span.end.line, span.end.column), (1, 9, 5, 8));
+        assert_eq!(&source[ast.span_of("pattern").unwrap().range()], "r'\\d+\\.\\d*'");
+
+        assert_eq!(format::format(source.as_bytes()), source);
+        assert!(syntax::highlight(&source).iter().all(|highlight| highlight.category != syntax::Category::Comment));
+
+        assert!(matches!(Kfg::parse(b"a = '''\nb = 1\n''"), Err(crate::ParserError::Lexer(LexerError::UnterminatedString(_)))));
+    }
+}
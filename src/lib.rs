@@ -8,21 +8,129 @@ pub(crate) mod ast;
 pub use ast::*;
 
 pub(crate) mod lexer;
+pub use lexer::LexerError;
+
 pub(crate) mod parser;
-pub use parser::ParserError;
+pub use parser::{Limit, Mode, ParseOptions, ParserError, Warning, MAX_DEPTH};
+
+pub mod error;
+pub use error::Error;
+
+pub(crate) mod import;
+pub use import::ImportError;
+
+pub(crate) mod flat;
+pub use flat::{FlatError, FlatValue};
+
+pub(crate) mod format;
+pub use format::FormatError;
+
+pub mod json;
+pub(crate) mod toml;
+pub(crate) mod yaml;
+
+pub mod convert;
+pub use convert::{ConvertError, Format};
+
+pub(crate) mod writer;
+
+pub(crate) mod diff;
+pub use diff::{Change, ChangeKind};
+
+pub(crate) mod patch;
+pub use patch::{Operation, Patch, PatchError};
+
+pub mod edit;
+pub use edit::EditError;
+
+pub mod syntax;
+
+pub mod incremental;
+
+pub(crate) mod doc;
+
+pub mod span;
+pub use span::{FileId, Files, Location, SourceSpan};
+
+pub mod layer;
+pub use layer::{LayerError, Layers, Origin, Source};
+
+pub mod merge;
+pub use merge::{Conflict, Merge, MergeError};
+
+pub mod watch;
+pub use watch::{WatchError, Watcher};
+
+pub mod config;
+pub use config::{Config, ConfigError, FromAst};
+
+pub mod export;
+pub use export::{FlatOptions, KeyCase};
 
 pub struct Kfg;
 
 impl Kfg {
-    pub fn read(value: &str) -> Result<Ast, parser::ParserError> {
-        let content = std::fs::read(value).expect("couldn't read the file");
+    pub fn read(path: impl AsRef<std::path::Path>) -> Result<Ast, Error> {
+        let content = std::fs::read(path)?;
 
-        Self::parse(&content)
+        Ok(Self::parse(&content)?)
     }
 
     pub fn parse(buffer: &[u8]) -> Result<Ast, parser::ParserError> {
-        let tokens = lexer::lex(buffer);
+        Self::parse_with(buffer, &ParseOptions::default()).map(|(ast, _)| ast)
+    }
+
+    /// Parses within the limits and [`Mode`] of `options`, with what a lenient parse warned about
+    pub fn parse_with(buffer: &[u8], options: &ParseOptions) -> Result<(Ast, Vec<Warning>), parser::ParserError> {
+        Self::parse_source(buffer, options, None)
+    }
+
+    /// Parses and records where every key and value was written, see [`Ast::span_of`]
+    pub fn parse_spanned(buffer: &[u8], file: FileId) -> Result<Ast, parser::ParserError> {
+        Self::parse_source(buffer, &ParseOptions::default(), Some(file)).map(|(ast, _)| ast)
+    }
+
+    fn parse_source(buffer: &[u8], options: &ParseOptions, file: Option<FileId>) -> Result<(Ast, Vec<Warning>), parser::ParserError> {
+        parser::check_input(buffer, options)?;
+
+        let tokens = lexer::lex_with(buffer, options.mode)?;
+
+        let (mut ast, warnings, spans) = match file {
+            Some(_) => parser::parse_spanned(&tokens, options).map(|(ast, warnings, spans)| (ast, warnings, Some(spans)))?,
+            None => parser::parse(&tokens, options).map(|(ast, warnings)| (ast, warnings, None))?,
+        };
+
+        let source = String::from_utf8_lossy(buffer);
+
+        if doc::has_docs(buffer) {
+            ast.1.docs = doc::docs(&source, &ast);
+        }
+
+        if let (Some(file), Some(spans)) = (file, spans) {
+            ast.1 = span::Sources::new(&source, file, &ast, spans);
+        }
+
+        Ok((ast, warnings))
+    }
+
+    /// Reprints a source file in canonical form, failing if it doesn't parse or if the
+    /// result wouldn't read back as the same tree
+    pub fn format(buffer: &[u8]) -> Result<String, FormatError> {
+        let ast = Self::parse(buffer)?;
+        let formatted = format::format(buffer);
+
+        // compared as written since NaN isn't equal to itself
+        match Self::parse(formatted.as_bytes()) {
+            Ok(reparsed) if reparsed.to_kfg() == ast.to_kfg() => Ok(formatted),
+            _ => Err(FormatError::Changed),
+        }
+    }
+
+    pub fn parse_ini(buffer: &[u8]) -> Result<Ast, ImportError> {
+        import::ini(buffer)
+    }
 
-        parser::parse(&tokens)
+    pub fn parse_dotenv(buffer: &[u8]) -> Result<Ast, ImportError> {
+        import::dotenv(buffer)
     }
 }
@@ -0,0 +1,46 @@
+mod cli;
+
+const USAGE: &str = "\
+Usage: kfg <command> [options] <files...>
+
+Commands:
+  check [--format text|json] [--strict|--lenient] <files...>
+                                          Parse files and report errors
+  fmt [--check] <files...>                Rewrite files in canonical form
+  get <file> <path> [--output raw|kfg|json] [--default <value>]
+                                          Print the value at a key::path
+  set <file> <path> <value> [--create]    Replace the value at a key::path
+  convert [--from <format>] [--to <format>] [--strict] [in] [out]
+                                          Convert between kfg, json, toml and yaml
+  diff <from> <to> [--format text|json|patch]
+                                          Show the changed values between two files
+  patch <file> <patch> [out]              Apply a patch document to a file
+  merge-driver <base> <ours> <theirs>     Three-way merge into <ours>, for git
+  explain <path> <files...> [--env <prefix>] [--set <key::path=value>]...
+                                          Show where a value came from and what it overrode
+
+Files can be paths, directories (searched for *.kfg), globs or - for stdin";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let code = match args.first().map(String::as_str) {
+        Some("check") => cli::check::run(&args[1..]),
+        Some("fmt") => cli::fmt::run(&args[1..]),
+        Some("get") => cli::get::run(&args[1..]),
+        Some("set") => cli::set::run(&args[1..]),
+        Some("convert") => cli::convert::run(&args[1..]),
+        Some("diff") => cli::diff::run(&args[1..]),
+        Some("patch") => cli::patch::run(&args[1..]),
+        Some("merge-driver") => cli::merge_driver::run(&args[1..]),
+        Some("explain") => cli::explain::run(&args[1..]),
+        Some("-h" | "--help" | "help") => {
+            println!("{USAGE}");
+            0
+        }
+        Some(command) => cli::usage(&format!("unknown command '{command}'\n\n{USAGE}")),
+        None => cli::usage(USAGE),
+    };
+
+    std::process::exit(code);
+}
@@ -0,0 +1,351 @@
+use crate::{edit::{self, Span}, span, writer, Ast, Kfg, Node, ParserError};
+use std::collections::{BTreeSet, HashMap};
+
+/// A `key::path` both sides changed differently, `None` means the key isn't there
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<Node>,
+    pub ours: Option<Node>,
+    pub theirs: Option<Node>,
+}
+
+/// Which of the three versions didn't parse
+#[derive(Debug, Clone)]
+pub enum MergeError {
+    Base(ParserError),
+    Ours(ParserError),
+    Theirs(ParserError),
+}
+
+impl std::error::Error for MergeError {}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// The result of a three-way merge, conflicting keys keep the value from our side
+#[derive(Debug, Clone)]
+pub struct Merge {
+    pub ast: Ast,
+    pub conflicts: Vec<Conflict>,
+    /// The deepest keys where their side was taken, `None` when they removed it
+    pub taken: Vec<(String, Option<Node>)>,
+}
+
+struct Merger {
+    path: Vec<String>,
+    conflicts: Vec<Conflict>,
+    taken: Vec<(String, Option<Node>)>,
+}
+
+impl Merger {
+    fn node(&mut self, base: Option<&Node>, ours: Option<&Node>, theirs: Option<&Node>) -> Option<Node> {
+        if ours == theirs || theirs == base {
+            return ours.cloned();
+        }
+
+        let empty = HashMap::new();
+
+        if ours == base {
+            // only the keys they changed are taken, so a source edit touches just those
+            if let (Some(Node::Dict(base)), Some(Node::Dict(theirs))) = (base, theirs) {
+                return Some(Node::Dict(self.dict(base, base, theirs)));
+            }
+
+            self.taken.push((self.path.join("::"), theirs.cloned()));
+            return theirs.cloned();
+        }
+
+        match (base, ours, theirs) {
+            (Some(Node::Dict(base)), Some(Node::Dict(ours)), Some(Node::Dict(theirs))) => {
+                Some(Node::Dict(self.dict(base, ours, theirs)))
+            }
+            (None, Some(Node::Dict(ours)), Some(Node::Dict(theirs))) => {
+                Some(Node::Dict(self.dict(&empty, ours, theirs)))
+            }
+            _ => {
+                self.conflicts.push(Conflict {
+                    path: self.path.join("::"),
+                    base: base.cloned(),
+                    ours: ours.cloned(),
+                    theirs: theirs.cloned(),
+                });
+
+                ours.cloned()
+            }
+        }
+    }
+
+    fn dict(
+        &mut self,
+        base: &HashMap<String, Node>,
+        ours: &HashMap<String, Node>,
+        theirs: &HashMap<String, Node>,
+    ) -> HashMap<String, Node> {
+        let keys = base.keys().chain(ours.keys()).chain(theirs.keys()).collect::<BTreeSet<_>>();
+        let mut merged = HashMap::new();
+
+        for key in keys {
+            self.path.push(key.clone());
+
+            if let Some(node) = self.node(base.get(key), ours.get(key), theirs.get(key)) {
+                merged.insert(key.clone(), node);
+            }
+
+            self.path.pop();
+        }
+
+        merged
+    }
+}
+
+impl Ast {
+    /// Merges the changes `ours` and `theirs` made to `base`
+    ///
+    /// Dicts are merged key by key, everything else, arrays included, is taken whole from
+    /// whichever side changed it. Keys both sides changed differently are conflicts
+    pub fn merge(base: &Ast, ours: &Ast, theirs: &Ast) -> Merge {
+        let mut merger = Merger {
+            path: vec![],
+            conflicts: vec![],
+            taken: vec![],
+        };
+
+        let ast = Ast(merger.dict(&base.0, &ours.0, &theirs.0), Default::default());
+
+        Merge {
+            ast,
+            conflicts: merger.conflicts,
+            taken: merger.taken,
+        }
+    }
+}
+
+/// How a side of a conflict is written between the markers
+fn side(path: &str, node: &Option<Node>) -> String {
+    match node {
+        Some(node) => format!("{} = {}\n", writer::path(path), node.to_kfg()),
+        None => String::new(),
+    }
+}
+
+/// Their lines for a conflict, as they wrote them when they wrote it the way we did,
+/// comments included, else our key with their value
+fn theirs_side(source: &str, theirs: &str, segments: &[&str], entry: Span, value: Span, node: &Node) -> String {
+    let ending = span::line_ending(source);
+    let start = edit::line_start(source, entry.start);
+    let key = source[start..value.start].trim();
+
+    let found = edit::entries(theirs).into_iter().filter(|(path, _)| *path == segments).collect::<Vec<_>>();
+    let written = edit::locate(theirs).into_iter().filter(|(path, _)| *path == segments).collect::<Vec<_>>();
+
+    if let ([(_, entry)], [(_, value)]) = (&found[..], &written[..]) {
+        let start = edit::line_start(theirs, entry.start);
+
+        if theirs[start..value.start].trim() == key {
+            let mut lines = theirs[start..edit::line_end(theirs, entry.end)].to_string();
+
+            if !lines.ends_with(['\n', '\r']) {
+                lines.push_str(ending);
+            }
+
+            return lines;
+        }
+    }
+
+    let indent = &source[start..entry.start];
+    let indent = &indent[..indent.len() - indent.trim_start().len()];
+    let node = node.to_kfg().replace('\n', &format!("{ending}{indent}"));
+
+    format!("{}{node}{ending}", &source[start..value.start])
+}
+
+/// Wraps every conflicting assignment of `source` in conflict markers
+///
+/// A conflict written as a single assignment or dict entry is wrapped where it is, with
+/// their lines for it, anything else is added at the end as scoped assignments
+fn markers(mut source: String, theirs: &str, conflicts: &[Conflict]) -> String {
+    let ending = span::line_ending(&source);
+    let mut appended = String::new();
+    let mut wrapped = vec![];
+
+    for conflict in conflicts {
+        let segments = conflict.path.split("::").collect::<Vec<_>>();
+
+        let found = edit::entries(&source)
+            .into_iter()
+            .filter(|(path, _)| *path == segments)
+            .collect::<Vec<_>>();
+
+        let value = edit::locate(&source)
+            .into_iter()
+            .filter(|(path, _)| *path == segments)
+            .collect::<Vec<_>>();
+
+        match (&found[..], &value[..], &conflict.theirs) {
+            ([(_, entry)], [(_, value)], node) => wrapped.push((*entry, *value, segments, node.clone())),
+            _ => appended.push_str(&format!(
+                "<<<<<<< ours\n{}=======\n{}>>>>>>> theirs\n",
+                side(&conflict.path, &conflict.ours),
+                side(&conflict.path, &conflict.theirs),
+            )),
+        }
+    }
+
+    wrapped.sort_by_key(|(entry, ..)| std::cmp::Reverse(entry.start));
+
+    for (entry, value, segments, node) in wrapped {
+        let start = edit::line_start(&source, entry.start);
+        let end = edit::line_end(&source, entry.end);
+
+        let mut lines = source[start..end].to_string();
+
+        if !lines.ends_with(['\n', '\r']) {
+            lines.push_str(ending);
+        }
+
+        let theirs = match node {
+            Some(node) => theirs_side(&source, theirs, &segments, entry, value, &node),
+            None => String::new(),
+        };
+
+        let markers = format!("<<<<<<< ours{ending}{lines}======={ending}{theirs}>>>>>>> theirs{ending}");
+        source.replace_range(start..end, &markers);
+    }
+
+    if !appended.is_empty() && !source.is_empty() && !source.ends_with(['\n', '\r']) {
+        source.push_str(ending);
+    }
+
+    source + &appended.replace('\n', ending)
+}
+
+/// Merges three versions of a kfg file, keeping the formatting and comments of `ours`
+///
+/// Changes taken from their side are edited into our source, when that isn't possible
+/// the merged tree is written out instead. Conflicting assignments end up between
+/// `<<<<<<< ours`, `=======` and `>>>>>>> theirs` markers
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Result<(String, Vec<Conflict>), MergeError> {
+    let merge = Ast::merge(
+        &Kfg::parse(base.as_bytes()).map_err(MergeError::Base)?,
+        &Kfg::parse(ours.as_bytes()).map_err(MergeError::Ours)?,
+        &Kfg::parse(theirs.as_bytes()).map_err(MergeError::Theirs)?,
+    );
+
+    let edited = merge.taken.iter().try_fold(ours.to_string(), |source, (path, node)| match node {
+        Some(node) => edit::set(&source, path, node, true),
+        None => edit::remove(&source, path),
+    });
+
+    let source = match edited {
+        Ok(source) if Kfg::parse(source.as_bytes()).is_ok_and(|ast| ast.0 == merge.ast.0) => source,
+        _ => merge.ast.to_kfg(),
+    };
+
+    Ok((markers(source, theirs, &merge.conflicts), merge.conflicts))
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "\
+// shared settings
+port = 80
+hosts = ['a']
+server = {
+    .tls: false // for now
+    .name: 'base'
+}
+";
+
+    #[test]
+    fn test_merge() {
+        let ours = Kfg::parse(b"port = 8080\nhosts = ['a']\nserver = { .tls: true .name: 'base' }").unwrap();
+        let theirs = Kfg::parse(b"port = 80\nhosts = ['a', 'b']\nserver = { .tls: false .name: 'theirs' }\nextra = 1").unwrap();
+
+        let merge = Ast::merge(&Kfg::parse(BASE.as_bytes()).unwrap(), &ours, &theirs);
+        let expected = Kfg::parse(b"port = 8080\nhosts = ['a', 'b']\nserver = { .tls: true .name: 'theirs' }\nextra = 1").unwrap();
+
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.ast.0, expected.0);
+
+        let theirs = Kfg::parse(b"port = 443\nhosts = ['a']\nserver = { .tls: false .name: 'base' }").unwrap();
+        let merge = Ast::merge(&Kfg::parse(BASE.as_bytes()).unwrap(), &ours, &theirs);
+
+        assert_eq!(merge.conflicts, [Conflict {
+            path: "port".into(),
+            base: Some(Node::Integer(80)),
+            ours: Some(Node::Integer(8080)),
+            theirs: Some(Node::Integer(443)),
+        }]);
+        assert_eq!(merge.ast.0, ours.0);
+    }
+
+    #[test]
+    fn test_merge_source() {
+        let ours = BASE.replace("port = 80", "port = 8080 // ours").replace(".tls: false", ".tls: true");
+        let theirs = BASE.replace("'base'", "'theirs'").replace("hosts = ['a']\n", "") + "extra = [1, 2]\n";
+
+        let (merged, conflicts) = merge(BASE, &ours, &theirs).unwrap();
+
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, "\
+// shared settings
+port = 8080 // ours
+server = {
+    .tls: true // for now
+    .name: 'theirs'
+}
+extra = [1, 2]
+");
+
+        let theirs = "port = 80\nhosts = ['a']\nserver = { .tls: true .name: 'theirs' .debug: true }";
+        let (merged, conflicts) = merge(BASE, BASE, theirs).unwrap();
+
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, BASE.replace("'base'", "'theirs'").replace("false", "true") + "server::debug = true\n");
+
+        let base = Kfg::parse(BASE.as_bytes()).unwrap();
+        let taken = Ast::merge(&base, &base, &Kfg::parse(theirs.as_bytes()).unwrap()).taken;
+        let taken = taken.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(taken, ["server::debug", "server::name", "server::tls"]);
+
+        let theirs = BASE.replace("port = 80", "port = 443 // theirs").replace(".tls: false", ".tls: null");
+
+        let (merged, conflicts) = merge(BASE, &ours, &theirs).unwrap();
+
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(merged, "\
+// shared settings
+<<<<<<< ours
+port = 8080 // ours
+=======
+port = 443 // theirs
+>>>>>>> theirs
+hosts = ['a']
+server = {
+<<<<<<< ours
+    .tls: true // for now
+=======
+    .tls: null // for now
+>>>>>>> theirs
+    .name: 'base'
+}
+");
+
+        // written another way, their value goes under our key
+        let theirs = BASE.replace("    .tls: false // for now\n", "") + "server::tls = null // theirs\n";
+        let (merged, _) = merge(BASE, &ours, &theirs).unwrap();
+
+        assert!(merged.contains("=======\n    .tls: null\n>>>>>>> theirs\n"));
+
+        assert!(matches!(merge(BASE, &ours, "port = = 1"), Err(MergeError::Theirs(_))));
+        assert!(matches!(merge("port = = 1", &ours, &theirs), Err(MergeError::Base(_))));
+    }
+}
@@ -1,5 +1,647 @@
-use crate::{Ast, Node, Token, TokenKind};
-use std::collections::HashMap;
+use crate::{ast::ParseTokens, lexer::LexerError, span, Ast, Location, Node, SourceSpan, Token, TokenKind};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+    ops::Range,
+    slice::Iter,
+};
+
+/// A limit of [`ParseOptions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Input,
+    Depth,
+    String,
+    Array,
+    Keys,
+    Nodes,
+}
+
+impl Limit {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Limit::Input => "input is larger than allowed",
+            Limit::Depth => "nested deeper than allowed",
+            Limit::String => "string is longer than allowed",
+            Limit::Array => "array has more elements than allowed",
+            Limit::Keys => "more keys than allowed",
+            Limit::Nodes => "more values than allowed",
+        }
+    }
+}
+
+/// How forgiving a parse is, see [`ParseOptions::strict`] and [`ParseOptions::lenient`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Rejects trailing commas, mixed separators, duplicate keys and tabs used for indentation
+    Strict,
+    #[default]
+    Standard,
+    /// Reads `yes`/`no`/`on`/`off` as booleans, `~` as null and words that aren't values
+    /// as strings, warning about them
+    Lenient,
+}
+
+/// How deep values can nest whatever [`ParseOptions::max_depth`] says, so the tree can be
+/// dropped and written without running out of stack
+pub const MAX_DEPTH: usize = 256;
+
+/// Limits on what a parse accepts and how forgiving it is
+///
+/// `None` is unlimited, except for the depth every value nests, which is always bounded
+/// by [`MAX_DEPTH`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub mode: Mode,
+    /// In bytes
+    pub max_input: Option<usize>,
+    /// Dicts and arrays inside each other, scopes included
+    pub max_depth: usize,
+    /// In bytes, after escapes
+    pub max_string: Option<usize>,
+    pub max_array: Option<usize>,
+    /// Every key written, assignments and dict entries
+    pub max_keys: Option<usize>,
+    /// Every value in the tree, dicts and arrays included
+    pub max_nodes: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Standard,
+            max_input: None,
+            max_depth: 128,
+            max_string: None,
+            max_array: None,
+            max_keys: None,
+            max_nodes: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Limits for files uploaded by someone else, anything bigger than a real config fails
+    pub fn untrusted() -> Self {
+        Self {
+            mode: Mode::Standard,
+            max_input: Some(1 << 20),
+            max_depth: 32,
+            max_string: Some(64 << 10),
+            max_array: Some(10_000),
+            max_keys: Some(10_000),
+            max_nodes: Some(100_000),
+        }
+    }
+
+    pub fn strict() -> Self {
+        Self {
+            mode: Mode::Strict,
+            ..Default::default()
+        }
+    }
+
+    pub fn lenient() -> Self {
+        Self {
+            mode: Mode::Lenient,
+            ..Default::default()
+        }
+    }
+}
+
+/// Something a lenient parse accepted but would rather not have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A word that isn't a value, read as a string
+    UnquotedString(Token),
+}
+
+impl Warning {
+    /// The variant name, e.g. `UnquotedString`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Warning::UnquotedString(_) => "UnquotedString",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Warning::UnquotedString(_) => "read as a string, quote it",
+        }
+    }
+
+    pub fn token(&self) -> &Token {
+        match self {
+            Warning::UnquotedString(token) => token,
+        }
+    }
+
+    pub(crate) fn token_mut(&mut self) -> &mut Token {
+        match self {
+            Warning::UnquotedString(token) => token,
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Where the values and keys of a parse were written, in bytes and in the order they were read
+///
+/// Each segment of a scoped key is a key of its own, paired with the path it names
+#[derive(Debug, Default)]
+pub(crate) struct Spans {
+    pub values: Vec<(Vec<String>, Range<usize>)>,
+    pub keys: Vec<(Vec<String>, Range<usize>)>,
+    /// Where the assignment being read writes its value
+    path: Vec<String>,
+}
+
+/// The state of one parse, what it used up of its [`ParseOptions`] and what it warned about
+pub(crate) struct Context<'a> {
+    options: &'a ParseOptions,
+    keys: usize,
+    nodes: usize,
+    /// Paths written so far and the ones under them, only kept when strict
+    written: HashSet<Vec<String>>,
+    parents: HashSet<Vec<String>>,
+    /// Paths written with something other than a dict, only kept when strict
+    leaves: HashSet<Vec<String>>,
+    pub warnings: Vec<Warning>,
+    /// Only kept for a spanned parse
+    pub spans: Option<Spans>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(options: &'a ParseOptions) -> Self {
+        Self {
+            options,
+            keys: 0,
+            nodes: 0,
+            written: HashSet::new(),
+            parents: HashSet::new(),
+            leaves: HashSet::new(),
+            warnings: vec![],
+            spans: None,
+        }
+    }
+
+    /// Also records where every value and key was written
+    pub fn spanned(options: &'a ParseOptions) -> Self {
+        Self {
+            spans: Some(Spans::default()),
+            ..Self::new(options)
+        }
+    }
+
+    fn strict(&self) -> bool {
+        self.options.mode == Mode::Strict
+    }
+
+    fn key(&mut self, token: &Token) -> Result<(), ParserError> {
+        self.keys += 1;
+
+        match self.options.max_keys {
+            Some(max) if self.keys > max => Err(ParserError::Limit(Limit::Keys, token.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    fn node(&mut self, token: &Token) -> Result<(), ParserError> {
+        self.nodes += 1;
+
+        match self.options.max_nodes {
+            Some(max) if self.nodes > max => Err(ParserError::Limit(Limit::Nodes, token.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    fn depth(&self, depth: usize, token: &Token) -> Result<(), ParserError> {
+        match depth > self.options.max_depth.min(MAX_DEPTH) {
+            true => Err(ParserError::Limit(Limit::Depth, token.clone())),
+            false => Ok(()),
+        }
+    }
+
+    /// Fails when strict and an assignment to `path` replaces something written before,
+    /// scoped keys may still add to a dict
+    fn write(&mut self, path: Vec<String>, node: &Node, key: &Token) -> Result<(), ParserError> {
+        if !self.strict() {
+            return Ok(());
+        }
+
+        let replaced = self.written.contains(&path)
+            || self.parents.contains(&path)
+            || (1..path.len()).any(|len| self.leaves.contains(&path[..len]));
+
+        if replaced {
+            return Err(ParserError::DuplicateKey(key.clone()));
+        }
+
+        let mut pending = vec![(path, node)];
+
+        while let Some((path, node)) = pending.pop() {
+            for len in 1..path.len() {
+                self.parents.insert(path[..len].to_vec());
+            }
+
+            match node {
+                Node::Dict(dict) => {
+                    let entries = dict.iter().map(|(key, node)| ([&path[..], std::slice::from_ref(key)].concat(), node));
+                    pending.extend(entries);
+                }
+                _ => {
+                    self.leaves.insert(path.clone());
+                }
+            }
+
+            self.written.insert(path);
+        }
+
+        Ok(())
+    }
+}
+
+/// Fails when the input is bigger than allowed, pointing at where the limit is crossed
+pub(crate) fn check_input(buffer: &[u8], options: &ParseOptions) -> Result<(), ParserError> {
+    let Some(max) = options.max_input.filter(|&max| buffer.len() > max) else {
+        return Ok(());
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..max]);
+    let lines = span::line_starts(&head);
+
+    let line = lines.len();
+    let character = head[lines[line - 1]..].chars().count();
+
+    let token = Token::new(TokenKind::default(), (line, character, 0, max, max));
+
+    Err(ParserError::Limit(Limit::Input, token))
+}
+
+/// A dict or array still being read
+struct Frame {
+    open: Open,
+    /// Elements read so far
+    count: usize,
+    /// Seen since the last element, only kept when strict
+    comma: Option<Token>,
+    newline: bool,
+    /// Whether the elements are separated by commas, once known
+    commas: Option<bool>,
+    /// The path it's written at and the byte it starts at, for a spanned parse
+    span: Option<(Vec<String>, usize)>,
+    /// Levels of nesting outside of it, so the depth isn't summed over the stack
+    outer: usize,
+}
+
+enum Open {
+    Array(Vec<Node>),
+    /// With the key of the entry being read, a path for a scoped one
+    Dict(HashMap<String, Node>, Vec<String>),
+}
+
+impl Frame {
+    fn new(open: Open, outer: usize) -> Self {
+        Self {
+            open,
+            count: 0,
+            comma: None,
+            newline: false,
+            commas: None,
+            span: None,
+            outer,
+        }
+    }
+
+    fn push(&mut self, node: Node) {
+        self.count += 1;
+
+        match &mut self.open {
+            Open::Array(array) => array.push(node),
+            Open::Dict(dict, path) => {
+                let mut scopes = std::mem::take(path);
+                let key = scopes.pop().unwrap_or_default();
+
+                insert(dict, scopes, key, node);
+            }
+        }
+    }
+
+    /// How many levels of nesting it adds, counting the dicts a scoped entry opens
+    fn depth(&self) -> usize {
+        match &self.open {
+            Open::Array(_) => 1,
+            Open::Dict(_, path) => path.len().max(1),
+        }
+    }
+
+    /// The path of the element being read, for a spanned parse
+    fn path(&self) -> Option<Vec<String>> {
+        let (path, _) = self.span.as_ref()?;
+
+        let segment = match &self.open {
+            Open::Array(_) => vec![self.count.to_string()],
+            Open::Dict(_, key) => key.clone(),
+        };
+
+        Some([&path[..], &segment[..]].concat())
+    }
+
+    fn into_node(self) -> Node {
+        match self.open {
+            Open::Array(array) => Node::Array(array),
+            Open::Dict(dict, _) => Node::Dict(dict),
+        }
+    }
+
+    /// Checks what separates the element starting at `token` from the one before it
+    fn separate(&mut self, token: &Token) -> Result<(), ParserError> {
+        let comma = self.comma.take().is_some();
+        let newline = std::mem::take(&mut self.newline);
+
+        if self.count == 0 {
+            return Ok(());
+        }
+
+        if !comma && !newline {
+            return Err(ParserError::MissingToken(TokenKind::Comma, token.clone()));
+        }
+
+        match self.commas.replace(comma) {
+            Some(commas) if commas != comma => Err(ParserError::MixedSeparators(token.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Skips to the next value, `false` when the dict or array is over
+    fn next(&mut self, iter: &mut Peekable<Iter<Token>>, context: &mut Context) -> Result<bool, ParserError> {
+        use ParserError::*;
+        use TokenKind::*;
+
+        let strict = context.strict();
+
+        while let Some(&token) = iter.peek() {
+            match (&mut self.open, &token.kind) {
+                (Open::Array(_), CloseBracket) | (Open::Dict(..), CloseCurly) => {
+                    iter.next();
+
+                    return match self.comma.take() {
+                        Some(comma) if strict => Err(TrailingComma(comma)),
+                        _ => Ok(false),
+                    };
+                }
+                (_, Comma) if strict && (self.count == 0 || self.comma.is_some()) => {
+                    return Err(InvalidToken(token.clone()));
+                }
+                (_, Comma) => {
+                    iter.next();
+                    self.comma = Some(token.clone());
+                }
+                (_, NewLine) => {
+                    iter.next();
+                    self.newline = true;
+                }
+                (_, Space | Tab) => {
+                    iter.next();
+                }
+                (Open::Array(array), Symbol(_) | Quote | OpenBracket | OpenCurly) => {
+                    if context.options.max_array.is_some_and(|max| array.len() >= max) {
+                        return Err(Limit(self::Limit::Array, token.clone()));
+                    }
+
+                    if strict {
+                        self.separate(token)?;
+                    }
+
+                    return Ok(true);
+                }
+                (Open::Dict(dict, _), Dot) => {
+                    iter.next();
+
+                    let mut path = vec![];
+                    let mut last = token;
+
+                    // `.a::b: 1` reads like `a::b = 1`
+                    let token = loop {
+                        if iter.peek().is_none() {
+                            return Err(UnexpectedEOF(last.clone()));
+                        }
+
+                        let (symbol, token, end) = key(iter)?;
+                        path.push(symbol);
+
+                        if let (Some(spans), Some((at, _))) = (&mut context.spans, &self.span) {
+                            spans.keys.push(([&at[..], &path[..]].concat(), token.position.offset..end));
+                        }
+
+                        let Some(colon) = iter.next() else {
+                            return Err(UnexpectedEOF(token.clone()));
+                        };
+
+                        if !matches!(colon.kind, Colon) {
+                            return Err(MismatchedTokenType(Colon, colon.clone()));
+                        }
+
+                        match iter.peek() {
+                            Some(&next) if matches!(next.kind, Colon) => last = iter.next().unwrap(),
+                            None => return Err(UnexpectedEOF(colon.clone())),
+                            _ => break token,
+                        }
+                    };
+
+                    if strict && replaces(dict, &path) {
+                        return Err(DuplicateKey(token.clone()));
+                    }
+
+                    context.key(token)?;
+
+                    if strict {
+                        self.separate(token)?;
+                    }
+
+                    if let Open::Dict(_, key) = &mut self.open {
+                        *key = path;
+                    }
+
+                    return Ok(true);
+                }
+                _ => return Err(InvalidToken(token.clone())),
+            }
+        }
+
+        // an unclosed dict or array ends with the input
+        Ok(false)
+    }
+}
+
+/// Whether `key` can be written without quotes
+///
+/// Bare keys start with an ASCII letter or `_`, followed by ASCII letters, digits, `_` and `-`.
+/// `true`, `false` and `null` are keys too where a key is expected
+pub(crate) fn is_bare(key: &str) -> bool {
+    let mut chars = key.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The last token `iter` took since it was at `from`, which must be behind it
+fn last<'t>(from: &Peekable<Iter<'t, Token>>, iter: &Peekable<Iter<'t, Token>>) -> &'t Token {
+    from.clone().nth(from.len() - iter.len() - 1).unwrap()
+}
+
+/// Reads a bare or quoted key at the next token, which must exist, with the token it starts
+/// at and the byte it ends at
+///
+/// Quoted keys can't contain `::`, it separates the keys of a `key::path`
+fn key<'t>(iter: &mut Peekable<Iter<'t, Token>>) -> Result<(String, &'t Token, usize), ParserError> {
+    use TokenKind::*;
+
+    let token = *iter.peek().unwrap();
+    let from = iter.clone();
+
+    match token.kind {
+        Symbol(ref symbol) if is_bare(symbol) => {
+            iter.next();
+            Ok((symbol.clone(), token, token.position.end))
+        }
+        Symbol(_) => Err(ParserError::InvalidKey(token.clone())),
+        Quote => match iter.parse_string()? {
+            Node::String(key) if key.contains("::") => Err(ParserError::InvalidKey(token.clone())),
+            Node::String(key) => Ok((key, token, last(&from, iter).position.end)),
+            _ => unreachable!(),
+        },
+        _ => Err(ParserError::MismatchedTokenType(Symbol("".into()), token.clone())),
+    }
+}
+
+/// A word lenient parses read on their own
+fn lenient(symbol: &str) -> Option<Node> {
+    match symbol {
+        "yes" | "on" => Some(Node::Bool(true)),
+        "no" | "off" => Some(Node::Bool(false)),
+        "~" => Some(Node::Null),
+        _ => None,
+    }
+}
+
+/// Starts reading the value at the next token, dicts and arrays are opened on `stack`
+fn open(
+    iter: &mut Peekable<Iter<Token>>,
+    context: &mut Context,
+    stack: &mut Vec<Frame>,
+    depth: usize,
+) -> Result<Option<Node>, ParserError> {
+    use TokenKind::*;
+
+    let token = *iter.peek().unwrap();
+    context.node(token)?;
+
+    let levels = stack.last().map_or(depth, |frame| frame.outer + frame.depth());
+    context.depth(levels, token)?;
+
+    let open = match token.kind {
+        Symbol(_) => {
+            return match iter.parse_symbol() {
+                Err(ParserError::InvalidSymbol(symbol)) if context.options.mode == Mode::Lenient => {
+                    let symbol = symbol.kind.as_str();
+
+                    Ok(Some(lenient(symbol).unwrap_or_else(|| {
+                        context.warnings.push(Warning::UnquotedString(token.clone()));
+                        Node::String(symbol.into())
+                    })))
+                }
+                node => node.map(Some),
+            };
+        }
+        Quote => {
+            let node = iter.parse_string()?;
+
+            return match (&node, context.options.max_string) {
+                (Node::String(string), Some(max)) if string.len() > max => {
+                    Err(ParserError::Limit(Limit::String, token.clone()))
+                }
+                _ => Ok(Some(node)),
+            };
+        }
+        OpenBracket => Open::Array(vec![]),
+        OpenCurly => Open::Dict(HashMap::new(), vec![]),
+        _ => return Err(ParserError::UnreachableToken(token.clone())),
+    };
+
+    context.depth(levels + 1, token)?;
+
+    iter.next();
+    stack.push(Frame::new(open, levels));
+
+    Ok(None)
+}
+
+/// Reads the value at the next token, which must exist, `depth` levels down
+///
+/// Dicts and arrays are kept on a stack instead of recursing so nesting is only bounded by
+/// [`ParseOptions::max_depth`]
+pub(crate) fn value(iter: &mut Peekable<Iter<Token>>, context: &mut Context, depth: usize) -> Result<Node, ParserError> {
+    let from = iter.clone();
+
+    let mut stack = vec![];
+    let mut node = spanned(iter, &from, context, &mut stack, depth)?;
+
+    loop {
+        if let Some(done) = node.take() {
+            match stack.last_mut() {
+                Some(frame) => frame.push(done),
+                None => return Ok(done),
+            }
+        }
+
+        let frame = stack.last_mut().unwrap();
+
+        node = match frame.next(iter, context)? {
+            true => spanned(iter, &from, context, &mut stack, depth)?,
+            false => stack.pop().map(|frame| {
+                if let (Some(spans), Some((path, start))) = (&mut context.spans, &frame.span) {
+                    spans.values.push((path.clone(), *start..last(&from, iter).position.end));
+                }
+
+                frame.into_node()
+            }),
+        };
+    }
+}
+
+/// Like [`open`], also recording where a value was written when the parse is spanned,
+/// dicts and arrays once they're closed
+fn spanned<'t>(
+    iter: &mut Peekable<Iter<'t, Token>>,
+    from: &Peekable<Iter<'t, Token>>,
+    context: &mut Context,
+    stack: &mut Vec<Frame>,
+    depth: usize,
+) -> Result<Option<Node>, ParserError> {
+    let Some(spans) = &context.spans else {
+        return open(iter, context, stack, depth);
+    };
+
+    let path = match stack.last() {
+        Some(frame) => frame.path().unwrap_or_default(),
+        None => spans.path.clone(),
+    };
+
+    let start = iter.peek().unwrap().position.offset;
+    let node = open(iter, context, stack, depth)?;
+
+    match (&node, &mut context.spans) {
+        (Some(_), Some(spans)) => spans.values.push((path, start..last(from, iter).position.end)),
+        _ => stack.last_mut().unwrap().span = Some((path, start)),
+    }
+
+    Ok(node)
+}
 
 #[derive(Debug, Clone)]
 pub enum ParserError {
@@ -7,101 +649,261 @@ pub enum ParserError {
     MissingToken(TokenKind, Token),
     MismatchedTokenType(TokenKind, Token),
     InvalidToken(Token),
-    InvalidSymbol(String),
+    InvalidSymbol(Token),
     BrokenString(Token),
     UnclosedString(Token),
     TrailingComma(Token),
-    ScopeInsideDict(Token),
+    MixedSeparators(Token),
+    DuplicateKey(Token),
+    InvalidKey(Token),
     EscapeOutsideOfString(Token),
     UnexpectedEOF(Token),
     UnreachableToken(Token),
+    Lexer(LexerError),
+    Limit(Limit, Token),
+}
+
+impl ParserError {
+    /// The variant name, e.g. `InvalidToken`
+    pub fn name(&self) -> &'static str {
+        use ParserError::*;
+
+        match self {
+            MissingValueAfterDeclaration(_) => "MissingValueAfterDeclaration",
+            MissingToken(_, _) => "MissingToken",
+            MismatchedTokenType(_, _) => "MismatchedTokenType",
+            InvalidToken(_) => "InvalidToken",
+            InvalidSymbol(_) => "InvalidSymbol",
+            BrokenString(_) => "BrokenString",
+            UnclosedString(_) => "UnclosedString",
+            TrailingComma(_) => "TrailingComma",
+            MixedSeparators(_) => "MixedSeparators",
+            DuplicateKey(_) => "DuplicateKey",
+            InvalidKey(_) => "InvalidKey",
+            EscapeOutsideOfString(_) => "EscapeOutsideOfString",
+            UnexpectedEOF(_) => "UnexpectedEOF",
+            UnreachableToken(_) => "UnreachableToken",
+            Lexer(error) => error.name(),
+            Limit(limit, _) => match limit {
+                self::Limit::Input => "InputTooLarge",
+                self::Limit::Depth => "TooDeep",
+                self::Limit::String => "StringTooLong",
+                self::Limit::Array => "ArrayTooLong",
+                self::Limit::Keys => "TooManyKeys",
+                self::Limit::Nodes => "TooManyNodes",
+            },
+        }
+    }
+
+    /// Where the error is, the span of the token it points at
+    ///
+    /// Only strings run over several lines and the parser never points inside one, so the
+    /// span ends on the line it starts
+    pub fn span(&self) -> Option<SourceSpan> {
+        let position = self.token()?.position;
+
+        Some(SourceSpan {
+            file: Default::default(),
+            start: Location {
+                offset: position.offset,
+                line: position.line,
+                column: position.character + 1,
+            },
+            end: Location {
+                offset: position.end,
+                line: position.line,
+                column: position.character + position.length + 1,
+            },
+        })
+    }
+
+    /// The token the error points at, if it has one
+    pub fn token(&self) -> Option<&Token> {
+        use ParserError::*;
+
+        match self {
+            MissingValueAfterDeclaration(token)
+            | MissingToken(_, token)
+            | MismatchedTokenType(_, token)
+            | InvalidToken(token)
+            | InvalidSymbol(token)
+            | BrokenString(token)
+            | UnclosedString(token)
+            | TrailingComma(token)
+            | MixedSeparators(token)
+            | DuplicateKey(token)
+            | InvalidKey(token)
+            | EscapeOutsideOfString(token)
+            | UnexpectedEOF(token)
+            | UnreachableToken(token)
+            | Limit(_, token) => Some(token),
+            Lexer(error) => Some(error.token()),
+        }
+    }
 }
 
 impl std::error::Error for ParserError {}
 
+impl From<LexerError> for ParserError {
+    fn from(value: LexerError) -> Self {
+        Self::Lexer(value)
+    }
+}
+
 impl std::fmt::Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{self:?}")
     }
 }
 
-pub fn parse(tokens: &[Token]) -> Result<Ast, ParserError> {
+/// A top-level `key = value`, under the scopes written before it
+#[derive(Debug, Clone)]
+pub(crate) struct Assignment {
+    pub scopes: Vec<String>,
+    pub key: String,
+    pub node: Node,
+}
+
+impl Assignment {
+    /// Inserts the value the way a full parse would, replacing whatever isn't a dict on its path
+    pub fn apply(self, ast: &mut Ast) {
+        insert(&mut ast.0, self.scopes, self.key, self.node);
+    }
+}
+
+/// Inserts `node` at `key` in the dicts `scopes` lead to, replacing whatever isn't a dict on the way
+fn insert(mut dict: &mut HashMap<String, Node>, scopes: Vec<String>, key: String, node: Node) {
+    for key in scopes {
+        let entry = dict.entry(key).or_insert_with(|| Node::Dict(HashMap::new()));
+
+        if !matches!(entry, Node::Dict(_)) {
+            *entry = Node::Dict(HashMap::new());
+        }
+
+        let Node::Dict(inner) = entry else { unreachable!() };
+        dict = inner;
+    }
+
+    dict.insert(key, node);
+}
+
+/// Whether writing `path` into `dict` replaces something rather than adding to a dict on the way
+fn replaces(mut dict: &HashMap<String, Node>, path: &[String]) -> bool {
+    let Some((key, scopes)) = path.split_last() else {
+        return false;
+    };
+
+    for scope in scopes {
+        match dict.get(scope) {
+            Some(Node::Dict(inner)) => dict = inner,
+            Some(_) => return true,
+            None => return false,
+        }
+    }
+
+    dict.contains_key(key)
+}
+
+/// Parses an [`Ast`], with the warnings of a lenient parse
+pub fn parse(tokens: &[Token], options: &ParseOptions) -> Result<(Ast, Vec<Warning>), ParserError> {
+    let mut context = Context::new(options);
+    let ast = build(tokens, &mut context)?;
+
+    Ok((ast, context.warnings))
+}
+
+/// Parses an [`Ast`] and where its values and keys were written
+pub(crate) fn parse_spanned(tokens: &[Token], options: &ParseOptions) -> Result<(Ast, Vec<Warning>, Spans), ParserError> {
+    let mut context = Context::spanned(options);
+    let ast = build(tokens, &mut context)?;
+
+    Ok((ast, context.warnings, context.spans.unwrap_or_default()))
+}
+
+fn build(tokens: &[Token], context: &mut Context) -> Result<Ast, ParserError> {
     let mut ast = Ast::default();
+    let mut read = vec![];
+
+    assignments(tokens, context, &mut read, |_, _| {})?;
+
+    for assignment in read {
+        assignment.apply(&mut ast);
+    }
+
+    debug!("\x1b[1;33m*\x1b[39m Assignments: \x1b[36m{}\x1b[m", ast.assignments());
 
+    Ok(ast)
+}
+
+/// Reads the top-level assignments in order, keeping the ones before an error
+///
+/// `clean` is called with the number of assignments read so far and a line whenever the
+/// parser reaches the start of that line with nothing pending, where parsing could start over
+pub(crate) fn assignments(
+    tokens: &[Token],
+    context: &mut Context,
+    assignments: &mut Vec<Assignment>,
+    mut clean: impl FnMut(usize, usize),
+) -> Result<(), ParserError> {
     let mut scopes: Option<Vec<String>> = None;
 
     let mut iter = tokens.iter().peekable();
 
-    while let Some(key) = iter.next() {
+    while let Some(&token) = iter.peek() {
         use ParserError::*;
         use TokenKind::*;
 
-        match key.kind {
-            NewLine => {}
-            Symbol(ref symbol) => {
-                if iter.peek().is_none() {
-                    return Err(UnexpectedEOF(key.clone()));
+        match token.kind {
+            NewLine => {
+                iter.next();
+
+                if scopes.is_none() {
+                    clean(assignments.len(), token.position.line + 1);
                 }
+            }
+            Symbol(_) | Quote => {
+                let (symbol, key, end) = self::key(&mut iter)?;
+
+                if let Some(spans) = &mut context.spans {
+                    let path = [scopes.as_deref().unwrap_or_default(), std::slice::from_ref(&symbol)].concat();
 
-                let next = iter.next().unwrap();
+                    spans.keys.push((path.clone(), key.position.offset..end));
+                    spans.path = path;
+                }
+
+                let Some(next) = iter.next() else {
+                    return Err(UnexpectedEOF(key.clone()));
+                };
 
                 match next.kind {
-                    Equals => unsafe {
+                    Equals => {
                         if iter.peek().is_none() {
                             return Err(MissingValueAfterDeclaration(key.clone()));
                         }
 
-                        let node = Node::try_from(&mut iter)?;
+                        context.key(key)?;
 
-                        if let Some(ref mut keys) = scopes {
-                            keys.push(symbol.clone());
+                        let depth = scopes.as_ref().map_or(0, Vec::len);
+                        let node = value(&mut iter, context, depth)?;
 
-                            let mut root = Node::Dict(HashMap::new());
+                        let scopes = scopes.take().unwrap_or_default();
+                        let path = [&scopes[..], std::slice::from_ref(&symbol)].concat();
+                        context.write(path, &node, key)?;
 
-                            {
-                                let mut child = &mut root;
-                                let mut scopes = keys.iter().skip(1).peekable();
-
-                                while let Some(scope) = scopes.next() {
-                                    if let Node::Dict(ref mut dict) = child {
-                                        if scopes.peek().is_some() {
-                                            dict.insert(scope.clone(), Node::Dict(HashMap::new()));
-
-                                            child = (*(dict as *mut HashMap<String, Node>))
-                                                .get_mut(scope)
-                                                .unwrap();
-                                        } else {
-                                            dict.insert(scope.clone(), node.clone());
-                                        }
-                                    }
-                                }
-                            }
-
-                            let mut dict = &mut ast.0;
-                            for key in keys {
-                                match dict.get_mut(key) {
-                                    Some(Node::Dict(inner)) => {
-                                        dict = &mut *(inner as *mut _);
-                                        continue;
-                                    }
-                                    _ => {
-                                        dict.insert(key.clone(), root);
-                                        break;
-                                    }
-                                }
-                            }
-
-                            scopes = None;
-                        } else {
-                            ast.0.insert(symbol.clone(), node);
-                        }
-                    },
+                        assignments.push(Assignment {
+                            scopes,
+                            key: symbol,
+                            node,
+                        });
+                    }
                     Colon => match iter.next() {
                         Some(next) => match next.kind {
-                            Colon => match scopes {
-                                Some(ref mut scopes) => scopes.push(symbol.clone()),
-                                None => scopes = Some(vec![symbol.clone()]),
-                            },
+                            Colon => {
+                                let scopes = scopes.get_or_insert_with(Vec::new);
+                                scopes.push(symbol);
+
+                                context.depth(scopes.len(), key)?;
+                            }
                             _ => return Err(MismatchedTokenType(TokenKind::Colon, key.clone())),
                         },
                         None => return Err(UnexpectedEOF(next.clone())),
@@ -109,15 +911,13 @@ pub fn parse(tokens: &[Token]) -> Result<Ast, ParserError> {
                     _ => return Err(UnreachableToken(key.clone())),
                 }
             }
-            Dot => return Err(InvalidToken(key.clone())),
-            BackSlash => return Err(EscapeOutsideOfString(key.clone())),
-            _ => return Err(UnreachableToken(key.clone())),
+            Dot => return Err(InvalidToken(token.clone())),
+            BackSlash => return Err(EscapeOutsideOfString(token.clone())),
+            _ => return Err(UnreachableToken(token.clone())),
         }
     }
 
-    debug!("\x1b[1;33m*\x1b[39m Assignments: \x1b[36m{}\x1b[m", ast.assignments());
-
-    Ok(ast)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -159,6 +959,171 @@ mod tests {
             ("varb".into(), Node::Dict(HashMap::from([("nested".into(), Node::Null)]))),
         ]);
 
-        assert_eq!(parse(tokens).unwrap().0, expected);
+        assert_eq!(parse(tokens, &Default::default()).unwrap().0.0, expected);
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::Kfg;
+
+        let limit = |source: &str, options: ParseOptions| match Kfg::parse_with(source.as_bytes(), &options) {
+            Err(ParserError::Limit(limit, token)) => (limit, token.position.line, token.position.character),
+            result => panic!("{result:?}"),
+        };
+
+        let options = ParseOptions { max_input: Some(12), ..Default::default() };
+        assert_eq!(limit("a = 1\nb = 22\n", options), (Limit::Input, 2, 6));
+
+        let options = ParseOptions { max_depth: 3, ..Default::default() };
+        assert!(Kfg::parse_with(b"a::b = [{ .c: 1 }]", &options).is_ok());
+        assert_eq!(limit("a::b = [{ .c: [1] }]", options.clone()), (Limit::Depth, 1, 14));
+        assert_eq!(limit("a::b::c::d::e = 1", options), (Limit::Depth, 1, 9));
+
+        let options = ParseOptions { max_string: Some(3), ..Default::default() };
+        assert_eq!(limit("a = 'abc'\nb = 'abcd'", options), (Limit::String, 2, 4));
+
+        let options = ParseOptions { max_array: Some(2), ..Default::default() };
+        assert_eq!(limit("a = [[1, 2], 3, 4]", options), (Limit::Array, 1, 16));
+
+        let options = ParseOptions { max_keys: Some(3), ..Default::default() };
+        assert_eq!(limit("a = 1\nb = { .c: 1, .d: 2 }", options), (Limit::Keys, 2, 14));
+
+        let options = ParseOptions { max_nodes: Some(3), ..Default::default() };
+        assert_eq!(limit("a = [1, 2]\nb = 3", options), (Limit::Nodes, 2, 4));
+
+        let deep = format!("a = {}", "[".repeat(100_000));
+        assert_eq!(limit(&deep, Default::default()), (Limit::Depth, 1, 132));
+
+        let name = ParserError::Limit(Limit::Nodes, Default::default()).name();
+        assert_eq!(name, "TooManyNodes");
+    }
+
+    #[test]
+    fn test_modes() {
+        use crate::{Kfg, LexerError};
+
+        let strict = |source: &str| match Kfg::parse_with(source.as_bytes(), &ParseOptions::strict()) {
+            Err(ParserError::Lexer(error)) => (error.name(), error.token().position.line, error.token().position.character),
+            Err(error) => (error.name(), error.token().unwrap().position.line, error.token().unwrap().position.character),
+            Ok(_) => ("Ok", 0, 0),
+        };
+
+        assert_eq!(strict("a = [\n  1,\n  2,\n]\nb = {\n  .c: 1\n  .d: [1, 2]\n}"), ("TrailingComma", 3, 3));
+        assert_eq!(strict("a = [\n  1,\n  2\n]\nb = {\n  .c: 1\n  .d: [1, 2]\n}"), ("Ok", 0, 0));
+        assert_eq!(strict("a = { .b: 1, .c: 2\n .d: 3 }"), ("MixedSeparators", 2, 2));
+        assert_eq!(strict("a = [1 2]"), ("MissingToken", 1, 7));
+        assert_eq!(strict("a = [1,, 2]"), ("InvalidToken", 1, 7));
+        assert_eq!(strict("a = 'x\\qy'"), ("UnknownEscape", 1, 6));
+        assert_eq!(strict("a = 1\n\tb = 2"), ("TabIndentation", 2, 0));
+        assert_eq!(strict("a = '\t'\nb = 2\t// tab"), ("Ok", 0, 0));
+        assert_eq!(strict("a = { .b: 1, .b: 2 }"), ("DuplicateKey", 1, 14));
+        assert_eq!(strict("a::b = 1\na::b = 2"), ("DuplicateKey", 2, 3));
+        assert_eq!(strict("a = { .b: 1 }\na::b = 2"), ("DuplicateKey", 2, 3));
+        assert_eq!(strict("a::b = 1\na = 2"), ("DuplicateKey", 2, 0));
+        assert_eq!(strict("a = 1\na::b = 2"), ("DuplicateKey", 2, 3));
+        assert_eq!(strict("a = { .b: 1 }\na::c = 2\nd = [{ .e: 1 }, { .e: 2 }]"), ("Ok", 0, 0));
+
+        assert!(Kfg::parse(b"a = [1, 2,]\nb = { .c: 1, .c: 2 }").is_ok());
+        assert!(matches!(Kfg::parse(b"a = yes"), Err(ParserError::InvalidSymbol(_))));
+
+        let span = |source: &str| Kfg::parse(source.as_bytes()).unwrap_err().span().map(|span| (span.start.line, span.start.column, span.end.column));
+
+        assert_eq!(span("a = 1\nb = \"x\" c"), Some((2, 5, 8)));
+        assert_eq!(span("a = [1, 2.x]"), Some((1, 9, 12)));
+
+        let (ast, warnings) = Kfg::parse_with(b"a = yes\r\nb = [on, off, no, ~]\r\nhost = localhost\r\nc = 'x'", &ParseOptions::lenient()).unwrap();
+
+        assert_eq!(ast.lookup("a"), Some(&Node::Bool(true)));
+        assert_eq!(ast.lookup("b"), Some(&Node::Array(vec![Node::Bool(true), Node::Bool(false), Node::Bool(false), Node::Null])));
+        assert_eq!(ast.lookup("host"), Some(&Node::String("localhost".into())));
+        assert_eq!(ast.lookup("c"), Some(&Node::String("x".into())));
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].name(), warnings[0].token().position.line, warnings[0].token().position.character), ("UnquotedString", 3, 7));
+
+        assert_eq!(LexerError::TabIndentation(Default::default()).message(), "tabs used for indentation");
+    }
+
+    #[test]
+    fn test_keys() {
+        use crate::Kfg;
+
+        let ast = Kfg::parse(b"'api.example.com' = 1\n'a b'::c-d = { .'x:y': 2, .true: 3 }\n_e = { .'': r'\\' }").unwrap();
+
+        assert_eq!(ast.0["api.example.com"], Node::Integer(1));
+        assert_eq!(ast.lookup("a b::c-d::true"), Some(&Node::Integer(3)));
+        assert!(matches!(ast.lookup("a b::c-d"), Some(Node::Dict(dict)) if dict["x:y"] == Node::Integer(2)));
+        assert_eq!(ast.lookup("_e::"), Some(&Node::String("\\".into())));
+
+        let invalid = |source: &str| match Kfg::parse(source.as_bytes()) {
+            Err(ParserError::InvalidKey(token)) => (token.kind.as_str().to_string(), token.position.character),
+            result => panic!("{result:?}"),
+        };
+
+        assert_eq!(invalid("1st = 1"), ("1st".into(), 0));
+        assert_eq!(invalid("a::$b = 1"), ("$b".into(), 3));
+        assert_eq!(invalid("a = { .-c: 1 }"), ("-c".into(), 7));
+        assert_eq!(invalid("a = { .'b::c': 1 }"), ("'".into(), 7));
+        assert_eq!(invalid("r'a::b'::c = 1"), ("'".into(), 0));
+    }
+
+    #[test]
+    fn test_scopes() {
+        use crate::Kfg;
+
+        // a scoped assignment adds to the dicts it goes through instead of nesting another one
+        let ast = Kfg::parse(b"a::b = 1\na::c = 2\nx::y::z = 3\nx::y::w = 4").unwrap();
+
+        assert_eq!(ast.lookup("a"), Kfg::parse(b"a = { .b: 1 .c: 2 }").unwrap().lookup("a"));
+        assert_eq!(ast.lookup("x"), Kfg::parse(b"x = { .y: { .z: 3 .w: 4 } }").unwrap().lookup("x"));
+    }
+
+    #[test]
+    fn test_decimal_point() {
+        use crate::Kfg;
+
+        let ast = Kfg::parse(b"a = { .port: 80 .host: 'a' }\nb = { .ratio: 0.5 }\nc = [1, 2.5]").unwrap();
+
+        assert_eq!(ast.lookup("a::port"), Some(&Node::Integer(80)));
+        assert_eq!(ast.lookup("a::host"), Some(&Node::String("a".into())));
+        assert_eq!(ast.lookup("b::ratio"), Some(&Node::Float(0.5)));
+        assert_eq!(ast.lookup("c"), Some(&Node::Array(vec![Node::Integer(1), Node::Float(2.5)])));
+        assert!(Kfg::parse(b"a = 80 .5").is_err());
+    }
+
+    #[test]
+    fn test_dict_scopes() {
+        use crate::Kfg;
+
+        let ast = Kfg::parse(b"a = { .server::port: 80, .server::tls::enabled: true, .server::'tls'::cert: 'c', .x: 1 }").unwrap();
+
+        assert_eq!(ast.lookup("a::server::port"), Some(&Node::Integer(80)));
+        assert_eq!(ast.lookup("a::server::tls::enabled"), Some(&Node::Bool(true)));
+        assert_eq!(ast.lookup("a::server::tls::cert"), Some(&Node::String("c".into())));
+        assert_eq!(ast.lookup("a::x"), Some(&Node::Integer(1)));
+
+        // like scoped assignments, a later entry replaces what isn't a dict and adds to what is
+        let ast = Kfg::parse(b"a = { .b: 1, .b::c: 2, .d: { .e: 3 }, .d::f: 4 }").unwrap();
+        assert_eq!(ast.lookup("a"), Kfg::parse(b"a = { .b: { .c: 2 }, .d: { .e: 3, .f: 4 } }").unwrap().lookup("a"));
+
+        let strict = |source: &str| Kfg::parse_with(source.as_bytes(), &ParseOptions::strict()).map_err(|error| error.name());
+
+        assert!(strict("a = { .b: { .c: 1 }\n .b::d: 2\n .e::f: 3\n .e::g: 4 }").is_ok());
+        assert_eq!(strict("a = { .b::c: 1, .b::c: 2 }").err(), Some("DuplicateKey"));
+        assert_eq!(strict("a = { .b: 1, .b::c: 2 }").err(), Some("DuplicateKey"));
+        assert_eq!(strict("a = { .b::c: 1, .b: 2 }").err(), Some("DuplicateKey"));
+
+        let options = ParseOptions { max_depth: 3, ..Default::default() };
+        assert!(Kfg::parse_with(b"a = { .b::c::d: 1, .e::f: [1] }", &options).is_ok());
+        assert!(matches!(Kfg::parse_with(b"a = { .b::c::d::e: 1 }", &options), Err(ParserError::Limit(Limit::Depth, _))));
+        assert!(matches!(Kfg::parse_with(b"a = { .b::c: [[1]] }", &options), Err(ParserError::Limit(Limit::Depth, _))));
+
+        let options = ParseOptions { max_depth: usize::MAX, ..Default::default() };
+        let nested = |depth: usize| format!("a = {}{}", "[".repeat(depth), "]".repeat(depth));
+
+        let ast = Kfg::parse_with(nested(MAX_DEPTH).as_bytes(), &options).unwrap().0;
+        let written = Kfg::parse_with(ast.to_kfg().as_bytes(), &options).unwrap().0;
+        assert!(written.lookup("a") == ast.lookup("a") && ast.to_json().contains(&"[".repeat(MAX_DEPTH)));
+        assert!(matches!(Kfg::parse_with(nested(MAX_DEPTH + 1).as_bytes(), &options), Err(ParserError::Limit(Limit::Depth, _))));
     }
 }
@@ -0,0 +1,447 @@
+use crate::{
+    diff::{align, Step},
+    Ast, Node,
+};
+use std::collections::{BTreeSet, HashMap};
+
+/// A single patch operation, paths are `key::path`s and numeric segments index into arrays
+///
+/// `add` into an array inserts before the index, `-` or the array length appends
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Add { path: String, value: Node },
+    Remove { path: String },
+    Replace { path: String, value: Node },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Node },
+}
+
+impl Operation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Add { .. } => "add",
+            Operation::Remove { .. } => "remove",
+            Operation::Replace { .. } => "replace",
+            Operation::Move { .. } => "move",
+            Operation::Copy { .. } => "copy",
+            Operation::Test { .. } => "test",
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            Operation::Add { path, .. }
+            | Operation::Remove { path }
+            | Operation::Replace { path, .. }
+            | Operation::Move { path, .. }
+            | Operation::Copy { path, .. }
+            | Operation::Test { path, .. } => path,
+        }
+    }
+}
+
+/// Why a patch couldn't be read or applied, the first field is the index of the operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    Invalid(usize, String),
+    EmptyPath(usize),
+    MissingPath(usize, String),
+    NotAContainer(usize, String),
+    InvalidIndex(usize, String),
+    MoveIntoItself(usize, String),
+    TestFailed(usize, String),
+}
+
+impl PatchError {
+    /// The index of the operation that failed
+    pub fn index(&self) -> usize {
+        match self {
+            PatchError::Invalid(index, _)
+            | PatchError::EmptyPath(index)
+            | PatchError::MissingPath(index, _)
+            | PatchError::NotAContainer(index, _)
+            | PatchError::InvalidIndex(index, _)
+            | PatchError::MoveIntoItself(index, _)
+            | PatchError::TestFailed(index, _) => *index,
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// An ordered list of operations, applied all at once or not at all
+///
+/// In a document the operations are an array of dicts under the `patch` key
+/// ```text
+/// patch = [
+///     { .op: 'replace' .path: 'server::port' .value: 8080 },
+///     { .op: 'move' .from: 'hosts::0' .path: 'backup' },
+/// ]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch(pub Vec<Operation>);
+
+impl Patch {
+    /// Reads the operations under the `patch` key of a document
+    pub fn from_ast(ast: &Ast) -> Result<Self, PatchError> {
+        let Some(Node::Array(operations)) = ast.get("patch") else {
+            return Err(PatchError::Invalid(0, "expected an array of operations under 'patch'".into()));
+        };
+
+        operations
+            .iter()
+            .enumerate()
+            .map(|(index, node)| operation(index, node))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// Writes the operations under the `patch` key, the inverse of [`Patch::from_ast`]
+    pub fn to_ast(&self) -> Ast {
+        let operations = self.0.iter().map(to_node).collect();
+
+        Ast([("patch".to_string(), Node::Array(operations))].into(), Default::default())
+    }
+
+    /// Builds the patch that turns `from` into `to`
+    ///
+    /// Operations are ordered so every path is valid at the moment it's applied
+    pub fn from_diff(from: &Ast, to: &Ast) -> Self {
+        let mut operations = vec![];
+        dict(&mut vec![], &from.0, &to.0, &mut operations);
+
+        Self(operations)
+    }
+}
+
+fn operation(index: usize, node: &Node) -> Result<Operation, PatchError> {
+    let Node::Dict(dict) = node else {
+        return Err(PatchError::Invalid(index, "an operation must be a dict".into()));
+    };
+
+    let string = |key: &str| match dict.get(key) {
+        Some(Node::String(value)) => Ok(value.clone()),
+        _ => Err(PatchError::Invalid(index, format!("'{key}' must be a string"))),
+    };
+
+    let value = || {
+        dict.get("value")
+            .cloned()
+            .ok_or_else(|| PatchError::Invalid(index, "'value' is missing".into()))
+    };
+
+    let path = string("path")?;
+
+    let operation = match string("op")?.as_str() {
+        "add" => Operation::Add { path, value: value()? },
+        "remove" => Operation::Remove { path },
+        "replace" => Operation::Replace { path, value: value()? },
+        "move" => Operation::Move { from: string("from")?, path },
+        "copy" => Operation::Copy { from: string("from")?, path },
+        "test" => Operation::Test { path, value: value()? },
+        op => return Err(PatchError::Invalid(index, format!("unknown operation '{op}'"))),
+    };
+
+    Ok(operation)
+}
+
+fn to_node(operation: &Operation) -> Node {
+    let mut dict = HashMap::from([
+        ("op".to_string(), Node::String(operation.name().into())),
+        ("path".to_string(), Node::String(operation.path().into())),
+    ]);
+
+    match operation {
+        Operation::Add { value, .. } | Operation::Replace { value, .. } | Operation::Test { value, .. } => {
+            dict.insert("value".into(), value.clone());
+        }
+        Operation::Move { from, .. } | Operation::Copy { from, .. } => {
+            dict.insert("from".into(), Node::String(from.clone()));
+        }
+        Operation::Remove { .. } => {}
+    }
+
+    Node::Dict(dict)
+}
+
+fn join(path: &[String], segment: impl ToString) -> String {
+    path.iter()
+        .cloned()
+        .chain(std::iter::once(segment.to_string()))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn node(path: &mut Vec<String>, from: &Node, to: &Node, operations: &mut Vec<Operation>) {
+    match (from, to) {
+        (Node::Dict(from), Node::Dict(to)) => dict(path, from, to, operations),
+        (Node::Array(from), Node::Array(to)) => array(path, from, to, operations),
+        (from, to) if from != to => operations.push(Operation::Replace {
+            path: path.join("::"),
+            value: to.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn dict(path: &mut Vec<String>, from: &HashMap<String, Node>, to: &HashMap<String, Node>, operations: &mut Vec<Operation>) {
+    let keys = from.keys().chain(to.keys()).collect::<BTreeSet<_>>();
+
+    for key in keys {
+        match (from.get(key), to.get(key)) {
+            (Some(from), Some(to)) => {
+                path.push(key.clone());
+                node(path, from, to, operations);
+                path.pop();
+            }
+            (Some(_), None) => operations.push(Operation::Remove { path: join(path, key) }),
+            (None, Some(to)) => operations.push(Operation::Add {
+                path: join(path, key),
+                value: to.clone(),
+            }),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+/// Walks the alignment left to right, `k` is where the next element sits in the
+/// array as patched so far
+fn array(path: &mut Vec<String>, from: &[Node], to: &[Node], operations: &mut Vec<Operation>) {
+    let mut k = 0;
+
+    for step in align(from, to) {
+        match step {
+            Step::Same(..) => k += 1,
+            Step::Paired(i, j) => {
+                path.push(k.to_string());
+                node(path, &from[i], &to[j], operations);
+                path.pop();
+                k += 1;
+            }
+            Step::Removed(_) => operations.push(Operation::Remove { path: join(path, k) }),
+            Step::Added(j) => {
+                operations.push(Operation::Add {
+                    path: join(path, k),
+                    value: to[j].clone(),
+                });
+                k += 1;
+            }
+        }
+    }
+}
+
+/// Splits a path into its parent segments and the last one
+fn split(index: usize, path: &str) -> Result<(Vec<&str>, &str), PatchError> {
+    let mut segments = path.split("::").collect::<Vec<_>>();
+
+    match segments.pop() {
+        Some(last) if !path.is_empty() => Ok((segments, last)),
+        _ => Err(PatchError::EmptyPath(index)),
+    }
+}
+
+fn get<'a>(root: &'a Node, index: usize, path: &str) -> Result<&'a Node, PatchError> {
+    let (parents, last) = split(index, path)?;
+    let mut node = root;
+
+    for segment in parents.into_iter().chain(std::iter::once(last)) {
+        node = match node {
+            Node::Dict(dict) => dict.get(segment),
+            Node::Array(array) => segment.parse::<usize>().ok().and_then(|i| array.get(i)),
+            _ => None,
+        }
+        .ok_or_else(|| PatchError::MissingPath(index, path.into()))?;
+    }
+
+    Ok(node)
+}
+
+/// Returns the container holding the last segment of `path`
+fn parent<'a>(root: &'a mut Node, index: usize, path: &'a str) -> Result<(&'a mut Node, &'a str), PatchError> {
+    let (parents, last) = split(index, path)?;
+    let mut node = root;
+
+    for segment in parents {
+        node = match node {
+            Node::Dict(dict) => dict.get_mut(segment),
+            Node::Array(array) => segment.parse::<usize>().ok().and_then(|i| array.get_mut(i)),
+            _ => None,
+        }
+        .ok_or_else(|| PatchError::MissingPath(index, path.into()))?;
+    }
+
+    Ok((node, last))
+}
+
+fn position(index: usize, path: &str, segment: &str, len: usize) -> Result<usize, PatchError> {
+    segment
+        .parse::<usize>()
+        .ok()
+        .filter(|&i| i < len)
+        .ok_or_else(|| PatchError::InvalidIndex(index, path.into()))
+}
+
+fn add(root: &mut Node, index: usize, path: &str, value: Node) -> Result<(), PatchError> {
+    match parent(root, index, path)? {
+        (Node::Dict(dict), last) => {
+            dict.insert(last.into(), value);
+        }
+        (Node::Array(array), "-") => array.push(value),
+        (Node::Array(array), last) => {
+            let i = position(index, path, last, array.len() + 1)?;
+            array.insert(i, value);
+        }
+        _ => return Err(PatchError::NotAContainer(index, path.into())),
+    }
+
+    Ok(())
+}
+
+fn remove(root: &mut Node, index: usize, path: &str) -> Result<Node, PatchError> {
+    match parent(root, index, path)? {
+        (Node::Dict(dict), last) => dict
+            .remove(last)
+            .ok_or_else(|| PatchError::MissingPath(index, path.into())),
+        (Node::Array(array), last) => {
+            let i = position(index, path, last, array.len())?;
+            Ok(array.remove(i))
+        }
+        _ => Err(PatchError::NotAContainer(index, path.into())),
+    }
+}
+
+fn apply(root: &mut Node, index: usize, operation: &Operation) -> Result<(), PatchError> {
+    match operation {
+        Operation::Add { path, value } => add(root, index, path, value.clone()),
+        Operation::Remove { path } => remove(root, index, path).map(drop),
+        Operation::Replace { path, value } => {
+            remove(root, index, path)?;
+            add(root, index, path, value.clone())
+        }
+        Operation::Move { from, path } => {
+            if path.strip_prefix(from.as_str()).is_some_and(|rest| rest.starts_with("::")) {
+                return Err(PatchError::MoveIntoItself(index, path.clone()));
+            }
+
+            let value = remove(root, index, from)?;
+            add(root, index, path, value)
+        }
+        Operation::Copy { from, path } => {
+            let value = get(root, index, from)?.clone();
+            add(root, index, path, value)
+        }
+        Operation::Test { path, value } => match get(root, index, path)? == value {
+            true => Ok(()),
+            false => Err(PatchError::TestFailed(index, path.clone())),
+        },
+    }
+}
+
+impl Ast {
+    /// Applies every operation of `patch` in order
+    ///
+    /// The tree is left untouched when any operation fails, the error has its index
+    pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), PatchError> {
+        let mut root = Node::Dict(self.0.clone());
+
+        for (index, operation) in patch.0.iter().enumerate() {
+            apply(&mut root, index, operation)?;
+        }
+
+        if let Node::Dict(dict) = root {
+            self.0 = dict;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Kfg;
+
+    #[test]
+    fn test_apply_patch() {
+        let mut ast = Kfg::parse(b"
+            server = { .port: 80 .hosts: ['a', 'b'] }
+            debug = true
+        ").unwrap();
+
+        let patch = Patch::from_ast(&Kfg::parse(b"
+            patch = [
+                { .op: 'test' .path: 'server::port' .value: 80 },
+                { .op: 'replace' .path: 'server::port' .value: 8080 },
+                { .op: 'add' .path: 'server::hosts::1' .value: 'c' },
+                { .op: 'add' .path: 'server::hosts::-' .value: 'd' },
+                { .op: 'move' .from: 'debug' .path: 'server::debug' },
+                { .op: 'copy' .from: 'server::hosts::0' .path: 'primary' },
+                { .op: 'remove' .path: 'server::hosts::2' },
+            ]
+        ").unwrap()).unwrap();
+
+        ast.apply_patch(&patch).unwrap();
+
+        let expected = Kfg::parse(b"
+            server = { .port: 8080 .hosts: ['a', 'c', 'd'] .debug: true }
+            primary = 'a'
+        ").unwrap();
+
+        assert_eq!(ast.0, expected.0);
+        assert_eq!(Patch::from_ast(&patch.to_ast()).unwrap(), patch);
+
+        let failing = Patch(vec![
+            Operation::Remove { path: "primary".into() },
+            Operation::Test { path: "server::port".into(), value: Node::Integer(80) },
+        ]);
+
+        assert_eq!(ast.apply_patch(&failing), Err(PatchError::TestFailed(1, "server::port".into())));
+        assert_eq!(ast.0, expected.0);
+
+        let errors = [
+            (Operation::Remove { path: "missing".into() }, PatchError::MissingPath(0, "missing".into())),
+            (Operation::Remove { path: "server::hosts::9".into() }, PatchError::InvalidIndex(0, "server::hosts::9".into())),
+            (Operation::Add { path: "primary::x".into(), value: Node::Null }, PatchError::NotAContainer(0, "primary::x".into())),
+            (Operation::Move { from: "server".into(), path: "server::inner".into() }, PatchError::MoveIntoItself(0, "server::inner".into())),
+            (Operation::Add { path: "".into(), value: Node::Null }, PatchError::EmptyPath(0)),
+        ];
+
+        for (operation, error) in errors {
+            assert_eq!(ast.apply_patch(&Patch(vec![operation])), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_from_diff() {
+        let from = Kfg::parse(b"
+            list = [1, 2, 3, 4, 5]
+            nested = [{ .a: [1, 2] }, 'x', 'y']
+            server = { .port: 80 .old: true }
+            kind = 'a'
+        ").unwrap();
+
+        let to = Kfg::parse(b"
+            list = [0, 1, 9, 4, 6, 7]
+            nested = ['w', { .a: [2, 3] }, 'y']
+            server = { .port: 8080 .new: [] }
+            kind = { .name: 'a' }
+            added = null
+        ").unwrap();
+
+        let patch = Patch::from_diff(&from, &to);
+        let mut patched = from.clone();
+
+        patched.apply_patch(&patch).unwrap();
+
+        assert_eq!(patched.0, to.0);
+        assert!(Patch::from_diff(&to, &to).0.is_empty());
+    }
+}
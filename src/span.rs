@@ -0,0 +1,202 @@
+use crate::{layer::Origin, parser::Spans, Ast, Node};
+use std::{collections::HashMap, ops::Range, path::PathBuf};
+
+/// The byte offset every line starts at, lines end with `\n`, `\r\n` or a lone `\r`
+pub fn line_starts(source: &str) -> Vec<usize> {
+    let bytes = source.as_bytes();
+
+    let ends = bytes.iter().enumerate().filter(|&(i, &byte)| match byte {
+        b'\n' => true,
+        b'\r' => bytes.get(i + 1) != Some(&b'\n'),
+        _ => false,
+    });
+
+    std::iter::once(0).chain(ends.map(|(i, _)| i + 1)).collect()
+}
+
+/// The line ending a source uses, the first one written or else `\n`
+pub(crate) fn line_ending(source: &str) -> &'static str {
+    match source.find(['\n', '\r']).map(|i| &source[i..]) {
+        Some(rest) if rest.starts_with("\r\n") => "\r\n",
+        Some(rest) if rest.starts_with('\r') => "\r",
+        _ => "\n",
+    }
+}
+
+/// Names the file a span points into, handed out by [`Files`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub usize);
+
+/// A point in a source file, lines and columns count from 1 and columns count chars
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Where something was written, it can run over several lines
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub file: FileId,
+    pub start: Location,
+    pub end: Location,
+}
+
+impl SourceSpan {
+    /// The bytes it covers
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+}
+
+impl std::fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.start.line, self.start.column)
+    }
+}
+
+/// The files spans point into
+#[derive(Debug, Default, Clone)]
+pub struct Files(Vec<PathBuf>);
+
+impl Files {
+    pub fn add(&mut self, path: impl Into<PathBuf>) -> FileId {
+        self.0.push(path.into());
+        FileId(self.0.len() - 1)
+    }
+
+    pub fn path(&self, file: FileId) -> Option<&PathBuf> {
+        self.0.get(file.0)
+    }
+}
+
+/// Where the keys and values of an [`Ast`] came from, by `key::path`
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Sources {
+    keys: HashMap<String, SourceSpan>,
+    values: HashMap<String, SourceSpan>,
+    /// Every value written at a path by [`Layers`](crate::Layers), in the order they were
+    pub origins: HashMap<String, Vec<Origin>>,
+    pub docs: HashMap<String, String>,
+}
+
+impl Sources {
+    /// Spans of the values and keys the parser found in `source`, the last write of a path
+    /// is the one kept
+    ///
+    /// Anything else already known about `ast` is kept
+    pub fn new(source: &str, file: FileId, ast: &Ast, found: Spans) -> Self {
+        let lines = line_starts(source);
+
+        let location = |offset: usize| {
+            let line = lines.partition_point(|&start| start <= offset) - 1;
+
+            Location {
+                offset,
+                line: line + 1,
+                column: source[lines[line]..offset].chars().count() + 1,
+            }
+        };
+
+        let span = |range: Range<usize>| SourceSpan {
+            file,
+            start: location(range.start),
+            end: location(range.end),
+        };
+
+        let mut spans = ast.1.clone();
+
+        for (path, range) in found.values {
+            let path = path.join("::");
+
+            match ast.lookup(&path) {
+                // a scoped key turned what was written into a dict
+                Some(Node::Dict(_)) if !source[range.start..].starts_with('{') => spans.values.remove(&path),
+                Some(_) => spans.values.insert(path, span(range)),
+                // replaced by a later assignment
+                None => None,
+            };
+        }
+
+        for (path, range) in found.keys {
+            let path = path.join("::");
+
+            if ast.lookup(&path).is_some() {
+                spans.keys.insert(path, span(range));
+            }
+        }
+
+        spans
+    }
+}
+
+impl Ast {
+    /// Where the value at a `key::path` was written, only known after [`Kfg::parse_spanned`](crate::Kfg::parse_spanned)
+    ///
+    /// A dict made of scoped keys has no literal, its span is the last key that wrote into it
+    pub fn span_of(&self, path: &str) -> Option<SourceSpan> {
+        self.1.values.get(path).or_else(|| self.1.keys.get(path)).copied()
+    }
+
+    /// Where the key of a `key::path` was last written, array elements have none
+    pub fn key_span_of(&self, path: &str) -> Option<SourceSpan> {
+        self.1.keys.get(path).copied()
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use crate::Kfg;
+    use super::*;
+
+    #[test]
+    fn test_spans() {
+        let source = "port = 80\nserver = {\n  .host: 'é'\n  .ports: [1, 2]\n}\nserver::tls::enabled = true\nold = 1\nold = { .new: 2 }\n";
+        let ast = Kfg::parse_spanned(source.as_bytes(), FileId(3)).unwrap();
+
+        let text = |span: SourceSpan| &source[span.range()];
+
+        assert_eq!(text(ast.span_of("port").unwrap()), "80");
+        assert_eq!(text(ast.key_span_of("port").unwrap()), "port");
+
+        let server = ast.span_of("server").unwrap();
+        assert_eq!(server.file, FileId(3));
+        assert_eq!((server.start.line, server.start.column, server.end.line, server.end.column), (2, 10, 5, 2));
+
+        let ports = ast.span_of("server::ports::1").unwrap();
+        assert_eq!(text(ports), "2");
+        assert_eq!((ports.start.line, ports.start.column), (4, 15));
+        assert_eq!(ast.key_span_of("server::ports::1"), None);
+
+        let host = ast.span_of("server::host").unwrap();
+        assert_eq!((text(host), host.end.column), ("'é'", 13));
+
+        assert_eq!(text(ast.span_of("server::tls").unwrap()), "tls");
+        assert_eq!(text(ast.span_of("server::tls::enabled").unwrap()), "true");
+        assert_eq!(text(ast.span_of("old").unwrap()), "{ .new: 2 }");
+        assert_eq!(ast.span_of("missing"), None);
+
+        assert_eq!(Kfg::parse(source.as_bytes()).unwrap().span_of("port"), None);
+
+        let source = "'k é'::v = [\n  1,\n  { .'w x': 2 },\n]";
+        let ast = Kfg::parse_spanned(source.as_bytes(), FileId(0)).unwrap();
+        let text = |span: SourceSpan| &source[span.range()];
+
+        assert_eq!(text(ast.key_span_of("k é").unwrap()), "'k é'");
+        assert_eq!(text(ast.key_span_of("k é::v::1::w x").unwrap()), "'w x'");
+        assert_eq!(text(ast.span_of("k é::v::1").unwrap()), "{ .'w x': 2 }");
+        assert_eq!(ast.span_of("k é::v").unwrap().end.line, 4);
+
+        let source = "a = 'é'\nb = .";
+        let error = Kfg::parse(source.as_bytes()).unwrap_err().span().unwrap();
+
+        assert_eq!((error.start.offset, error.end.offset), (source.len() - 1, source.len()));
+        assert_eq!((error.start.line, error.start.column, error.end.column), (2, 5, 6));
+
+        let mut files = Files::default();
+        let id = files.add("a.kfg");
+        assert_eq!(files.path(id), Some(&PathBuf::from("a.kfg")));
+    }
+}
@@ -0,0 +1,813 @@
+use crate::{ast::ParseTokens, edit::Span, lexer, parser, span, Node, TokenKind};
+
+/// What a piece of a source file is, for highlighting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// The last segment of the key of an assignment, `c` in `a::b::c = 1`
+    Key,
+    /// The segments before it, `a` and `b` in `a::b::c = 1` or in `{ .a::b::c: 1 }`
+    ScopeSegment,
+    /// A key inside a dict, `key` in `{ .key: 1 }` and `c` in `{ .a::b::c: 1 }`
+    DictKey,
+    String,
+    Escape,
+    Integer,
+    Float,
+    Boolean,
+    Null,
+    Comment,
+    Punctuation,
+    Invalid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Highlight {
+    pub category: Category,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    Word(String),
+    Punct(TokenKind),
+    NewLine,
+    Comment,
+    /// The pieces of a string, quotes included, or `None` if it's never closed
+    String(Option<Vec<Highlight>>),
+}
+
+/// Splits a source file into words, punctuation, comments and strings, with their spans
+///
+/// Characters are grouped the same way the lexer does, only comments and strings are kept whole
+fn lex(source: &str) -> Vec<(Lexeme, Span)> {
+    let mut lexemes = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    let span = |start: usize, end: usize| Span { start, end };
+
+    while let Some((start, char)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+
+        let lexeme = match (TokenKind::from(char), next) {
+            (TokenKind::Space | TokenKind::Tab, _) => continue,
+            (TokenKind::NewLine, _) => Lexeme::NewLine,
+            (TokenKind::Slash, Some('/')) => {
+                while chars.next_if(|&(_, c)| !matches!(c, '\n' | '\r')).is_some() {}
+                Lexeme::Comment
+            }
+            (TokenKind::Slash, Some('*')) => {
+                chars.next();
+
+                let mut last = ' ';
+
+                for (_, c) in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+
+                    last = c;
+                }
+
+                Lexeme::Comment
+            }
+            // `r'` starts a raw string, `'''` a multi-line one
+            (TokenKind::Quote, _) | (TokenKind::Symbol(_), Some('\'')) if matches!(char, '\'' | 'r') => {
+                let raw = char == 'r';
+
+                if raw {
+                    chars.next();
+                }
+
+                let multiline = chars.peek().is_some_and(|&(i, _)| source[i..].starts_with("''"));
+
+                if multiline {
+                    chars.nth(1);
+                }
+
+                let mut pieces = vec![];
+                let mut piece = start;
+                let mut closed = false;
+
+                while let Some((i, c)) = chars.next_if(|&(_, c)| multiline || !matches!(c, '\n' | '\r')) {
+                    match c {
+                        '\'' if !multiline || source[i..].starts_with("'''") => {
+                            let end = if multiline { i + 3 } else { i + 1 };
+
+                            if multiline {
+                                chars.nth(1);
+                            }
+
+                            closed = true;
+                            pieces.push(Highlight {
+                                category: Category::String,
+                                span: span(piece, end),
+                            });
+                            break;
+                        }
+                        '\\' if !raw => {
+                            if piece < i {
+                                pieces.push(Highlight {
+                                    category: Category::String,
+                                    span: span(piece, i),
+                                });
+                            }
+
+                            let escaped = chars.next_if(|&(_, c)| multiline || !matches!(c, '\n' | '\r'));
+                            let mut end = escaped.map_or(i + 1, |(j, c)| j + c.len_utf8());
+
+                            // the digits of `\xNN` and `\u{XXXX}` belong to the escape
+                            match escaped.map(|(_, c)| c) {
+                                Some('x') => {
+                                    for _ in 0..2 {
+                                        end = chars.next_if(|&(_, c)| c.is_ascii_hexdigit()).map_or(end, |(j, _)| j + 1);
+                                    }
+                                }
+                                Some('u') if chars.next_if(|&(_, c)| c == '{').is_some() => {
+                                    end += 1;
+
+                                    while let Some((j, c)) = chars.next_if(|&(_, c)| c.is_ascii_hexdigit() || c == '}') {
+                                        end = j + 1;
+
+                                        if c == '}' {
+                                            break;
+                                        }
+                                    }
+                                }
+                                _ => {}
+                            }
+
+                            pieces.push(Highlight {
+                                category: Category::Escape,
+                                span: span(i, end),
+                            });
+
+                            piece = end;
+                        }
+                        _ => {}
+                    }
+                }
+
+                Lexeme::String(closed.then_some(pieces))
+            }
+            (TokenKind::Symbol(mut word), _) => {
+                while let Some((_, c)) = chars.next_if(|&(_, c)| matches!(TokenKind::from(c), TokenKind::Symbol(_))) {
+                    word.push(c);
+                }
+
+                Lexeme::Word(word)
+            }
+            (kind, _) => Lexeme::Punct(kind),
+        };
+
+        let end = chars.peek().map_or(source.len(), |&(i, _)| i);
+
+        lexemes.push((lexeme, span(start, end)));
+    }
+
+    lexemes
+}
+
+/// Where every comment is, found the way [`highlight`] finds them so nothing in a string counts
+pub(crate) fn comments(source: &str) -> impl Iterator<Item = Span> {
+    lex(source).into_iter().filter(|(lexeme, _)| *lexeme == Lexeme::Comment).map(|(_, span)| span)
+}
+
+/// Whether a word or string can be a key, only bare words and closed strings can
+fn is_key(lexeme: &Lexeme) -> bool {
+    match lexeme {
+        Lexeme::Word(word) => parser::is_bare(word),
+        Lexeme::String(pieces) => pieces.is_some(),
+        _ => false,
+    }
+}
+
+/// The key a bare or quoted key was written as
+fn key(text: &str) -> String {
+    if !text.ends_with('\'') {
+        return text.into();
+    }
+
+    match lexer::lex(text.as_bytes()).map(|tokens| tokens.iter().peekable().parse_string()) {
+        Ok(Ok(Node::String(key))) => key,
+        _ => text.into(),
+    }
+}
+
+/// What a dict, or the top level, expects next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Key,
+    Dot,
+    Colon,
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    Dict(Expect),
+    Array,
+}
+
+struct Classifier {
+    lexemes: Vec<(Lexeme, Span)>,
+    highlights: Vec<Highlight>,
+    /// What the top level expects, `Key` or `Value`
+    top: Expect,
+    frames: Vec<Frame>,
+}
+
+impl Classifier {
+    fn push(&mut self, category: Category, span: Span) {
+        self.highlights.push(Highlight { category, span });
+    }
+
+    fn kind(&self, i: usize) -> Option<&Lexeme> {
+        self.lexemes.get(i).map(|(lexeme, _)| lexeme)
+    }
+
+    fn is(&self, i: usize, kind: TokenKind) -> bool {
+        matches!(self.kind(i), Some(Lexeme::Punct(k)) if *k == kind)
+    }
+
+    fn touches(&self, i: usize) -> bool {
+        i > 0 && i < self.lexemes.len() && self.lexemes[i - 1].1.end == self.lexemes[i].1.start
+    }
+
+    /// Whether the line starting at `i` is a `key::path =` assignment
+    fn assignment(&self, mut i: usize) -> bool {
+        loop {
+            if !matches!(self.kind(i), Some(Lexeme::Word(_) | Lexeme::String(Some(_)))) {
+                return false;
+            }
+
+            if self.is(i + 1, TokenKind::Equals) {
+                return true;
+            }
+
+            if !(self.is(i + 1, TokenKind::Colon) && self.is(i + 2, TokenKind::Colon)) {
+                return false;
+            }
+
+            i += 3;
+        }
+    }
+
+    fn expecting_value(&self) -> bool {
+        match self.frames.last() {
+            Some(Frame::Array) => true,
+            Some(Frame::Dict(expect)) => *expect == Expect::Value,
+            None => self.top == Expect::Value,
+        }
+    }
+
+    /// Moves on once a whole value was read
+    fn valued(&mut self) {
+        match self.frames.last_mut() {
+            Some(Frame::Dict(expect)) => *expect = Expect::Key,
+            Some(Frame::Array) => {}
+            None => self.top = Expect::Key,
+        }
+    }
+
+    fn run(&mut self) {
+        use TokenKind::*;
+
+        let mut i = 0;
+        let mut line_start = true;
+
+        while i < self.lexemes.len() {
+            let (lexeme, span) = self.lexemes[i].clone();
+
+            // `=` only belongs to the top level, so an assignment means every bracket left open
+            // before it was a mistake
+            if line_start && !self.frames.is_empty() && self.assignment(i) {
+                self.frames.clear();
+                self.top = Expect::Key;
+            }
+
+            line_start = false;
+
+            match lexeme {
+                Lexeme::NewLine => line_start = true,
+                Lexeme::Comment => self.push(Category::Comment, span),
+                Lexeme::Word(_) | Lexeme::String(Some(_)) if self.frames.is_empty() && self.top == Expect::Key => {
+                    let scoped = self.is(i + 1, Colon) && self.is(i + 2, Colon);
+                    let category = if scoped { Category::ScopeSegment } else { Category::Key };
+
+                    self.push(if is_key(&lexeme) { category } else { Category::Invalid }, span);
+                }
+                Lexeme::Word(_) | Lexeme::String(Some(_)) if self.frames.last() == Some(&Frame::Dict(Expect::Dot)) => {
+                    let scoped = self.is(i + 1, Colon) && self.is(i + 2, Colon);
+                    let category = if scoped { Category::ScopeSegment } else { Category::DictKey };
+
+                    self.push(if is_key(&lexeme) { category } else { Category::Invalid }, span);
+
+                    // a segment of `.a::b:` takes its `::` along, another key comes after it
+                    if scoped {
+                        self.push(Category::Punctuation, self.lexemes[i + 1].1);
+                        self.push(Category::Punctuation, self.lexemes[i + 2].1);
+                        i += 2;
+                    } else {
+                        *self.frames.last_mut().unwrap() = Frame::Dict(Expect::Colon);
+                    }
+                }
+                Lexeme::String(pieces) if self.expecting_value() => {
+                    match pieces {
+                        Some(pieces) => self.highlights.extend(pieces),
+                        None => self.push(Category::Invalid, span),
+                    }
+
+                    self.valued();
+                }
+                Lexeme::String(_) => self.push(Category::Invalid, span),
+                Lexeme::Word(word) if self.expecting_value() => {
+                    let mut end = span.end;
+                    let mut text = word;
+
+                    // a decimal point touches the digits on both sides
+                    if self.is(i + 1, Dot) && self.touches(i + 1) && self.touches(i + 2) {
+                        if let Some(Lexeme::Word(decimals)) = self.kind(i + 2) {
+                            text = format!("{text}.{decimals}");
+                            end = self.lexemes[i + 2].1.end;
+                            i += 2;
+                        }
+                    }
+
+                    let category = match Node::typed(&text) {
+                        Node::Integer(_) => Category::Integer,
+                        Node::Float(_) => Category::Float,
+                        Node::Bool(_) => Category::Boolean,
+                        Node::Null => Category::Null,
+                        _ => Category::Invalid,
+                    };
+
+                    self.push(category, Span { start: span.start, end });
+                    self.valued();
+                }
+                Lexeme::Word(_) => self.push(Category::Invalid, span),
+                Lexeme::Punct(kind) => {
+                    let valid = match (&kind, self.frames.last().copied()) {
+                        (OpenCurly, _) if self.expecting_value() => {
+                            self.frames.push(Frame::Dict(Expect::Key));
+                            true
+                        }
+                        (OpenBracket, _) if self.expecting_value() => {
+                            self.frames.push(Frame::Array);
+                            true
+                        }
+                        (CloseCurly, Some(Frame::Dict(Expect::Key))) | (CloseBracket, Some(Frame::Array)) => {
+                            self.frames.pop();
+                            self.valued();
+                            true
+                        }
+                        (Colon, None) => self.top == Expect::Key,
+                        (Equals, None) if self.top == Expect::Key => {
+                            self.top = Expect::Value;
+                            true
+                        }
+                        (Comma, Some(Frame::Array | Frame::Dict(Expect::Key))) => true,
+                        (Dot, Some(Frame::Dict(Expect::Key))) => {
+                            *self.frames.last_mut().unwrap() = Frame::Dict(Expect::Dot);
+                            true
+                        }
+                        (Colon, Some(Frame::Dict(Expect::Colon))) => {
+                            *self.frames.last_mut().unwrap() = Frame::Dict(Expect::Value);
+                            true
+                        }
+                        // recover from a dict or array closed with the wrong bracket or too early
+                        (CloseCurly | CloseBracket, Some(_)) => {
+                            let open = if kind == CloseCurly { Frame::Dict(Expect::Key) } else { Frame::Array };
+
+                            if let Some(at) = self.frames.iter().rposition(|frame| {
+                                matches!((frame, open), (Frame::Dict(_), Frame::Dict(_)) | (Frame::Array, Frame::Array))
+                            }) {
+                                self.frames.truncate(at);
+                                self.valued();
+                            }
+
+                            false
+                        }
+                        _ => false,
+                    };
+
+                    self.push(if valid { Category::Punctuation } else { Category::Invalid }, span);
+                }
+            }
+
+            i += 1;
+        }
+    }
+}
+
+/// Classifies every non-blank span of a source file, in order
+///
+/// Anything out of place is [`Category::Invalid`] and classifying carries on after it,
+/// so files that don't parse are highlighted too
+pub fn highlight(source: &str) -> Vec<Highlight> {
+    let mut classifier = Classifier {
+        lexemes: lex(source),
+        highlights: vec![],
+        top: Expect::Key,
+        frames: vec![],
+    };
+
+    classifier.run();
+    classifier.highlights
+}
+
+/// What an outline item holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Dict,
+    Array,
+    /// A single value, with the category of its highlight
+    Value(Category),
+}
+
+/// A key, or a dict or array inside an array, with everything written inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// The key as written, `a::b` for a scoped assignment or dict entry, the index for array elements
+    pub name: String,
+    pub path: Vec<String>,
+    pub kind: ItemKind,
+    /// From the key to the end of the value
+    pub span: Span,
+    /// Just the key
+    pub selection: Span,
+    pub children: Vec<Item>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldKind {
+    Dict,
+    Array,
+    Comment,
+}
+
+/// Something that can be folded away, only ones spanning several lines are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    pub kind: FoldKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outline {
+    pub items: Vec<Item>,
+    pub folds: Vec<Fold>,
+}
+
+/// An open dict or array, with the item it's the value of
+struct Open {
+    kind: FoldKind,
+    start: usize,
+    item: Item,
+    index: usize,
+}
+
+struct Builder<'a> {
+    source: &'a str,
+    open: Vec<Open>,
+    items: Vec<Item>,
+    folds: Vec<Fold>,
+    /// The key read last, waiting for its value
+    pending: Option<Item>,
+    scope: Vec<(String, Span)>,
+    dot: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn fold(&mut self, kind: FoldKind, span: Span) {
+        if self.source[span.start..span.end].contains(['\n', '\r']) {
+            self.folds.push(Fold { kind, span });
+        }
+    }
+
+    fn key(&mut self, category: Category, span: Span) {
+        let text = &self.source[span.start..span.end];
+
+        let mut path = match self.open.last() {
+            Some(open) if category == Category::DictKey => open.item.path.clone(),
+            _ => vec![],
+        };
+
+        let scope = std::mem::take(&mut self.scope);
+
+        let start = match category {
+            Category::DictKey => self.dot,
+            _ => scope.first().map_or(span.start, |(_, span)| span.start),
+        };
+
+        let written = scope.into_iter().map(|(segment, _)| segment).chain([key(text)]).collect::<Vec<_>>();
+        let name = written.join("::");
+
+        path.extend(written);
+
+        self.pending = Some(Item {
+            name,
+            path,
+            kind: ItemKind::Dict,
+            span: Span { start, end: span.end },
+            selection: span,
+            children: vec![],
+        });
+    }
+
+    /// Adds a finished item to whatever is open, or the top level
+    fn finish(&mut self, mut item: Item, end: usize) {
+        item.span.end = end;
+
+        match self.open.last_mut() {
+            Some(open) => open.item.children.push(item),
+            None => self.items.push(item),
+        }
+    }
+
+    /// Counts a value read inside an array
+    fn element(&mut self) -> Option<Item> {
+        let open = self.open.last_mut().filter(|open| open.kind == FoldKind::Array)?;
+        let name = open.index.to_string();
+
+        open.index += 1;
+
+        let mut path = open.item.path.clone();
+        path.push(name.clone());
+
+        Some(Item {
+            name,
+            path,
+            kind: ItemKind::Dict,
+            span: Span::default(),
+            selection: Span::default(),
+            children: vec![],
+        })
+    }
+
+    fn value(&mut self, category: Category, span: Span) {
+        match self.pending.take() {
+            Some(mut item) => {
+                item.kind = ItemKind::Value(category);
+                self.finish(item, span.end);
+            }
+            // scalars in arrays aren't worth an item of their own
+            None => drop(self.element()),
+        }
+    }
+
+    fn open(&mut self, kind: FoldKind, span: Span) {
+        let mut item = self.pending.take().or_else(|| self.element()).unwrap_or(Item {
+            name: String::new(),
+            path: vec![],
+            kind: ItemKind::Dict,
+            span,
+            selection: span,
+            children: vec![],
+        });
+
+        if item.span == Span::default() {
+            item.span = span;
+            item.selection = span;
+        }
+
+        item.kind = match kind {
+            FoldKind::Array => ItemKind::Array,
+            _ => ItemKind::Dict,
+        };
+
+        self.open.push(Open {
+            kind,
+            start: span.start,
+            item,
+            index: 0,
+        });
+    }
+
+    fn close(&mut self, end: usize) {
+        let Some(open) = self.open.pop() else {
+            return;
+        };
+
+        self.fold(open.kind, Span { start: open.start, end });
+
+        // a stray dict or array, what's inside still belongs somewhere
+        if open.item.path.is_empty() {
+            for child in open.item.children {
+                let end = child.span.end;
+                self.finish(child, end);
+            }
+        } else {
+            self.finish(open.item, end);
+        }
+    }
+}
+
+/// Builds the outline of a source file and where it can be folded
+///
+/// Like [`highlight`], it carries on past syntax errors, dicts and arrays that are never
+/// closed end with the file
+pub fn outline(source: &str) -> Outline {
+    let highlights = highlight(source);
+
+    let mut builder = Builder {
+        source,
+        open: vec![],
+        items: vec![],
+        folds: vec![],
+        pending: None,
+        scope: vec![],
+        dot: 0,
+    };
+
+    let mut comments: Option<Span> = None;
+    let mut iter = highlights.iter().peekable();
+
+    while let Some(&Highlight { category, mut span }) = iter.next() {
+        let text = &source[span.start..span.end];
+
+        if category == Category::Comment {
+            // line comments on consecutive lines fold together
+            comments = match comments {
+                Some(block) if text.starts_with("//") && source[block.end..span.start].trim().is_empty()
+                    && span::line_starts(&source[block.end..span.start]).len() == 2 =>
+                {
+                    Some(Span { start: block.start, end: span.end })
+                }
+                block => {
+                    if let Some(block) = block {
+                        builder.fold(FoldKind::Comment, block);
+                    }
+
+                    Some(span)
+                }
+            };
+
+            continue;
+        }
+
+        match (category, text) {
+            (Category::ScopeSegment, _) => builder.scope.push((key(text), span)),
+            (Category::Key | Category::DictKey, _) => {
+                // a key at the top level ends anything left open before it
+                while category == Category::Key && !builder.open.is_empty() {
+                    builder.close(span.start);
+                }
+
+                builder.key(category, span);
+            }
+            (Category::String, _) => {
+                // the pieces of a string make one value, up to the closing quote
+                let mut closed = text.len() > 1 && text.ends_with('\'');
+
+                while !closed {
+                    let Some(next) = iter.next_if(|next| {
+                        matches!(next.category, Category::String | Category::Escape) && next.span.start == span.end
+                    }) else {
+                        break;
+                    };
+
+                    span.end = next.span.end;
+                    closed = next.category == Category::String && source[..span.end].ends_with('\'');
+                }
+
+                builder.value(Category::String, span);
+            }
+            (Category::Punctuation, "{") => builder.open(FoldKind::Dict, span),
+            (Category::Punctuation, "[") => builder.open(FoldKind::Array, span),
+            (Category::Punctuation, "}" | "]") => builder.close(span.end),
+            (Category::Punctuation, ".") => builder.dot = span.start,
+            (Category::Punctuation | Category::Invalid, _) => {}
+            (category, _) => builder.value(category, span),
+        }
+    }
+
+    if let Some(block) = comments {
+        builder.fold(FoldKind::Comment, block);
+    }
+
+    while !builder.open.is_empty() {
+        builder.close(source.len());
+    }
+
+    builder.folds.sort_by_key(|fold| fold.span.start);
+
+    Outline {
+        items: builder.items,
+        folds: builder.folds,
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        use Category::*;
+
+        let source = "// c\na::b = { .x: 'a\\'b' .y: [1.5, true, null] } /* d */\nc = oops\n";
+
+        let highlights = highlight(source)
+            .into_iter()
+            .map(|highlight| (highlight.category, &source[highlight.span.start..highlight.span.end]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(highlights, [
+            (Comment, "// c"),
+            (ScopeSegment, "a"), (Punctuation, ":"), (Punctuation, ":"), (Key, "b"), (Punctuation, "="),
+            (Punctuation, "{"), (Punctuation, "."), (DictKey, "x"), (Punctuation, ":"),
+            (String, "'a"), (Escape, "\\'"), (String, "b'"),
+            (Punctuation, "."), (DictKey, "y"), (Punctuation, ":"),
+            (Punctuation, "["), (Float, "1.5"), (Punctuation, ","), (Boolean, "true"), (Punctuation, ","), (Null, "null"), (Punctuation, "]"),
+            (Punctuation, "}"), (Comment, "/* d */"),
+            (Key, "c"), (Punctuation, "="), (Invalid, "oops"),
+        ]);
+
+        let source = "'a b'::c = { .'d': 1, .f::g: 2 }\n$e = 2";
+        let quoted = highlight(source).into_iter().map(|highlight| (highlight.category, &source[highlight.span.start..highlight.span.end]));
+
+        assert_eq!(quoted.filter(|(category, _)| *category != Punctuation).collect::<Vec<_>>(), [
+            (ScopeSegment, "'a b'"), (Key, "c"), (DictKey, "'d'"), (Integer, "1"), (ScopeSegment, "f"), (DictKey, "g"), (Integer, "2"),
+            (Invalid, "$e"), (Integer, "2"),
+        ]);
+
+        let broken = highlight("a = 'open\nb = { .c 1 ]\nd = 2");
+
+        assert_eq!(broken.iter().map(|highlight| highlight.category).collect::<Vec<_>>(), [
+            Key, Punctuation, Invalid,
+            Key, Punctuation, Punctuation, Punctuation, DictKey, Invalid, Invalid,
+            Key, Punctuation, Integer,
+        ]);
+    }
+
+    #[test]
+    fn test_outline() {
+        let source = "\
+// one
+// two
+server = {
+    .port: 80
+    .tls: { .on: true }
+}
+list = [
+    { .name: 'a\\n' },
+    2,
+    [3],
+]
+a::b = 'x'
+broken = {
+    .inner: [1,
+c = 1
+";
+
+        let outline = outline(source);
+
+        let names = |items: &[Item]| items.iter().map(|item| (item.path.join("::"), item.kind)).collect::<Vec<_>>();
+
+        assert_eq!(names(&outline.items), [
+            ("server".into(), ItemKind::Dict),
+            ("list".into(), ItemKind::Array),
+            ("a::b".into(), ItemKind::Value(Category::String)),
+            ("broken".into(), ItemKind::Dict),
+            ("c".into(), ItemKind::Value(Category::Integer)),
+        ]);
+
+        assert_eq!(names(&outline.items[0].children), [
+            ("server::port".into(), ItemKind::Value(Category::Integer)),
+            ("server::tls".into(), ItemKind::Dict),
+        ]);
+
+        assert_eq!(names(&outline.items[1].children), [
+            ("list::0".into(), ItemKind::Dict),
+            ("list::2".into(), ItemKind::Array),
+        ]);
+
+        assert_eq!(names(&outline.items[1].children[0].children), [("list::0::name".into(), ItemKind::Value(Category::String))]);
+        assert_eq!(names(&outline.items[3].children), [("broken::inner".into(), ItemKind::Array)]);
+
+        let server = &outline.items[0];
+
+        assert_eq!(&source[server.span.start..server.span.end], "server = {\n    .port: 80\n    .tls: { .on: true }\n}");
+        assert_eq!(&source[server.selection.start..server.selection.end], "server");
+        assert_eq!(&source[server.children[1].span.start..server.children[1].span.end], ".tls: { .on: true }");
+        assert_eq!(outline.items[2].name, "a::b");
+
+        let quoted = super::outline("'a b'::c = { .'d\\'e': 1, .f::g: {} }");
+        assert_eq!(names(&quoted.items[0].children), [
+            ("a b::c::d'e".into(), ItemKind::Value(Category::Integer)),
+            ("a b::c::f::g".into(), ItemKind::Dict),
+        ]);
+        assert_eq!(quoted.items[0].children[1].name, "f::g");
+
+        let folds = outline.folds.iter().map(|fold| (fold.kind, &source[fold.span.start..fold.span.end])).collect::<Vec<_>>();
+
+        assert_eq!(folds, [
+            (FoldKind::Comment, "// one\n// two"),
+            (FoldKind::Dict, "{\n    .port: 80\n    .tls: { .on: true }\n}"),
+            (FoldKind::Array, "[\n    { .name: 'a\\n' },\n    2,\n    [3],\n]"),
+            (FoldKind::Dict, "{\n    .inner: [1,\n"),
+            (FoldKind::Array, "[1,\n"),
+        ]);
+    }
+}
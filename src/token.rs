@@ -12,6 +12,12 @@ impl Token {
         }
     }
 
+    /// Whether `next` starts right where this token ends, with no whitespace between them
+    pub fn touches(&self, next: &Token) -> bool {
+        self.position.line == next.position.line
+            && self.position.character + self.position.length == next.position.character
+    }
+
     pub fn join(&mut self, rhs: &Token) -> Result<(), &'static str> {
         if let TokenKind::Symbol(ref mut symbol) = self.kind {
             symbol.push_str(rhs.kind.as_str());
@@ -90,7 +96,7 @@ impl From<char> for TokenKind {
             ' ' => Space,
             '\t' => Tab,
             '=' => Equals,
-            '\n' => NewLine,
+            '\n' | '\r' => NewLine,
             '[' => OpenBracket,
             ']' => CloseBracket,
             '{' => OpenCurly,
@@ -106,19 +112,39 @@ impl Default for TokenKind {
     }
 }
 
+/// Where a token starts, lines count from 1 and characters from 0, and how far it goes
+///
+/// `length` counts chars and `offset` to `end` are bytes, strings that run over several
+/// lines are a single token so only the bytes tell where one of those ends
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TokenPosition {
-    pub line: isize,
-    pub character: isize,
-    pub length: isize,
+    pub line: usize,
+    pub character: usize,
+    pub length: usize,
+    pub offset: usize,
+    pub end: usize,
+}
+
+impl From<(usize, usize, usize)> for TokenPosition {
+    fn from(value: (usize, usize, usize)) -> Self {
+        Self {
+            line: value.0,
+            character: value.1,
+            length: value.2,
+            offset: 0,
+            end: 0,
+        }
+    }
 }
 
-impl From<(isize, isize, isize)> for TokenPosition {
-    fn from(value: (isize, isize, isize)) -> Self {
+impl From<(usize, usize, usize, usize, usize)> for TokenPosition {
+    fn from(value: (usize, usize, usize, usize, usize)) -> Self {
         Self {
             line: value.0,
             character: value.1,
             length: value.2,
+            offset: value.3,
+            end: value.4,
         }
     }
 }
@@ -139,6 +165,8 @@ impl Default for TokenPosition {
             line: 1,
             character: 0,
             length: 0,
+            offset: 0,
+            end: 0,
         }
     }
 }
@@ -146,5 +174,6 @@ impl Default for TokenPosition {
 impl std::ops::AddAssign for TokenPosition {
     fn add_assign(&mut self, rhs: Self) {
         self.length += rhs.length;
+        self.end = self.end.max(rhs.end);
     }
 }
@@ -0,0 +1,658 @@
+use crate::{
+    convert::{self, SyntaxError, Warning},
+    Ast, Node,
+};
+use std::collections::HashMap;
+
+fn is_bare(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn key(key: &str) -> String {
+    if is_bare(key) {
+        key.to_string()
+    } else {
+        string(key)
+    }
+}
+
+fn string(value: &str) -> String {
+    let mut string = String::from('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => string.push_str("\\\""),
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            '\r' => string.push_str("\\r"),
+            '\t' => string.push_str("\\t"),
+            c if c.is_control() => string.push_str(&format!("\\u{:04X}", c as u32)),
+            c => string.push(c),
+        }
+    }
+
+    string.push('"');
+    string
+}
+
+fn sorted(dict: &HashMap<String, Node>) -> Vec<(&String, &Node)> {
+    let mut entries = dict.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+/// Arrays of non-empty dicts are written as `[[array.of.tables]]`
+fn is_table_array(node: &Node) -> bool {
+    matches!(node, Node::Array(array) if !array.is_empty()
+        && array.iter().all(|node| matches!(node, Node::Dict(dict) if !dict.is_empty())))
+}
+
+fn inline(node: &Node, path: &mut Vec<String>, warnings: &mut Vec<Warning>) -> Option<String> {
+    let value = match node {
+        Node::String(value) => string(value),
+        Node::Integer(value) => value.to_string(),
+        Node::Float(value) if value.is_nan() => "nan".into(),
+        Node::Float(value) if value.is_infinite() => if *value > 0.0 { "inf" } else { "-inf" }.into(),
+        Node::Float(value) => format!("{value:?}"),
+        Node::Bool(value) => value.to_string(),
+        Node::Null => {
+            warnings.push(Warning::new(path, "toml has no null, the key was left out"));
+            return None;
+        }
+        Node::Array(array) => {
+            let mut values = vec![];
+
+            for (i, node) in array.iter().enumerate() {
+                // leaving one out moves the rest, so the warning is about the array
+                if let Node::Null = node {
+                    warnings.push(Warning::new(path, format!("toml has no null, element {i} was left out and the ones after it moved up")));
+                    continue;
+                }
+
+                path.push(i.to_string());
+                values.extend(inline(node, path, warnings));
+                path.pop();
+            }
+
+            format!("[{}]", values.join(", "))
+        }
+        Node::Dict(dict) if dict.is_empty() => "{}".into(),
+        Node::Dict(dict) => {
+            let mut entries = vec![];
+
+            for (name, node) in sorted(dict) {
+                path.push(name.clone());
+
+                if let Some(value) = inline(node, path, warnings) {
+                    entries.push(format!("{} = {value}", key(name)));
+                }
+
+                path.pop();
+            }
+
+            format!("{{ {} }}", entries.join(", "))
+        }
+    };
+
+    Some(value)
+}
+
+/// Writes a table's entries and then its subtables, `tables` is the header path, which unlike
+/// `path` leaves out the positions inside arrays of tables
+fn table(dict: &HashMap<String, Node>, path: &mut Vec<String>, tables: &mut Vec<String>, out: &mut String, warnings: &mut Vec<Warning>) {
+    let header = |tables: &[String]| tables.iter().map(|name| key(name)).collect::<Vec<_>>().join(".");
+
+    for (name, node) in sorted(dict) {
+        if matches!(node, Node::Dict(_)) || is_table_array(node) {
+            continue;
+        }
+
+        path.push(name.clone());
+
+        if let Some(value) = inline(node, path, warnings) {
+            out.push_str(&format!("{} = {value}\n", key(name)));
+        }
+
+        path.pop();
+    }
+
+    for (name, node) in sorted(dict) {
+        path.push(name.clone());
+        tables.push(name.clone());
+
+        match node {
+            Node::Dict(dict) => {
+                // tables holding nothing but other tables are created by their headers
+                let implicit = !dict.is_empty() && dict.values().all(|node| matches!(node, Node::Dict(_)) || is_table_array(node));
+
+                if !implicit {
+                    out.push_str(&format!("\n[{}]\n", header(tables)));
+                }
+
+                table(dict, path, tables, out, warnings);
+            }
+            Node::Array(array) if is_table_array(node) => {
+                for (i, node) in array.iter().enumerate() {
+                    let Node::Dict(dict) = node else { unreachable!() };
+
+                    out.push_str(&format!("\n[[{}]]\n", header(tables)));
+
+                    path.push(i.to_string());
+                    table(dict, path, tables, out, warnings);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+
+        path.pop();
+        tables.pop();
+    }
+}
+
+/// Writes the tree as a TOML document, pushing every `null` it had to leave out into `warnings`
+pub(crate) fn write(ast: &Ast, warnings: &mut Vec<Warning>) -> String {
+    let mut out = String::new();
+
+    table(&ast.0, &mut vec![], &mut vec![], &mut out, warnings);
+
+    out.trim_start_matches('\n').to_string()
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    index: usize,
+    warnings: &'a mut Vec<Warning>,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError::at(self.source, self.index, message)
+    }
+
+    fn rest(&self) -> &str {
+        &self.source[self.index..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        if self.rest().starts_with(prefix) {
+            self.index += prefix.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.index += 1;
+        }
+    }
+
+    /// Skips whitespace, comments and, if `newlines` is set, line breaks
+    fn blank(&mut self, newlines: bool) {
+        loop {
+            self.spaces();
+
+            match self.peek() {
+                Some('#') => {
+                    let end = self.rest().find('\n').unwrap_or(self.rest().len());
+                    self.index += end;
+                }
+                Some('\r' | '\n') if newlines => self.index += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), SyntaxError> {
+        self.blank(false);
+        self.eat("\r");
+
+        if self.peek().is_some() && !self.eat("\n") {
+            return Err(self.error("expected a new line"));
+        }
+
+        Ok(())
+    }
+
+    fn key(&mut self) -> Result<Vec<String>, SyntaxError> {
+        let mut path = vec![];
+
+        loop {
+            self.spaces();
+
+            let segment = match self.peek() {
+                Some('"') => self.basic()?,
+                Some('\'') => self.literal()?,
+                _ => {
+                    let end = self
+                        .rest()
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                        .unwrap_or(self.rest().len());
+
+                    if end == 0 {
+                        return Err(self.error("expected a key"));
+                    }
+
+                    let segment = self.rest()[..end].to_string();
+                    self.index += end;
+                    segment
+                }
+            };
+
+            path.push(segment);
+            self.spaces();
+
+            if !self.eat(".") {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn escape(&mut self, string: &mut String) -> Result<(), SyntaxError> {
+        let c = self.peek().ok_or_else(|| self.error("unclosed string"))?;
+        self.index += c.len_utf8();
+
+        let mut unicode = |length: usize| {
+            let code = self
+                .rest()
+                .get(..length)
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .and_then(char::from_u32)
+                .ok_or_else(|| self.error("invalid unicode escape"))?;
+
+            self.index += length;
+            Ok(code)
+        };
+
+        let c = match c {
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            '"' => '"',
+            '\\' => '\\',
+            'u' => unicode(4)?,
+            'U' => unicode(8)?,
+            _ => return Err(self.error("invalid escape")),
+        };
+
+        string.push(c);
+        Ok(())
+    }
+
+    fn basic(&mut self) -> Result<String, SyntaxError> {
+        let multiline = self.eat("\"\"\"");
+
+        if !multiline {
+            self.eat("\"");
+        } else if !self.eat("\r\n") {
+            self.eat("\n");
+        }
+
+        let mut string = String::new();
+
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unclosed string"))?;
+
+            if multiline && self.eat("\"\"\"") {
+                // up to two quotes can end the content
+                while self.eat("\"") {
+                    string.push('"');
+                }
+
+                return Ok(string);
+            }
+
+            self.index += c.len_utf8();
+
+            match c {
+                '"' if !multiline => return Ok(string),
+                '\\' if multiline && matches!(self.rest().trim_start_matches([' ', '\t']).chars().next(), Some('\n' | '\r')) => {
+                    self.index += self.rest().len() - self.rest().trim_start().len();
+                }
+                '\\' => self.escape(&mut string)?,
+                '\n' if !multiline => return Err(self.error("unclosed string")),
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn literal(&mut self) -> Result<String, SyntaxError> {
+        let multiline = self.eat("'''");
+
+        if !multiline {
+            self.eat("'");
+        } else if !self.eat("\r\n") {
+            self.eat("\n");
+        }
+
+        let end = if multiline { "'''" } else { "'" };
+
+        let Some(length) = self.rest().find(end) else {
+            return Err(self.error("unclosed string"));
+        };
+
+        let mut string = self.rest()[..length].to_string();
+
+        if !multiline && string.contains('\n') {
+            return Err(self.error("unclosed string"));
+        }
+
+        self.index += length + end.len();
+
+        if multiline {
+            while self.eat("'") {
+                string.push('\'');
+            }
+        }
+
+        Ok(string)
+    }
+
+    fn value(&mut self, path: &mut Vec<String>) -> Result<Node, SyntaxError> {
+        match self.peek() {
+            Some('"') => self.basic().map(Node::String),
+            Some('\'') => self.literal().map(Node::String),
+            Some('[' | '{') if convert::too_deep(path.len()) => Err(self.error("too deeply nested")),
+            Some('[') => {
+                self.index += 1;
+
+                let mut array = vec![];
+
+                loop {
+                    self.blank(true);
+
+                    if self.eat("]") {
+                        return Ok(Node::Array(array));
+                    }
+
+                    path.push(array.len().to_string());
+                    array.push(self.value(path)?);
+                    path.pop();
+
+                    self.blank(true);
+
+                    if !self.eat(",") && !self.rest().starts_with(']') {
+                        return Err(self.error("expected ',' or ']'"));
+                    }
+                }
+            }
+            Some('{') => {
+                self.index += 1;
+
+                let mut dict = HashMap::new();
+
+                self.spaces();
+
+                if self.eat("}") {
+                    return Ok(Node::Dict(dict));
+                }
+
+                loop {
+                    let key = self.key()?;
+
+                    if !self.eat("=") {
+                        return Err(self.error("expected '='"));
+                    }
+
+                    self.spaces();
+
+                    path.extend(key.iter().cloned());
+                    let node = self.value(path)?;
+                    path.truncate(path.len() - key.len());
+
+                    insert(&mut dict, &key, node).map_err(|message| self.error(message))?;
+
+                    self.spaces();
+
+                    if self.eat("}") {
+                        return Ok(Node::Dict(dict));
+                    }
+
+                    if !self.eat(",") {
+                        return Err(self.error("expected ',' or '}'"));
+                    }
+                }
+            }
+            Some(_) => self.scalar(path),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn scalar(&mut self, path: &[String]) -> Result<Node, SyntaxError> {
+        let start = self.index;
+
+        let mut end = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "_+-.:".contains(c)))
+            .unwrap_or(self.rest().len());
+
+        // a date followed by a time, separated by a space
+        let date = self.rest().len() > 10 && self.rest().as_bytes()[4] == b'-' && self.rest().as_bytes()[7] == b'-';
+
+        if date && end == 10 && self.rest()[11..].starts_with(|c: char| c.is_ascii_digit()) {
+            end = 11 + self.rest()[11..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "_+-.:".contains(c)))
+                .unwrap_or(self.rest().len() - 11);
+        }
+
+        let word = self.rest()[..end].to_string();
+        self.index += end;
+
+        let invalid = || SyntaxError::at(self.source, start, format!("invalid value '{word}'"));
+
+        let node = match word.as_str() {
+            "true" => Node::Bool(true),
+            "false" => Node::Bool(false),
+            "inf" | "+inf" => Node::Float(f64::INFINITY),
+            "-inf" => Node::Float(f64::NEG_INFINITY),
+            "nan" | "+nan" | "-nan" => Node::Float(f64::NAN),
+            word if date || word.get(2..3) == Some(":") => {
+                self.warnings.push(Warning::new(path, "toml date and time was read as a string"));
+                Node::String(word.to_string())
+            }
+            word => {
+                let digits = word.replace('_', "");
+
+                let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+                    .into_iter()
+                    .find_map(|(prefix, radix)| digits.strip_prefix(prefix).map(|digits| (digits, radix)));
+
+                if let Some((digits, radix)) = radix {
+                    Node::Integer(i64::from_str_radix(digits, radix).map_err(|_| invalid())?)
+                } else if let Ok(value) = digits.parse::<i64>() {
+                    Node::Integer(value)
+                } else if digits.contains(['.', 'e', 'E']) {
+                    Node::Float(digits.parse::<f64>().map_err(|_| invalid())?)
+                } else {
+                    return Err(invalid());
+                }
+            }
+        };
+
+        Ok(node)
+    }
+}
+
+/// Inserts `node` at a dotted key, failing on keys that are already defined
+fn insert(dict: &mut HashMap<String, Node>, key: &[String], node: Node) -> Result<(), String> {
+    let (last, parents) = key.split_last().unwrap();
+
+    let dict = table_at(dict, parents)?;
+
+    if dict.contains_key(last) {
+        return Err(format!("duplicate key '{}'", key.join(".")));
+    }
+
+    dict.insert(last.clone(), node);
+    Ok(())
+}
+
+/// The table at `path`, where arrays of tables resolve to their last element
+fn table_at<'a>(dict: &'a mut HashMap<String, Node>, path: &[String]) -> Result<&'a mut HashMap<String, Node>, String> {
+    if convert::too_deep(path.len()) {
+        return Err("too deeply nested".into());
+    }
+
+    let mut dict = dict;
+
+    for segment in path {
+        let node = dict.entry(segment.clone()).or_insert_with(|| Node::Dict(HashMap::new()));
+
+        dict = match node {
+            Node::Dict(dict) => dict,
+            Node::Array(array) => match array.last_mut() {
+                Some(Node::Dict(dict)) => dict,
+                _ => return Err(format!("'{segment}' isn't a table")),
+            },
+            _ => return Err(format!("'{segment}' isn't a table")),
+        };
+    }
+
+    Ok(dict)
+}
+
+/// Reads a TOML document, dates and times are kept as strings
+pub(crate) fn read(buffer: &[u8], warnings: &mut Vec<Warning>) -> Result<Ast, SyntaxError> {
+    let source = String::from_utf8_lossy(buffer);
+
+    let mut reader = Reader {
+        source: &source,
+        index: 0,
+        warnings,
+    };
+
+    let mut root = HashMap::new();
+    let mut table = vec![];
+
+    loop {
+        reader.blank(true);
+
+        if reader.peek().is_none() {
+            return Ok(Ast(root, Default::default()));
+        }
+
+        if reader.eat("[[") {
+            let path = reader.key()?;
+
+            if !reader.eat("]]") {
+                return Err(reader.error("expected ']]'"));
+            }
+
+            let (last, parents) = path.split_last().unwrap();
+            let parent = table_at(&mut root, parents).map_err(|message| reader.error(message))?;
+
+            match parent.entry(last.clone()).or_insert_with(|| Node::Array(vec![])) {
+                Node::Array(array) => array.push(Node::Dict(HashMap::new())),
+                _ => return Err(reader.error(format!("'{last}' isn't an array of tables"))),
+            }
+
+            table = path;
+        } else if reader.eat("[") {
+            let path = reader.key()?;
+
+            if !reader.eat("]") {
+                return Err(reader.error("expected ']'"));
+            }
+
+            table_at(&mut root, &path).map_err(|message| reader.error(message))?;
+            table = path;
+        } else {
+            let start = reader.index;
+            let key = reader.key()?;
+
+            if !reader.eat("=") {
+                return Err(reader.error("expected '='"));
+            }
+
+            reader.spaces();
+
+            let mut path = [table.clone(), key.clone()].concat();
+            let node = reader.value(&mut path)?;
+
+            let dict = table_at(&mut root, &table).map_err(|message| reader.error(message))?;
+            insert(dict, &key, node).map_err(|message| SyntaxError::at(&source, start, message))?;
+        }
+
+        reader.end_of_line()?;
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read() {
+        let buffer = br#"
+            # comment
+            title = "TOML \"example\"" # trailing
+            literal = 'C:\path'
+            number = 1_000
+            hex = 0xff
+            float = 6.5e-1
+            dotted.key = true
+            date = 1979-05-27 07:32:00
+            multi = """
+            a \
+            b"""
+            list = [
+                1,
+                2, # comment
+            ]
+            inline = { x = 1, y = { z = [] } }
+
+            [server."tls.v2"]
+            enabled = false
+
+            [[products]]
+            name = "a"
+
+            [[products]]
+            name = "b"
+        "#;
+
+        let mut warnings = vec![];
+
+        let expected = HashMap::<String, _>::from([
+            ("title".into(), Node::String("TOML \"example\"".into())),
+            ("literal".into(), Node::String("C:\\path".into())),
+            ("number".into(), Node::Integer(1000)),
+            ("hex".into(), Node::Integer(255)),
+            ("float".into(), Node::Float(0.65)),
+            ("dotted".into(), Node::Dict(HashMap::from([("key".into(), Node::Bool(true))]))),
+            ("date".into(), Node::String("1979-05-27 07:32:00".into())),
+            ("multi".into(), Node::String("            a b".into())),
+            ("list".into(), Node::Array(vec![Node::Integer(1), Node::Integer(2)])),
+            ("inline".into(), Node::Dict(HashMap::from([
+                ("x".into(), Node::Integer(1)),
+                ("y".into(), Node::Dict(HashMap::from([("z".into(), Node::Array(vec![]))]))),
+            ]))),
+            ("server".into(), Node::Dict(HashMap::from([
+                ("tls.v2".into(), Node::Dict(HashMap::from([("enabled".into(), Node::Bool(false))]))),
+            ]))),
+            ("products".into(), Node::Array(vec![
+                Node::Dict(HashMap::from([("name".into(), Node::String("a".into()))])),
+                Node::Dict(HashMap::from([("name".into(), Node::String("b".into()))])),
+            ])),
+        ]);
+
+        assert_eq!(read(buffer, &mut warnings).unwrap().0, expected);
+        assert_eq!(warnings, [Warning::new(&["date".into()], "toml date and time was read as a string")]);
+
+        assert_eq!(read(b"a = 1\na = 2", &mut vec![]).unwrap_err().message, "duplicate key 'a'");
+
+        let deep = format!("a = {}", "[".repeat(20_000));
+        assert_eq!(read(deep.as_bytes(), &mut vec![]).unwrap_err().message, "too deeply nested");
+        let deep = format!("[{}]", vec!["a"; 20_000].join("."));
+        assert_eq!(read(deep.as_bytes(), &mut vec![]).unwrap_err().message, "too deeply nested");
+    }
+}
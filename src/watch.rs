@@ -0,0 +1,270 @@
+use crate::{Ast, Kfg, ParserError};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+#[derive(Debug, Clone)]
+pub enum WatchError {
+    Io(Arc<std::io::Error>),
+    Parse(ParserError),
+}
+
+impl std::error::Error for WatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WatchError::Io(error) => Some(error.as_ref()),
+            WatchError::Parse(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// What subscribers hear about when the file changes
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The file parsed, `paths` are the `key::path`s whose value changed
+    Reloaded { ast: Ast, paths: Vec<String> },
+    /// The file couldn't be read or parsed, the last good tree is kept
+    Failed(WatchError),
+}
+
+impl Event {
+    /// Whether a reload changed the value at `path` or anything below it
+    pub fn changed(&self, path: &str) -> bool {
+        let Event::Reloaded { paths, .. } = self else {
+            return false;
+        };
+
+        paths.iter().any(|changed| {
+            changed == path
+                || changed.strip_prefix(path).is_some_and(|rest| rest.starts_with("::"))
+                || path.strip_prefix(changed.as_str()).is_some_and(|rest| rest.starts_with("::"))
+        })
+    }
+}
+
+struct State {
+    ast: Ast,
+    /// Hash of the content last read, `None` while the file can't be read
+    hash: Option<u64>,
+    error: Option<WatchError>,
+}
+
+struct Inner {
+    path: PathBuf,
+    /// Both are locked even when poisoned, a panic on another thread doesn't stop the watcher
+    state: Mutex<State>,
+    subscribers: Mutex<Vec<Sender<Event>>>,
+}
+
+/// Keeps the last good [`Ast`] of a file, reloading it when its content changes
+///
+/// Only the one file is watched, clones share the same state and subscribers
+#[derive(Clone)]
+pub struct Watcher {
+    inner: Arc<Inner>,
+}
+
+fn read(path: &PathBuf) -> Result<(u64, Ast), (Option<u64>, WatchError)> {
+    let buffer = std::fs::read(path).map_err(|error| (None, WatchError::Io(Arc::new(error))))?;
+
+    let mut hasher = DefaultHasher::new();
+    buffer.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    // the same parse as `Kfg::read`, on the bytes that were hashed
+    Kfg::parse(&buffer)
+        .map(|ast| (hash, ast))
+        .map_err(|error| (Some(hash), WatchError::Parse(error)))
+}
+
+impl Watcher {
+    /// Loads the file, failing if the first version can't be read or parsed
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, WatchError> {
+        let path = path.into();
+        let (hash, ast) = read(&path).map_err(|(_, error)| error)?;
+
+        let state = State {
+            ast,
+            hash: Some(hash),
+            error: None,
+        };
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                path,
+                state: Mutex::new(state),
+                subscribers: Mutex::new(vec![]),
+            }),
+        })
+    }
+
+    /// The last version of the file that parsed
+    pub fn ast(&self) -> Ast {
+        self.inner.state.lock().unwrap_or_else(|error| error.into_inner()).ast.clone()
+    }
+
+    /// Why the latest version of the file isn't the one in [`Watcher::ast`], if it isn't
+    pub fn error(&self) -> Option<WatchError> {
+        self.inner.state.lock().unwrap_or_else(|error| error.into_inner()).error.clone()
+    }
+
+    /// Returns a channel receiving every event from now on
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (sender, receiver) = channel();
+        self.inner.subscribers.lock().unwrap_or_else(|error| error.into_inner()).push(sender);
+        receiver
+    }
+
+    /// Checks the file once, notifying subscribers and returning the event if anything changed
+    pub fn poll(&self) -> Option<Event> {
+        let mut state = self.inner.state.lock().unwrap_or_else(|error| error.into_inner());
+
+        let event = match read(&self.inner.path) {
+            Ok((hash, _)) if state.hash == Some(hash) => return None,
+            Ok((hash, ast)) => {
+                let paths = state.ast.diff(&ast).into_iter().map(|change| change.path).collect();
+
+                debug!("reloaded {}", self.inner.path.display());
+
+                state.ast = ast.clone();
+                state.hash = Some(hash);
+                state.error = None;
+
+                Event::Reloaded { ast, paths }
+            }
+            Err((hash, _)) if hash.is_some() && state.hash == hash => return None,
+            Err((None, _)) if state.hash.is_none() => return None,
+            Err((hash, error)) => {
+                warn!("couldn't reload {}: {error}", self.inner.path.display());
+
+                state.hash = hash;
+                state.error = Some(error.clone());
+
+                Event::Failed(error)
+            }
+        };
+
+        drop(state);
+
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+
+        Some(event)
+    }
+
+    /// Polls the file every `interval` on a background thread until the [`Poller`] is dropped
+    pub fn spawn(&self, interval: Duration) -> Poller {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let watcher = self.clone();
+        let stopped = stop.clone();
+
+        let thread = std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                watcher.poll();
+                std::thread::sleep(interval);
+            }
+        });
+
+        Poller {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Stops the background polling of [`Watcher::spawn`] when dropped
+pub struct Poller {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    #[test]
+    fn test_watch() {
+        let path = std::env::temp_dir().join(format!("kfg-watch-{}.kfg", std::process::id()));
+        std::fs::write(&path, "port = 80\nserver = { .host: 'a' .debug: false }\n").unwrap();
+
+        let watcher = Watcher::new(&path).unwrap();
+        let events = watcher.subscribe();
+
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&path, "port = 80\nserver = { .host: 'b' .debug: false }\n").unwrap();
+
+        let event = watcher.poll().unwrap();
+
+        assert!(matches!(&event, Event::Reloaded { paths, .. } if *paths == ["server::host"]));
+        assert!(event.changed("server") && event.changed("server::host") && !event.changed("port"));
+        assert!(matches!(events.try_recv(), Ok(Event::Reloaded { .. })));
+
+        std::fs::write(&path, "port = = 1\n").unwrap();
+
+        assert!(matches!(watcher.poll(), Some(Event::Failed(WatchError::Parse(_)))));
+        assert!(watcher.poll().is_none());
+        assert!(watcher.error().is_some());
+        assert_eq!(watcher.ast().lookup("server::host"), Some(&Node::String("b".into())));
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(watcher.poll(), Some(Event::Failed(WatchError::Io(error))) if error.kind() == std::io::ErrorKind::NotFound));
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&path, "port = 8080\n").unwrap();
+
+        let poller = watcher.spawn(Duration::from_millis(1));
+
+        assert!(matches!(events.recv_timeout(Duration::from_secs(5)), Ok(Event::Failed(_))));
+        assert!(matches!(events.recv_timeout(Duration::from_secs(5)), Ok(Event::Failed(_))));
+        assert!(matches!(events.recv_timeout(Duration::from_secs(5)), Ok(Event::Reloaded { .. })));
+        assert!(watcher.error().is_none());
+
+        drop(poller);
+
+        // a thread panicking while it holds the state leaves the last good tree
+        let clone = watcher.clone();
+        let _ = std::thread::spawn(move || {
+            let _state = clone.inner.state.lock();
+            panic!("poisons the lock");
+        })
+        .join();
+
+        assert_eq!(watcher.ast().lookup("port"), Some(&Node::Integer(8080)));
+        assert!(watcher.poll().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
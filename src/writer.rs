@@ -0,0 +1,209 @@
+use crate::{parser, Ast, Node};
+use std::collections::HashMap;
+
+const INDENT: &str = "    ";
+
+/// Quotes and escapes `value` as a kfg string
+pub fn string(value: &str) -> String {
+    let mut string = String::from('\'');
+
+    for c in value.chars() {
+        match c {
+            '\\' => string.push_str("\\\\"),
+            '\'' => string.push_str("\\'"),
+            '\n' => string.push_str("\\n"),
+            '\r' => string.push_str("\\r"),
+            '\t' => string.push_str("\\t"),
+            '\0' => string.push_str("\\0"),
+            c if c.is_control() => string.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => string.push(c),
+        }
+    }
+
+    string.push('\'');
+    string
+}
+
+fn sorted(dict: &HashMap<String, Node>) -> Vec<(&String, &Node)> {
+    let mut entries = dict.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+fn is_container(node: &Node) -> bool {
+    match node {
+        Node::Array(array) => !array.is_empty(),
+        Node::Dict(dict) => !dict.is_empty(),
+        _ => false,
+    }
+}
+
+/// Writes a key, quoted unless it's a bare one
+pub fn key(key: &str) -> String {
+    if parser::is_bare(key) {
+        key.to_string()
+    } else {
+        string(key)
+    }
+}
+
+/// Writes a `key::path` as the key of a scoped assignment
+pub fn path(path: &str) -> String {
+    path.split("::").map(key).collect::<Vec<_>>().join("::")
+}
+
+/// The `///` lines documenting the entry at `key`, if it has a doc
+fn doc(docs: &HashMap<String, String>, path: &[String], key: &str, indent: &str) -> String {
+    let path = path.iter().map(String::as_str).chain([key]).collect::<Vec<_>>().join("::");
+
+    let Some(doc) = docs.get(&path) else {
+        return String::new();
+    };
+
+    doc.split('\n')
+        .map(|line| match line {
+            "" => format!("{indent}///\n"),
+            line => format!("{indent}/// {line}\n"),
+        })
+        .collect()
+}
+
+fn child(node: &Node, depth: usize, key: String, path: &mut Vec<String>, docs: &HashMap<String, String>) -> String {
+    path.push(key);
+    let value = write(node, depth, path, docs);
+    path.pop();
+    value
+}
+
+fn write(node: &Node, depth: usize, path: &mut Vec<String>, docs: &HashMap<String, String>) -> String {
+    let indent = INDENT.repeat(depth + 1);
+    let close = INDENT.repeat(depth);
+
+    match node {
+        Node::String(value) => string(value),
+        Node::Integer(value) => value.to_string(),
+        Node::Float(value) => format!("{value:?}"),
+        Node::Bool(value) => value.to_string(),
+        Node::Null => "null".into(),
+        Node::Array(array) if array.is_empty() => "[]".into(),
+        Node::Array(array) if !array.iter().any(is_container) => {
+            let array = array
+                .iter()
+                .enumerate()
+                .map(|(i, node)| child(node, depth, i.to_string(), path, docs))
+                .collect::<Vec<_>>();
+
+            format!("[{}]", array.join(", "))
+        }
+        Node::Array(array) => {
+            let array = array
+                .iter()
+                .enumerate()
+                .map(|(i, node)| format!("{indent}{},\n", child(node, depth + 1, i.to_string(), path, docs)))
+                .collect::<String>();
+
+            format!("[\n{array}{close}]")
+        }
+        Node::Dict(dict) if dict.is_empty() => "{}".into(),
+        Node::Dict(dict) => {
+            let dict = sorted(dict)
+                .into_iter()
+                .map(|(name, node)| {
+                    let doc = doc(docs, path, name, &indent);
+                    format!("{doc}{indent}.{}: {}\n", key(name), child(node, depth + 1, name.clone(), path, docs))
+                })
+                .collect::<String>();
+
+            format!("{{\n{dict}{close}}}")
+        }
+    }
+}
+
+/// Writes the tree as a kfg file
+pub(crate) fn write_ast(ast: &Ast) -> String {
+    let mut path = vec![];
+
+    sorted(&ast.0)
+        .into_iter()
+        .map(|(name, node)| {
+            let doc = doc(&ast.1.docs, &[], name, "");
+            format!("{doc}{} = {}\n", key(name), child(node, 0, name.clone(), &mut path, &ast.1.docs))
+        })
+        .collect()
+}
+
+fn inline(node: &Node) -> String {
+    match node {
+        Node::Array(array) => {
+            let array = array.iter().map(inline).collect::<Vec<_>>();
+
+            format!("[{}]", array.join(", "))
+        }
+        Node::Dict(dict) => {
+            let dict = sorted(dict)
+                .into_iter()
+                .map(|(name, node)| format!(".{}: {}", key(name), inline(node)))
+                .collect::<Vec<_>>();
+
+            format!("{{{}}}", dict.join(", "))
+        }
+        node => write(node, 0, &mut vec![], &HashMap::new()),
+    }
+}
+
+impl Node {
+    /// Writes the node as a kfg literal, nested dicts and arrays span multiple lines
+    pub fn to_kfg(&self) -> String {
+        write(self, 0, &mut vec![], &HashMap::new())
+    }
+
+    /// Writes the node as a kfg literal on a single line
+    pub fn to_kfg_inline(&self) -> String {
+        inline(self)
+    }
+}
+
+impl Ast {
+    /// Writes the tree as a kfg file with one assignment per top-level key, sorted by key
+    ///
+    /// Docs are written as `///` lines before their keys
+    pub fn to_kfg(&self) -> String {
+        write_ast(self)
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use crate::Kfg;
+
+    #[test]
+    fn test_write() {
+        let ast = Kfg::parse(b"
+            b = { .list: [1, 2.0, 'it\\'s\\n'] .nested: { .x: null } .empty: [] }
+            a = [[true], {}]
+        ").unwrap();
+
+        let expected = "\
+a = [
+    [true],
+    {},
+]
+b = {
+    .empty: []
+    .list: [1, 2.0, 'it\\'s\\n']
+    .nested: {
+        .x: null
+    }
+}
+";
+
+        assert_eq!(ast.to_kfg(), expected);
+        assert_eq!(ast["a"].to_kfg_inline(), "[[true], {}]");
+        assert_eq!(ast["b"].to_kfg_inline(), "{.empty: [], .list: [1, 2.0, 'it\\'s\\n'], .nested: {.x: null}}");
+        assert_eq!(Kfg::parse(expected.as_bytes()).unwrap().0, ast.0);
+
+        let ast = Kfg::parse(b"'1st' = { .'a b': 1, .c-d: 2, .'': 3 }").unwrap();
+        assert_eq!(ast.to_kfg(), "'1st' = {\n    .'': 3\n    .'a b': 1\n    .c-d: 2\n}\n");
+    }
+}
@@ -0,0 +1,623 @@
+use crate::{
+    convert::{self, SyntaxError, Warning},
+    json, Ast, Node,
+};
+use std::collections::HashMap;
+
+const INDENT: &str = "  ";
+
+/// Whether a string can be written without quotes and still be read back as the same string
+fn is_plain(value: &str) -> bool {
+    let mut chars = value.chars();
+
+    let first = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '/');
+    let rest = chars.all(|c| c.is_alphanumeric() || " _./-".contains(c));
+
+    // yaml 1.1 readers take these as booleans
+    let boolean = matches!(value.to_lowercase().as_str(), "y" | "n" | "yes" | "no" | "on" | "off");
+
+    first && rest && !boolean && !value.ends_with(' ') && matches!(scalar(value), Node::String(_))
+}
+
+fn string(value: &str) -> String {
+    if is_plain(value) {
+        value.to_string()
+    } else {
+        // double quoted yaml strings understand every json escape
+        json::string(value)
+    }
+}
+
+fn sorted(dict: &HashMap<String, Node>) -> Vec<(&String, &Node)> {
+    let mut entries = dict.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+fn is_block(node: &Node) -> bool {
+    match node {
+        Node::Array(array) => !array.is_empty(),
+        Node::Dict(dict) => !dict.is_empty(),
+        _ => false,
+    }
+}
+
+fn inline(node: &Node) -> String {
+    match node {
+        Node::String(value) => string(value),
+        Node::Integer(value) => value.to_string(),
+        Node::Float(value) if value.is_nan() => ".nan".into(),
+        Node::Float(value) if value.is_infinite() => if *value > 0.0 { ".inf" } else { "-.inf" }.into(),
+        Node::Float(value) => format!("{value:?}"),
+        Node::Bool(value) => value.to_string(),
+        Node::Null => "null".into(),
+        Node::Array(_) => "[]".into(),
+        Node::Dict(_) => "{}".into(),
+    }
+}
+
+/// Writes a block node, every line but the first is indented by `depth`
+fn block(node: &Node, depth: usize) -> String {
+    let indent = INDENT.repeat(depth);
+
+    match node {
+        Node::Array(array) if !array.is_empty() => array
+            .iter()
+            .map(|node| match node {
+                node if is_block(node) => format!("- {}", block(node, depth + 1)),
+                node => format!("- {}\n", inline(node)),
+            })
+            .collect::<Vec<_>>()
+            .join(&indent),
+        Node::Dict(dict) if !dict.is_empty() => sorted(dict)
+            .into_iter()
+            .map(|(key, node)| match node {
+                Node::Array(_) if is_block(node) => format!("{}:\n{indent}{}", string(key), block(node, depth)),
+                Node::Dict(_) if is_block(node) => format!("{}:\n{indent}{INDENT}{}", string(key), block(node, depth + 1)),
+                node => format!("{}: {}\n", string(key), inline(node)),
+            })
+            .collect::<Vec<_>>()
+            .join(&indent),
+        node => format!("{}\n", inline(node)),
+    }
+}
+
+/// Writes the tree as a YAML document in block style, nothing is lost
+pub(crate) fn write(ast: &Ast, _warnings: &mut Vec<Warning>) -> String {
+    if ast.0.is_empty() {
+        return "{}\n".into();
+    }
+
+    block(&Node::Dict(ast.0.clone()), 0)
+}
+
+/// Resolves a plain scalar with the YAML core schema
+fn scalar(value: &str) -> Node {
+    match value {
+        "" | "~" | "null" | "Null" | "NULL" => Node::Null,
+        "true" | "True" | "TRUE" => Node::Bool(true),
+        "false" | "False" | "FALSE" => Node::Bool(false),
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Node::Float(f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => Node::Float(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => Node::Float(f64::NAN),
+        value => {
+            let number = value.strip_prefix('+').unwrap_or(value);
+
+            if let Some(hex) = number.strip_prefix("0x") {
+                i64::from_str_radix(hex, 16).map_or_else(|_| Node::String(value.into()), Node::Integer)
+            } else if let Some(octal) = number.strip_prefix("0o") {
+                i64::from_str_radix(octal, 8).map_or_else(|_| Node::String(value.into()), Node::Integer)
+            } else if let Ok(integer) = number.parse::<i64>() {
+                Node::Integer(integer)
+            } else if number.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+                && number.chars().all(|c| c.is_ascii_digit() || "-.eE+".contains(c))
+            {
+                number.parse::<f64>().map_or_else(|_| Node::String(value.into()), Node::Float)
+            } else {
+                Node::String(value.into())
+            }
+        }
+    }
+}
+
+struct Reader {
+    lines: Vec<String>,
+    line: usize,
+    /// Sequences and mappings open around the current line
+    depth: usize,
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty() || line.starts_with('#')
+}
+
+fn strip_comment(text: &str) -> &str {
+    let bytes = text.as_bytes();
+
+    for i in 0..bytes.len() {
+        if bytes[i] == b'#' && (i == 0 || bytes[i - 1] == b' ' || bytes[i - 1] == b'\t') {
+            return text[..i].trim_end();
+        }
+    }
+
+    text.trim_end()
+}
+
+fn is_sequence(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+impl Reader {
+    fn error(&self, column: usize, message: impl Into<String>) -> SyntaxError {
+        SyntaxError {
+            line: self.line + 1,
+            column: column + 1,
+            message: message.into(),
+        }
+    }
+
+    /// Moves to the next line with content and returns its indentation
+    fn next(&mut self) -> Option<usize> {
+        while self.line < self.lines.len() && is_blank(&self.lines[self.line]) {
+            self.line += 1;
+        }
+
+        self.lines.get(self.line).map(|line| indent(line))
+    }
+
+    fn text(&self) -> &str {
+        self.lines[self.line].trim_start_matches(' ')
+    }
+
+    /// Splits a mapping entry into its key and the rest of the line
+    fn entry(&self, text: &str, column: usize) -> Result<Option<(String, String)>, SyntaxError> {
+        if text.starts_with(['"', '\'']) {
+            let (key, length) = quoted(text).map_err(|message| self.error(column, message))?;
+            let rest = text[length..].trim_start_matches(' ');
+
+            return match rest.strip_prefix(':') {
+                Some(rest) if rest.is_empty() || rest.starts_with(' ') => Ok(Some((key, rest.trim().to_string()))),
+                _ => Ok(None),
+            };
+        }
+
+        if text.starts_with(['[', '{', '|', '>', '&', '*', '!']) {
+            return Ok(None);
+        }
+
+        let text = strip_comment(text);
+
+        let colon = text
+            .match_indices(':')
+            .map(|(i, _)| i)
+            .find(|&i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '));
+
+        Ok(colon.map(|i| (text[..i].trim_end().to_string(), text[i + 1..].trim().to_string())))
+    }
+
+    fn node(&mut self, min: usize) -> Result<Node, SyntaxError> {
+        let Some(indent) = self.next().filter(|&indent| indent >= min) else {
+            return Ok(Node::Null);
+        };
+
+        let text = self.text().to_string();
+
+        let sequence = is_sequence(&text);
+        let mapping = !sequence && self.entry(&text, indent)?.is_some();
+
+        if !sequence && !mapping {
+            self.line += 1;
+            return self.value(text, indent);
+        }
+
+        if convert::too_deep(self.depth) {
+            return Err(self.error(indent, "too deeply nested"));
+        }
+
+        self.depth += 1;
+
+        let node = match sequence {
+            true => self.sequence(indent),
+            false => self.mapping(indent).map(Node::Dict),
+        };
+
+        self.depth -= 1;
+        node
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Node, SyntaxError> {
+        let mut array = vec![];
+
+        while self.next() == Some(indent) && is_sequence(self.text()) {
+            let rest = self.text()[1..].trim_start_matches(' ').to_string();
+
+            if is_blank(&rest) {
+                self.line += 1;
+                array.push(self.node(indent + 1)?);
+            } else {
+                // read the rest as if it started its own line, so `- key: value` opens a mapping
+                let column = self.lines[self.line].len() - rest.len();
+                self.lines[self.line] = format!("{}{rest}", " ".repeat(column));
+                array.push(self.node(column)?);
+            }
+        }
+
+        Ok(Node::Array(array))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<HashMap<String, Node>, SyntaxError> {
+        let mut dict = HashMap::new();
+
+        while let Some(current) = self.next() {
+            if current < indent {
+                break;
+            }
+
+            if current > indent {
+                return Err(self.error(current, "unexpected indentation"));
+            }
+
+            let text = self.text().to_string();
+
+            let Some((key, rest)) = self.entry(&text, indent)? else {
+                if is_sequence(&text) {
+                    break;
+                }
+
+                return Err(self.error(indent, "expected a mapping entry"));
+            };
+
+            self.line += 1;
+
+            let node = if is_blank(&rest) {
+                match self.next() {
+                    Some(next) if next > indent => self.node(next)?,
+                    Some(next) if next == indent && is_sequence(self.text()) => self.sequence(indent)?,
+                    _ => Node::Null,
+                }
+            } else {
+                self.value(rest, indent)?
+            };
+
+            if dict.insert(key.clone(), node).is_some() {
+                return Err(self.error(indent, format!("duplicate key '{key}'")));
+            }
+        }
+
+        Ok(dict)
+    }
+
+    /// Reads a value that starts on a line, which may continue on the lines after it
+    fn value(&mut self, text: String, indent: usize) -> Result<Node, SyntaxError> {
+        let line = self.line - 1;
+
+        if text.starts_with(['|', '>']) {
+            return self.block_scalar(&text, indent);
+        }
+
+        if text.starts_with(['&', '*', '!']) {
+            return Err(self.error(indent, "anchors, aliases and tags aren't supported"));
+        }
+
+        let mut text = text;
+
+        if text.starts_with(['[', '{']) {
+            // join lines until the collection is closed
+            while flow(&text, self.depth).is_err() && self.line < self.lines.len() {
+                text.push(' ');
+                text.push_str(self.lines[self.line].trim());
+                self.line += 1;
+            }
+
+            let (node, length) = flow(&text, self.depth).map_err(|message| SyntaxError {
+                line: line + 1,
+                column: indent + 1,
+                message,
+            })?;
+
+            if !strip_comment(&text[length..]).trim().is_empty() {
+                return Err(self.error(indent, "unexpected characters after a collection"));
+            }
+
+            return Ok(node);
+        }
+
+        if text.starts_with(['"', '\'']) {
+            let (string, length) = quoted(&text).map_err(|message| self.error(indent, message))?;
+
+            if !strip_comment(&text[length..]).is_empty() {
+                return Err(self.error(indent, "unexpected characters after a string"));
+            }
+
+            return Ok(Node::String(string));
+        }
+
+        Ok(scalar(strip_comment(&text)))
+    }
+
+    fn block_scalar(&mut self, header: &str, indent: usize) -> Result<Node, SyntaxError> {
+        let folded = header.starts_with('>');
+        let chomp = strip_comment(&header[1..]).trim().to_string();
+
+        let mut lines = vec![];
+        let mut content = None;
+
+        while self.line < self.lines.len() {
+            let line = &self.lines[self.line];
+
+            if line.trim().is_empty() {
+                lines.push(String::new());
+                self.line += 1;
+                continue;
+            }
+
+            let current = *content.get_or_insert(self::indent(line));
+
+            if self::indent(line) < current || current <= indent {
+                break;
+            }
+
+            lines.push(line[current..].to_string());
+            self.line += 1;
+        }
+
+        let trailing = lines.iter().rev().take_while(|line| line.is_empty()).count();
+        let body = &lines[..lines.len() - trailing];
+
+        let mut string = if folded {
+            let mut string = String::new();
+
+            for (i, line) in body.iter().enumerate() {
+                match (i, line.is_empty()) {
+                    (0, _) => {}
+                    (_, true) => string.push('\n'),
+                    (_, false) if body[i - 1].is_empty() => {}
+                    _ => string.push(' '),
+                }
+
+                string.push_str(line);
+            }
+
+            string
+        } else {
+            body.join("\n")
+        };
+
+        match chomp.as_str() {
+            "-" => {}
+            "+" => string.push_str(&"\n".repeat(trailing + 1)),
+            _ if !body.is_empty() => string.push('\n'),
+            _ => {}
+        }
+
+        Ok(Node::String(string))
+    }
+}
+
+/// Reads a quoted string at the start of `text`, returning it and how many bytes it took
+fn quoted(text: &str) -> Result<(String, usize), String> {
+    let quote = text.chars().next().unwrap();
+
+    if quote == '"' {
+        let end = text[1..]
+            .char_indices()
+            .scan(false, |escaped, (i, c)| {
+                let end = !*escaped && c == '"';
+                *escaped = !*escaped && c == '\\';
+                Some((i, end))
+            })
+            .find(|(_, end)| *end)
+            .map(|(i, _)| i + 2)
+            .ok_or("unclosed string")?;
+
+        let ast = json::read(format!("{{\"s\": {}}}", &text[..end]).as_bytes(), &mut vec![]).map_err(|error| error.message)?;
+
+        match ast.0.get("s") {
+            Some(Node::String(string)) => Ok((string.clone(), end)),
+            _ => Err("invalid string".into()),
+        }
+    } else {
+        let mut string = String::new();
+        let mut chars = text.char_indices().skip(1).peekable();
+
+        while let Some((i, c)) = chars.next() {
+            if c == '\'' {
+                if chars.next_if(|(_, c)| *c == '\'').is_some() {
+                    string.push('\'');
+                    continue;
+                }
+
+                return Ok((string, i + 1));
+            }
+
+            string.push(c);
+        }
+
+        Err("unclosed string".into())
+    }
+}
+
+/// Reads a flow collection or scalar at the start of `text`, `depth` levels down, returning it
+/// and how many bytes it took
+fn flow(text: &str, depth: usize) -> Result<(Node, usize), String> {
+    let skip = |text: &str, i: usize| i + text[i..].len() - text[i..].trim_start().len();
+
+    let mut i = skip(text, 0);
+
+    match text[i..].chars().next() {
+        Some('[' | '{') if convert::too_deep(depth) => Err("too deeply nested".into()),
+        Some(open @ ('[' | '{')) => {
+            let close = if open == '[' { ']' } else { '}' };
+
+            let mut array = vec![];
+            let mut dict = HashMap::new();
+
+            i = skip(text, i + 1);
+
+            loop {
+                match text[i..].chars().next() {
+                    None => return Err(format!("unclosed '{open}'")),
+                    Some(c) if c == close => return Ok((if open == '[' { Node::Array(array) } else { Node::Dict(dict) }, i + 1)),
+                    _ => {}
+                }
+
+                let (node, length) = flow(&text[i..], depth + 1)?;
+                i = skip(text, i + length);
+
+                if open == '{' {
+                    let Some(rest) = text[i..].strip_prefix(':') else {
+                        return Err("expected ':'".into());
+                    };
+
+                    let key = match node {
+                        Node::String(key) => key,
+                        node => inline(&node),
+                    };
+
+                    let (value, length) = flow(rest, depth + 1)?;
+                    i = skip(text, i + 1 + length);
+
+                    dict.insert(key, value);
+                } else {
+                    array.push(node);
+                }
+
+                match text[i..].chars().next() {
+                    Some(',') => i = skip(text, i + 1),
+                    Some(c) if c == close => {}
+                    _ => return Err(format!("expected ',' or '{close}'")),
+                }
+            }
+        }
+        Some('"' | '\'') => {
+            let (string, length) = quoted(&text[i..])?;
+            Ok((Node::String(string), i + length))
+        }
+        _ => {
+            let end = text[i..]
+                .char_indices()
+                .find(|&(j, c)| ",]}".contains(c) || (c == ':' && text[i + j + 1..].starts_with([' ', ',', ']', '}'])))
+                .map_or(text.len(), |(j, _)| i + j);
+
+            Ok((scalar(text[i..end].trim()), end))
+        }
+    }
+}
+
+/// Reads a YAML document whose top level is a mapping
+///
+/// Covers block and flow collections, quoted, plain and block scalars, but not anchors, aliases or tags
+pub(crate) fn read(buffer: &[u8], _warnings: &mut Vec<Warning>) -> Result<Ast, SyntaxError> {
+    let source = String::from_utf8_lossy(buffer);
+
+    let lines = source
+        .lines()
+        .map(|line| line.trim_end_matches('\r').to_string())
+        .skip_while(|line| is_blank(line) || line.starts_with("%"))
+        .collect::<Vec<_>>();
+
+    let offset = source.lines().count() - lines.len();
+
+    let mut reader = Reader { lines, line: 0, depth: 0 };
+
+    if reader.next().is_some() && reader.text() == "---" {
+        reader.line += 1;
+    }
+
+    let error = |mut error: SyntaxError| {
+        error.line += offset;
+        error
+    };
+
+    let node = match reader.next() {
+        None => Node::Dict(HashMap::new()),
+        Some(indent) => reader.node(indent).map_err(error)?,
+    };
+
+    if reader.next().is_some() && !matches!(reader.text(), "---" | "...") {
+        return Err(error(reader.error(indent(&reader.lines[reader.line]), "unexpected content")));
+    }
+
+    match node {
+        Node::Dict(dict) => Ok(Ast(dict, Default::default())),
+        _ => Err(SyntaxError { line: 1, column: 1, message: "the top level must be a mapping".into() }),
+    }
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read() {
+        let buffer = br#"
+# comment
+---
+name: kfg # trailing
+quoted: "a\tb"
+single: 'it''s'
+number: 0x1F
+float: 1.5e3
+none: ~
+flow: [1, {a: b}, "c, d"]
+server:
+  hosts:
+  - a
+  - b
+  tls:
+    enabled: yes
+list:
+  - name: first
+    port: 80
+  - - nested
+  -
+    deep: true
+text: |
+  line one
+  line two
+folded: >-
+  one
+  two
+"#;
+
+        let expected = HashMap::<String, _>::from([
+            ("name".into(), Node::String("kfg".into())),
+            ("quoted".into(), Node::String("a\tb".into())),
+            ("single".into(), Node::String("it's".into())),
+            ("number".into(), Node::Integer(31)),
+            ("float".into(), Node::Float(1500.0)),
+            ("none".into(), Node::Null),
+            ("flow".into(), Node::Array(vec![
+                Node::Integer(1),
+                Node::Dict(HashMap::from([("a".into(), Node::String("b".into()))])),
+                Node::String("c, d".into()),
+            ])),
+            ("server".into(), Node::Dict(HashMap::from([
+                ("hosts".into(), Node::Array(vec![Node::String("a".into()), Node::String("b".into())])),
+                ("tls".into(), Node::Dict(HashMap::from([("enabled".into(), Node::String("yes".into()))]))),
+            ]))),
+            ("list".into(), Node::Array(vec![
+                Node::Dict(HashMap::from([("name".into(), Node::String("first".into())), ("port".into(), Node::Integer(80))])),
+                Node::Array(vec![Node::String("nested".into())]),
+                Node::Dict(HashMap::from([("deep".into(), Node::Bool(true))])),
+            ])),
+            ("text".into(), Node::String("line one\nline two\n".into())),
+            ("folded".into(), Node::String("one two".into())),
+        ]);
+
+        assert_eq!(read(buffer, &mut vec![]).unwrap().0, expected);
+
+        assert_eq!(read(b"a: 1\n  b: 2", &mut vec![]).unwrap_err(), SyntaxError { line: 2, column: 3, message: "unexpected indentation".into() });
+
+        let deep = format!("a: {}", "[".repeat(20_000));
+        assert_eq!(read(deep.as_bytes(), &mut vec![]).unwrap_err().message, "too deeply nested");
+        let deep: String = (0..200).map(|i| format!("{}a:\n", " ".repeat(i))).collect();
+        assert_eq!(read(deep.as_bytes(), &mut vec![]).unwrap_err().message, "too deeply nested");
+    }
+}